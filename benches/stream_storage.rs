@@ -0,0 +1,182 @@
+// Memory and XRANGE speed of stream nodes against the layout streams had before them, it gets
+// its own binary as counting allocations takes over the global allocator.
+//
+// cargo test --release --bench stream_storage -- bench_storage --nocapture
+
+// only the modules streams are built from are mounted, the commands and the server stay out, so
+// most of what they hold goes unused here
+#![allow(dead_code)]
+
+#[path = "../src/cmd/error.rs"]
+mod cmd_error;
+mod cmd {
+    pub use crate::cmd_error::CmdError;
+}
+#[path = "../src/clock.rs"]
+mod clock;
+#[path = "../src/consumer_group.rs"]
+mod consumer_group;
+#[path = "../src/constants.rs"]
+mod constants;
+#[path = "../src/data_entry.rs"]
+mod data_entry;
+#[path = "../src/hash_entry.rs"]
+mod hash_entry;
+#[path = "../src/list_entry.rs"]
+mod list_entry;
+#[path = "../src/listpack.rs"]
+mod listpack;
+#[path = "../src/resp.rs"]
+mod resp;
+#[path = "../src/scan.rs"]
+mod scan;
+#[path = "../src/set_entry.rs"]
+mod set_entry;
+#[path = "../src/stream_entry.rs"]
+mod stream_entry;
+#[path = "../src/zset_entry.rs"]
+mod zset_entry;
+
+use crate::resp::RespType;
+use crate::stream_entry::{StreamEntry, StreamID};
+use std::collections::BTreeMap;
+
+fn id(millis: u128, seq: u64) -> StreamID {
+    StreamID { millis, seq }
+}
+
+// the layout streams had before nodes, kept to compare against
+#[derive(Default)]
+struct LegacyStream {
+    ids: BTreeMap<u128, std::collections::VecDeque<u64>>,
+    data: std::collections::HashMap<StreamID, BTreeMap<String, String>>,
+}
+
+impl LegacyStream {
+    fn insert(&mut self, id: StreamID, fields: BTreeMap<String, String>) {
+        self.ids.entry(id.millis).or_default().push_back(id.seq);
+        self.data.insert(id, fields);
+    }
+
+    fn range(&self, start: &StreamID, end: &StreamID, count: usize) -> RespType {
+        use RespType::{Array, BulkString};
+        let ids = self
+            .ids
+            .range(start.millis..=end.millis)
+            .flat_map(|(&millis, seqs)| seqs.iter().map(move |&seq| StreamID { millis, seq }))
+            .filter(|id| start <= id && id <= end)
+            .take(count);
+        let entries = ids
+            .map(|id| {
+                let fields = self.data[&id]
+                    .iter()
+                    .flat_map(|(field, value)| [BulkString(field.clone()), BulkString(value.clone())])
+                    .collect();
+                Array(vec![BulkString(id.to_string()), Array(fields)])
+            })
+            .collect();
+        Array(entries)
+    }
+}
+
+// bytes held by allocations made on the current thread, so tests running alongside don't
+// skew the numbers
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATED: std::cell::Cell<isize> = const { std::cell::Cell::new(0) };
+}
+
+fn count_allocated(bytes: isize) {
+    let _ = ALLOCATED.try_with(|allocated| allocated.set(allocated.get() + bytes));
+}
+
+unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        count_allocated(layout.size() as isize);
+        std::alloc::System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        count_allocated(-(layout.size() as isize));
+        std::alloc::System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, new_size: usize) -> *mut u8 {
+        count_allocated(new_size as isize - layout.size() as isize);
+        std::alloc::System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+// what `build` returns, and how many bytes it still holds
+fn retained_by<T>(build: impl FnOnce() -> T) -> (T, isize) {
+    let before = ALLOCATED.with(|allocated| allocated.get());
+    let value = build();
+    (value, ALLOCATED.with(|allocated| allocated.get()) - before)
+}
+
+#[test]
+fn bench_storage() {
+    const ENTRIES: u128 = 100_000;
+    const QUERIES: u128 = 10_000;
+    let stream_id = |i: u128| id(1_700_000_000_000 + i / 4, (i % 4) as u64);
+    let fields = |i: u128| {
+        BTreeMap::from([
+            ("sensor".to_string(), (i % 16).to_string()),
+            ("temperature".to_string(), format!("{}.{}", 20 + i % 10, i % 7)),
+            ("status".to_string(), "ok".to_string()),
+        ])
+    };
+    let (stream, stream_bytes) = retained_by(|| {
+        let mut stream = StreamEntry::new();
+        for i in 1..=ENTRIES {
+            stream.append_stream(&stream_id(i).to_string(), fields(i)).unwrap();
+        }
+        stream
+    });
+    let (legacy, legacy_bytes) = retained_by(|| {
+        let mut legacy = LegacyStream::default();
+        for i in 1..=ENTRIES {
+            legacy.insert(stream_id(i), fields(i));
+        }
+        legacy
+    });
+    println!(
+        "memory for {} entries: nodes {} bytes ({} per entry), legacy {} bytes ({} per entry)",
+        ENTRIES,
+        stream_bytes,
+        stream_bytes / ENTRIES as isize,
+        legacy_bytes,
+        legacy_bytes / ENTRIES as isize,
+    );
+
+    let starts = (0..QUERIES).map(|query| stream_id(query * 7919 % ENTRIES + 1)).collect::<Vec<_>>();
+    for start in starts.iter().take(100) {
+        assert_eq!(
+            stream.query_xrange(start, &StreamID::MAX, Some(10), false),
+            legacy.range(start, &StreamID::MAX, 10)
+        );
+    }
+    let timer = std::time::Instant::now();
+    for start in starts.iter() {
+        stream.query_xrange(start, &StreamID::MAX, Some(10), false);
+    }
+    let stream_time = timer.elapsed();
+    let timer = std::time::Instant::now();
+    for start in starts.iter() {
+        legacy.range(start, &StreamID::MAX, 10);
+    }
+    let legacy_time = timer.elapsed();
+    println!(
+        "{} XRANGE COUNT 10: nodes {:?} ({:?} per query), legacy {:?} ({:?} per query)",
+        QUERIES,
+        stream_time,
+        stream_time / QUERIES as u32,
+        legacy_time,
+        legacy_time / QUERIES as u32,
+    );
+    assert!(stream_bytes < legacy_bytes);
+}
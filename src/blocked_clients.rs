@@ -0,0 +1,259 @@
+use crate::cmd::CmdError;
+use crate::data_entry::{DataEntry, DataValue, ValueType};
+use crate::keyspace_events::EventClass;
+use crate::list_entry::{ListEnd, ListEntry};
+use crate::redis::{get_alive_entry, queue_db_update, queue_expired, Databases, RedisDB, Slaves};
+use crate::resp::RespType;
+use crate::resp_array_of_bulks;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::{oneshot, Mutex};
+
+pub type AMBlockedClients = Arc<Mutex<BlockedClients>>;
+
+pub struct BlockedClient {
+    pub keys: Vec<String>,
+    pub end: ListEnd,
+    // set for BLMOVE, where the popped element must be pushed into `destination`
+    pub destination: Option<(String, ListEnd)>,
+    pub sender: oneshot::Sender<RespType>,
+}
+
+/// Clients blocked on list keys. Every key keeps its waiters in arrival order, so the client that
+/// blocked first is the first one to be served once that key gets new elements.
+#[derive(Default)]
+pub struct BlockedClients {
+    next_id: u64,
+    clients: HashMap<u64, BlockedClient>,
+    waiting: HashMap<String, VecDeque<u64>>,
+    ready_keys: Vec<String>,
+}
+
+impl BlockedClients {
+    pub fn block(&mut self, client: BlockedClient) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        for key in client.keys.iter() {
+            self.waiting.entry(key.clone()).or_default().push_back(id);
+        }
+        self.clients.insert(id, client);
+        id
+    }
+
+    /// Removes the client, returning false if it was already served.
+    pub fn unblock(&mut self, id: u64) -> bool {
+        match self.clients.remove(&id) {
+            Some(client) => {
+                for key in client.keys.iter() {
+                    if let Some(queue) = self.waiting.get_mut(key) {
+                        queue.retain(|waiting_id| *waiting_id != id);
+                        if queue.is_empty() {
+                            self.waiting.remove(key);
+                        }
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks `key` as having new elements, waiters are served on the next
+    /// `serve_blocked_clients` call.
+    pub fn signal_key_ready(&mut self, key: &str) {
+        if self.waiting.contains_key(key) && !self.ready_keys.iter().any(|k| k == key) {
+            self.ready_keys.push(key.to_string());
+        }
+    }
+
+    /// Marks every key someone waits for as ready, used when the keys of the whole database
+    /// were replaced at once.
+    pub fn signal_all_keys_ready(&mut self) {
+        let waiting_keys = self.waiting.keys().cloned().collect::<Vec<_>>();
+        for key in waiting_keys {
+            self.signal_key_ready(&key);
+        }
+    }
+
+    fn next_waiter(&mut self, key: &str) -> Option<BlockedClient> {
+        let queue = self.waiting.get_mut(key)?;
+        while let Some(id) = queue.pop_front() {
+            if let Some(client) = self.clients.remove(&id) {
+                if queue.is_empty() {
+                    self.waiting.remove(key);
+                }
+                for other_key in client.keys.iter().filter(|k| *k != key) {
+                    if let Some(other_queue) = self.waiting.get_mut(other_key) {
+                        other_queue.retain(|waiting_id| *waiting_id != id);
+                    }
+                }
+                return Some(client);
+            }
+        }
+        self.waiting.remove(key);
+        None
+    }
+}
+
+fn get_list<'a>(dict: &'a mut RedisDB, key: &str) -> Result<Option<&'a mut ListEntry>, CmdError> {
+    match get_alive_entry(dict, &ValueType::new(key.to_string())) {
+        Some(DataEntry { value: DataValue::List(list), .. }) => Ok(Some(list)),
+        Some(_) => Err(CmdError::WrongType),
+        None => Ok(None),
+    }
+}
+
+/// Pops from `source` and, for BLMOVE, pushes the element into `destination`.
+pub fn pop_for_client(
+    dict: &mut RedisDB,
+    source: &str,
+    end: ListEnd,
+    destination: &Option<(String, ListEnd)>,
+) -> Result<Option<String>, CmdError> {
+    if let Some((destination, _)) = destination {
+        // fail before popping anything, so the source stays untouched
+        get_list(dict, destination)?;
+    }
+    let value = match get_list(dict, source)?.and_then(|list| list.pop(end)) {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let source_key = ValueType::new(source.to_string());
+    dict.touch(&source_key);
+    dict.notify(EventClass::List, end.pop_event(), &source_key);
+    remove_if_empty(dict, source);
+    if let Some((destination, destination_end)) = destination {
+        push(dict, destination, *destination_end, value.clone());
+    }
+    Ok(Some(value))
+}
+
+/// The reply BLPOP/BRPOP (`[key, element]`) or BLMOVE (`element`) sends for a popped element.
+pub fn reply_for_client(source: &str, value: String, destination: &Option<(String, ListEnd)>) -> RespType {
+    use RespType::{Array, BulkString};
+    match destination {
+        Some(_) => BulkString(value),
+        None => Array(vec![BulkString(source.to_string()), BulkString(value)]),
+    }
+}
+
+/// What replicas run for an element popped by `pop_for_client`, they never block.
+pub fn queue_pop_for_client(
+    slaves: &mut Slaves,
+    db: usize,
+    source: &str,
+    end: ListEnd,
+    destination: &Option<(String, ListEnd)>,
+    value: &str,
+) {
+    let pop = match end {
+        ListEnd::Left => "LPOP",
+        ListEnd::Right => "RPOP",
+    };
+    queue_db_update(slaves, db, &resp_array_of_bulks!(pop, source));
+    // there is no LMOVE for replicas to run instead
+    if let Some((destination, destination_end)) = destination {
+        let push = match destination_end {
+            ListEnd::Left => "LPUSH",
+            ListEnd::Right => "RPUSH",
+        };
+        queue_db_update(slaves, db, &resp_array_of_bulks!(push, destination, value));
+    }
+}
+
+// undoes `pop_for_client` when the blocked client went away before receiving its element
+fn undo_pop_for_client(
+    dict: &mut RedisDB,
+    source: &str,
+    end: ListEnd,
+    destination: &Option<(String, ListEnd)>,
+    value: String,
+) {
+    if let Some((destination, destination_end)) = destination {
+        if let Ok(Some(list)) = get_list(dict, destination) {
+            list.pop(*destination_end);
+            dict.touch(&ValueType::new(destination.to_string()));
+        }
+        remove_if_empty(dict, destination);
+    }
+    push(dict, source, end, value);
+}
+
+fn push(dict: &mut RedisDB, key: &str, end: ListEnd, value: String) {
+    if let Ok(None) = get_list(dict, key) {
+        dict.insert(
+            ValueType::new(key.to_string()),
+            DataEntry::with_value(DataValue::List(ListEntry::new())),
+        );
+    }
+    if let Ok(Some(list)) = get_list(dict, key) {
+        list.push(end, value);
+        let key = ValueType::new(key.to_string());
+        dict.touch(&key);
+        dict.notify(EventClass::List, end.push_event(), &key);
+    }
+}
+
+fn remove_if_empty(dict: &mut RedisDB, key: &str) {
+    if get_list(dict, key).is_ok_and(|list| list.is_some_and(|list| list.is_empty())) {
+        let key = ValueType::new(key.to_string());
+        dict.remove(&key);
+        dict.notify(EventClass::Generic, "del", &key);
+    }
+}
+
+/// Hands the elements pushed into ready keys of database `db` to the clients blocked on them, in
+/// FIFO order, queuing the pops for the replicas. It's called by the write that made them ready
+/// (or after a whole transaction) before it releases the lock of the replicas, never in the
+/// middle of a command.
+pub fn serve_blocked_clients(db: usize, dict: &mut RedisDB, blocked: &mut BlockedClients, slaves: &mut Slaves) {
+    while !blocked.ready_keys.is_empty() {
+        let ready_keys = std::mem::take(&mut blocked.ready_keys);
+        for key in ready_keys {
+            while get_list(dict, &key).is_ok_and(|list| list.is_some()) {
+                let client = match blocked.next_waiter(&key) {
+                    Some(client) => client,
+                    None => break,
+                };
+                if client.sender.is_closed() {
+                    continue;
+                }
+                let value = match pop_for_client(dict, &key, client.end, &client.destination) {
+                    Ok(Some(value)) => value,
+                    Ok(None) => break,
+                    Err(err) => {
+                        let _ = client.sender.send(RespType::SimpleError(err.to_string()));
+                        continue;
+                    }
+                };
+                if let Some((destination, _)) = &client.destination {
+                    blocked.signal_key_ready(destination);
+                }
+                let reply = reply_for_client(&key, value.clone(), &client.destination);
+                let BlockedClient { end, destination, sender, .. } = client;
+                match sender.send(reply) {
+                    Ok(()) => {
+                        queue_expired(slaves, db, dict);
+                        queue_pop_for_client(slaves, db, &key, end, &destination, &value);
+                    }
+                    Err(_) => undo_pop_for_client(dict, &key, end, &destination, value),
+                }
+            }
+        }
+    }
+}
+
+/// `serve_blocked_clients` for every database, since commands like MOVE and SWAPDB make keys
+/// ready outside the database the connection selected.
+pub async fn serve_all_blocked_clients(databases: &Databases, slaves: &mut Slaves) {
+    for (index, db) in databases.iter().enumerate() {
+        if db.blocked_clients.lock().await.ready_keys.is_empty() {
+            continue;
+        }
+        // same lock order as the commands: dict first, then the blocked clients
+        let mut dict_guard = db.dict.lock().await;
+        let mut blocked_guard = db.blocked_clients.lock().await;
+        serve_blocked_clients(index, &mut dict_guard, &mut blocked_guard, slaves);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::data_entry::ValueType;
+use crate::pubsub::Subscriber;
+use crate::resp::RespType;
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+pub type AMClient = Arc<Mutex<ClientState>>;
+/// Raised once any key a client WATCHes changes, shared with the databases holding those keys.
+pub type WatchFlag = Arc<AtomicBool>;
+
+/// Commands queued between MULTI and EXEC.
+#[derive(Debug, Default)]
+pub struct Transaction {
+    pub queued: Vec<RespType>,
+    // a command failed to queue, so EXEC must refuse to run the others
+    pub aborted: bool,
+}
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// State a connection keeps between its commands.
+pub struct ClientState {
+    pub id: u64,
+    // index of the database selected with SELECT
+    pub db: usize,
+    // set between MULTI and EXEC/DISCARD
+    pub tx: Option<Transaction>,
+    // set while EXEC runs the queued commands, which must not block
+    pub in_exec: bool,
+    pub watched: Vec<(usize, ValueType)>,
+    pub watch_dirty: WatchFlag,
+    // where the messages of the channels and patterns it subscribed to go
+    pub subscriber: Subscriber,
+    pub channels: HashSet<String>,
+    pub patterns: HashSet<String>,
+}
+
+impl ClientState {
+    pub fn new(subscriber: Subscriber) -> Self {
+        Self {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::SeqCst),
+            db: 0,
+            tx: None,
+            in_exec: false,
+            watched: Vec::new(),
+            watch_dirty: WatchFlag::default(),
+            subscriber,
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+        }
+    }
+
+    /// A client with subscriptions is in subscribe mode, where only the pub/sub commands work.
+    pub fn subscriptions(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+}
@@ -0,0 +1,5 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn gen_millis() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("Time Went Backwards").as_millis()
+}
@@ -0,0 +1,249 @@
+use async_trait::async_trait;
+
+use crate::blocked_clients::{
+    pop_for_client, queue_pop_for_client, reply_for_client, serve_blocked_clients, AMBlockedClients,
+    BlockedClient,
+};
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::list_entry::ListEnd;
+use crate::redis::{queue_expired, AMRedisDB, AMSlaves, TxLock};
+use crate::resp::RespType;
+use crate::utils::{next_arg, unpack_bulk_string};
+
+use tokio::sync::oneshot;
+use tokio::time::{self, Duration};
+
+// timeout is given in seconds as a float, where 0 means blocking forever
+fn parse_timeout(timeout: &str) -> Result<Option<Duration>, CmdError> {
+    let timeout = timeout
+        .parse::<f64>()
+        .map_err(|_| CmdError::InvalidTimeout)?;
+    if timeout < 0.0 {
+        return Err(CmdError::NegativeTimeout);
+    }
+    if !timeout.is_finite() {
+        return Err(CmdError::InvalidTimeout);
+    }
+    match timeout == 0.0 {
+        true => Ok(None),
+        false => Duration::try_from_secs_f64(timeout)
+            .map(Some)
+            .map_err(|_| CmdError::InvalidTimeout),
+    }
+}
+
+/// Pops from the first non-empty key, otherwise blocks until another client pushes into one of
+/// `keys` or `timeout` passes, in which case `timeout_reply` is returned. Inside a transaction it
+/// never blocks, as if the timeout passed right away. The pop reaches the replicas right when it
+/// happens, either here or in `serve_blocked_clients` for a client that blocked.
+async fn pop_or_block(
+    db: usize,
+    dict: AMRedisDB,
+    blocked_clients: AMBlockedClients,
+    slaves: AMSlaves,
+    tx_lock: TxLock,
+    in_exec: bool,
+    keys: Vec<String>,
+    end: ListEnd,
+    destination: Option<(String, ListEnd)>,
+    timeout: Option<Duration>,
+    timeout_reply: RespType,
+) -> RespType {
+    // EXEC already holds the lock for the whole transaction
+    let tx_guard = match in_exec {
+        true => None,
+        false => Some(tx_lock.read().await),
+    };
+    let mut slaves_guard = slaves.lock().await;
+    let mut dict_guard = dict.lock().await;
+    for key in keys.iter() {
+        match pop_for_client(&mut dict_guard, key, end, &destination) {
+            Ok(Some(value)) => {
+                // BLMOVE may have found the destination expired
+                queue_expired(&mut slaves_guard, db, &mut dict_guard);
+                queue_pop_for_client(&mut slaves_guard, db, key, end, &destination, &value);
+                if let Some((destination, _)) = &destination {
+                    let mut blocked_guard = blocked_clients.lock().await;
+                    blocked_guard.signal_key_ready(destination);
+                    // the clients blocked on the destination go before anyone else pops from it
+                    if !in_exec {
+                        serve_blocked_clients(db, &mut dict_guard, &mut blocked_guard, &mut slaves_guard);
+                    }
+                }
+                return reply_for_client(key, value, &destination);
+            }
+            Ok(None) => (),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        }
+    }
+    if in_exec {
+        return timeout_reply;
+    }
+    // registering while still holding the dict lock, so no push can slip in between
+    let (sender, mut receiver) = oneshot::channel();
+    let id = blocked_clients.lock().await.block(BlockedClient {
+        keys,
+        end,
+        destination,
+        sender,
+    });
+    drop(dict_guard);
+    drop(slaves_guard);
+    drop(tx_guard);
+
+    let reply = match timeout {
+        Some(dur) => time::timeout(dur, &mut receiver).await.ok(),
+        None => Some((&mut receiver).await),
+    };
+    match reply {
+        Some(Ok(reply)) => reply,
+        _ => {
+            // the client could have been served right after the timeout fired
+            match blocked_clients.lock().await.unblock(id) {
+                true => timeout_reply,
+                false => receiver.try_recv().unwrap_or(timeout_reply),
+            }
+        }
+    }
+}
+
+pub struct BPop {
+    pub keys: Vec<String>,
+    pub end: ListEnd,
+    pub timeout: Option<Duration>,
+    pub db: usize,
+    pub dict: AMRedisDB,
+    pub blocked_clients: AMBlockedClients,
+    pub slaves: AMSlaves,
+    pub tx_lock: TxLock,
+    pub in_exec: bool,
+}
+
+#[async_trait]
+impl Cmd for BPop {
+    async fn run(&mut self) -> RespType {
+        pop_or_block(
+            self.db,
+            self.dict.clone(),
+            self.blocked_clients.clone(),
+            self.slaves.clone(),
+            self.tx_lock.clone(),
+            self.in_exec,
+            self.keys.clone(),
+            self.end,
+            None,
+            self.timeout,
+            RespType::WildCard("*-1\r\n".into()),
+        )
+        .await
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match self.end {
+            ListEnd::Left => CmdType::BLPOP,
+            ListEnd::Right => CmdType::BRPOP,
+        }
+    }
+}
+
+impl BPop {
+    pub fn new<'a>(
+        args_iter: &mut impl Iterator<Item = &'a RespType>,
+        db: usize,
+        dict: AMRedisDB,
+        blocked_clients: AMBlockedClients,
+        slaves: AMSlaves,
+        tx_lock: TxLock,
+        in_exec: bool,
+        end: ListEnd,
+    ) -> Result<Self, CmdError> {
+        let mut keys = args_iter
+            .map(unpack_bulk_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        let timeout = keys.pop().ok_or_else(|| CmdError::MissingArgs)?;
+        let timeout = parse_timeout(&timeout)?;
+        if keys.is_empty() {
+            return Err(CmdError::MissingArgs);
+        }
+        Ok(Self {
+            keys,
+            end,
+            timeout,
+            db,
+            dict,
+            blocked_clients,
+            slaves,
+            tx_lock,
+            in_exec,
+        })
+    }
+}
+
+pub struct BLMove {
+    pub source: String,
+    pub destination: String,
+    pub from: ListEnd,
+    pub to: ListEnd,
+    pub timeout: Option<Duration>,
+    pub db: usize,
+    pub dict: AMRedisDB,
+    pub blocked_clients: AMBlockedClients,
+    pub slaves: AMSlaves,
+    pub tx_lock: TxLock,
+    pub in_exec: bool,
+}
+
+#[async_trait]
+impl Cmd for BLMove {
+    async fn run(&mut self) -> RespType {
+        pop_or_block(
+            self.db,
+            self.dict.clone(),
+            self.blocked_clients.clone(),
+            self.slaves.clone(),
+            self.tx_lock.clone(),
+            self.in_exec,
+            vec![self.source.clone()],
+            self.from,
+            Some((self.destination.clone(), self.to)),
+            self.timeout,
+            RespType::Null,
+        )
+        .await
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::BLMOVE
+    }
+}
+
+impl BLMove {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        db: usize,
+        dict: AMRedisDB,
+        blocked_clients: AMBlockedClients,
+        slaves: AMSlaves,
+        tx_lock: TxLock,
+        in_exec: bool,
+    ) -> Result<Self, CmdError> {
+        let source = next_arg(&mut args_iter)?;
+        let destination = next_arg(&mut args_iter)?;
+        let from = ListEnd::parse(&next_arg(&mut args_iter)?).ok_or_else(|| CmdError::InvalidArg)?;
+        let to = ListEnd::parse(&next_arg(&mut args_iter)?).ok_or_else(|| CmdError::InvalidArg)?;
+        let timeout = parse_timeout(&next_arg(&mut args_iter)?)?;
+        Ok(Self {
+            source,
+            destination,
+            from,
+            to,
+            timeout,
+            db,
+            dict,
+            blocked_clients,
+            slaves,
+            tx_lock,
+            in_exec,
+        })
+    }
+}
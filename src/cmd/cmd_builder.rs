@@ -1,21 +1,41 @@
+use crate::client::AMClient;
 use crate::cmd::{
     ack::{Ack, GetAck},
+    blocking_pop::{BLMove, BPop},
     config_get::ConfigGet,
+    db::{DbSize, Flush, Move, Select, SwapDb},
     echo::Echo,
     get::Get,
+    hash::{HDel, HExists, HGet, HGetAll, HIncrBy, HIncrByFloat, HLen, HMGet, HScan, HSet, HashPart},
+    incr::{IncrBy, IncrByFloat},
     info::Info,
-    keys::Keys,
+    keys::{Keys, Scan},
+    keyspace::{Del, Exists, Expire, ExpireUnit, Persist, Ttl},
+    list::{LIndex, LLen, LRange, LRem, LSet, LTrim, Pop, Push},
     misc::{ErrCmd, Ping, ReplConf},
+    object::ObjectEncoding,
+    propagate::Propagate,
     psync::Psync,
+    pubsub::{Publish, PubSubChannels, PubSubNumPat, PubSubNumSub, Subscribe, Unsubscribe},
+    tx::{Discard, Exec, Multi, Queue, Unwatch, Watch},
     set::Set,
+    sets::{SAdd, SCard, SIsMember, SMembers, SRem, SetAlgebra, SetOp},
     typ::Type,
     wait::Wait,
     xadd::XAdd,
+    xgroup::{XGroupConsumer, XGroupCreate, XGroupDestroy, XGroupSetId},
+    xpending::{XAck, XAutoClaim, XClaim, XPending},
     xrange::XRange,
     xread::XRead,
+    xreadgroup::XReadGroup,
+    xinfo::{XInfoConsumers, XInfoGroups, XInfoStream},
+    xtrim::{XDel, XLen, XSetId, XTrim},
+    zset::{ZAdd, ZCard, ZIncrBy, ZRange, ZRank, ZRem, ZScore},
 };
 use crate::cmd::{Cmd, CmdError};
 use crate::redis::*;
+use crate::list_entry::ListEnd;
+use crate::pubsub::AMPubSub;
 use crate::resp::RespType;
 use crate::slave_meta::WriteStream;
 use crate::utils::unpack_bulk_string;
@@ -25,16 +45,29 @@ use std::net::SocketAddr;
 pub struct CmdBuilder;
 
 impl CmdBuilder {
-    pub fn from_resp(
+    pub async fn from_resp(
         resp: RespType,
-        dict: AMRedisDB,
+        databases: Databases,
+        client: AMClient,
         config: AMConfig,
         slaves: AMSlaves,
-        streams: AMStreams,
-        stream_senders: AMStreamSenders,
+        pubsub: AMPubSub,
         socket_addr: Option<SocketAddr>,
         wr: Option<WriteStream>,
+        tx_lock: TxLock,
     ) -> Box<dyn Cmd + Send> {
+        // commands work on the database the connection selected when they were built
+        let client_guard = client.lock().await;
+        let (db_index, in_multi, in_exec) = (client_guard.db, client_guard.tx.is_some(), client_guard.in_exec);
+        let subscribed = client_guard.subscriptions() > 0;
+        drop(client_guard);
+        let queued = in_multi.then(|| (resp.clone(), client.clone()));
+        let (propagate_to, propagate_databases) = (slaves.clone(), databases.clone());
+        let Database {
+            dict,
+            stream_senders,
+            blocked_clients,
+        } = databases[db_index].clone();
         if let RespType::Array(array) = resp {
             let mut array_iter = array.iter();
             let cmd_type = Self::cmd_type(&mut array_iter);
@@ -46,17 +79,16 @@ impl CmdBuilder {
                     }) as Box<dyn Cmd + Send>
                 }
             };
-            let cmd: Result<Box<dyn Cmd + Send>, CmdError> = match cmd_type.to_lowercase().as_str() {
-                "ping" => Ok(Box::new(Ping {})),
+            let name = cmd_type.to_lowercase();
+            let cmd: Result<Box<dyn Cmd + Send>, CmdError> = match name.as_str() {
+                "ping" => Ok(Box::new(Ping { subscribed })),
                 "echo" => Echo::new(&mut array_iter).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
-                "set" => {
-                    Set::new(&mut array_iter, dict, slaves).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
-                }
+                "set" => Set::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
                 "get" => Get::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
                 "info" => {
-                    Info::new(&mut array_iter, config).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
+                    Info::new(&mut array_iter, config, databases).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
                 }
-                "psync" => Psync::new(&mut array_iter, dict, config, slaves, wr, socket_addr)
+                "psync" => Psync::new(&mut array_iter, databases, config, slaves, wr, socket_addr)
                     .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
                 "wait" => {
                     Wait::new(&mut array_iter, slaves).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
@@ -68,21 +100,203 @@ impl CmdBuilder {
                 "replconf" => Self::replconf_cmd(&mut array_iter, slaves, config),
 
                 "keys" => Keys::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
-                "type" => Type::new(&mut array_iter, dict, streams)
+                "scan" => Scan::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "type" => Type::new(&mut array_iter, dict)
                     .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
-                "xadd" => XAdd::new(&mut array_iter, streams, stream_senders)
+                "xadd" => XAdd::new(&mut array_iter, dict, stream_senders)
                     .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
                 "xrange" => {
-                    XRange::new(&mut array_iter, streams).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
+                    XRange::new(&mut array_iter, dict, false).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
+                }
+                "xrevrange" => {
+                    XRange::new(&mut array_iter, dict, true).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
+                }
+                "xread" => XRead::new(&mut array_iter, dict, stream_senders, tx_lock, in_exec)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "xlen" => XLen::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "xdel" => XDel::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "xtrim" => XTrim::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "xsetid" => XSetId::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "xgroup" => Self::xgroup_cmd(&mut array_iter, dict),
+                "xinfo" => Self::xinfo_cmd(&mut array_iter, dict),
+                "xreadgroup" => XReadGroup::new(&mut array_iter, db_index, dict, stream_senders, slaves, tx_lock, in_exec)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "xack" => XAck::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "xpending" => XPending::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "xclaim" => XClaim::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "xautoclaim" => {
+                    XAutoClaim::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
                 }
-                "xread" => XRead::new(&mut array_iter, streams, stream_senders)
+
+                "lpush" => Push::new(&mut array_iter, dict, blocked_clients, ListEnd::Left)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "rpush" => Push::new(&mut array_iter, dict, blocked_clients, ListEnd::Right)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "lpop" => Pop::new(&mut array_iter, dict, ListEnd::Left)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "rpop" => Pop::new(&mut array_iter, dict, ListEnd::Right)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "lrange" => LRange::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "llen" => LLen::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "lindex" => LIndex::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "lset" => LSet::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "lrem" => LRem::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "ltrim" => LTrim::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "blpop" => BPop::new(&mut array_iter, db_index, dict, blocked_clients, slaves, tx_lock, in_exec, ListEnd::Left)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "brpop" => BPop::new(&mut array_iter, db_index, dict, blocked_clients, slaves, tx_lock, in_exec, ListEnd::Right)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "blmove" => BLMove::new(&mut array_iter, db_index, dict, blocked_clients, slaves, tx_lock, in_exec)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+
+                "hset" => HSet::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "hget" => HGet::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "hmget" => HMGet::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "hdel" => HDel::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "hgetall" => HGetAll::new(&mut array_iter, dict, HashPart::All)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "hkeys" => HGetAll::new(&mut array_iter, dict, HashPart::Fields)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "hvals" => HGetAll::new(&mut array_iter, dict, HashPart::Values)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "hlen" => HLen::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "hexists" => HExists::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "hincrby" => HIncrBy::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "hincrbyfloat" => {
+                    HIncrByFloat::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
+                }
+                "hscan" => HScan::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+
+                "sadd" => SAdd::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "srem" => SRem::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "smembers" => SMembers::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "sismember" => SIsMember::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "scard" => SCard::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "sinter" => SetAlgebra::new(&mut array_iter, dict, SetOp::Inter, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "sunion" => SetAlgebra::new(&mut array_iter, dict, SetOp::Union, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "sdiff" => SetAlgebra::new(&mut array_iter, dict, SetOp::Diff, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "sinterstore" => SetAlgebra::new(&mut array_iter, dict, SetOp::Inter, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "sunionstore" => SetAlgebra::new(&mut array_iter, dict, SetOp::Union, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "sdiffstore" => SetAlgebra::new(&mut array_iter, dict, SetOp::Diff, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+
+                "object" => ObjectEncoding::new(&mut array_iter, dict)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+
+                "del" => Del::new(&mut array_iter, dict, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "unlink" => Del::new(&mut array_iter, dict, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "exists" => Exists::new(&mut array_iter, dict)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "expire" => Expire::new(&mut array_iter, dict, ExpireUnit::Seconds, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "pexpire" => Expire::new(&mut array_iter, dict, ExpireUnit::Millis, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "expireat" => Expire::new(&mut array_iter, dict, ExpireUnit::Seconds, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "pexpireat" => Expire::new(&mut array_iter, dict, ExpireUnit::Millis, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "ttl" => Ttl::new(&mut array_iter, dict, ExpireUnit::Seconds, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "pttl" => Ttl::new(&mut array_iter, dict, ExpireUnit::Millis, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "expiretime" => Ttl::new(&mut array_iter, dict, ExpireUnit::Seconds, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "pexpiretime" => Ttl::new(&mut array_iter, dict, ExpireUnit::Millis, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "persist" => Persist::new(&mut array_iter, dict)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+
+                "incr" => IncrBy::new(&mut array_iter, dict, false, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "decr" => IncrBy::new(&mut array_iter, dict, true, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "incrby" => IncrBy::new(&mut array_iter, dict, false, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "decrby" => IncrBy::new(&mut array_iter, dict, true, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "incrbyfloat" => IncrByFloat::new(&mut array_iter, dict)
                     .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
 
-                _ => Ok(Box::new(ErrCmd {
-                    err_msg: CmdError::NotImplementedCmd.to_string(),
-                }) as Box<dyn Cmd + Send>),
+                "zadd" => ZAdd::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "zincrby" => ZIncrBy::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "zrem" => ZRem::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "zscore" => ZScore::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "zcard" => ZCard::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "zrank" => ZRank::new(&mut array_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "zrange" => ZRange::new(&mut array_iter, dict, false)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "zrangebyscore" => ZRange::new(&mut array_iter, dict, true)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+
+                "select" => Select::new(&mut array_iter, &databases, client)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "move" => Move::new(&mut array_iter, databases, db_index)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "swapdb" => SwapDb::new(&mut array_iter, databases)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "dbsize" => DbSize::new(dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "flushdb" => Flush::new(&mut array_iter, databases, Some(db_index))
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "flushall" => Flush::new(&mut array_iter, databases, None)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+
+                "multi" => Multi::new(client).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "exec" => Exec::new(databases, client, config, slaves, pubsub, socket_addr, wr, tx_lock)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "discard" => Discard::new(databases, client).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "watch" => Watch::new(&mut array_iter, db_index, databases, client)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "unwatch" => Unwatch::new(databases, client).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+
+                "subscribe" => Subscribe::new(&mut array_iter, false, client, pubsub)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "psubscribe" => Subscribe::new(&mut array_iter, true, client, pubsub)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "unsubscribe" => Unsubscribe::new(&mut array_iter, false, client, pubsub)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "punsubscribe" => Unsubscribe::new(&mut array_iter, true, client, pubsub)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "publish" => Publish::new(&mut array_iter, pubsub, config, slaves)
+                    .map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+                "pubsub" => Self::pubsub_cmd(&mut array_iter, pubsub),
+
+                _ => Err(CmdError::NotImplementedCmd),
             };
+            if subscribed && !matches!(name.as_str(), "subscribe" | "psubscribe" | "unsubscribe" | "punsubscribe" | "ping") {
+                return Box::new(ErrCmd {
+                    err_msg: CmdError::NotAllowedInSubscribe(name).to_string(),
+                }) as Box<dyn Cmd + Send>;
+            }
+            // after MULTI commands are only parsed and queued, except the ones driving the
+            // transaction itself
+            if let Some((resp, client)) = queued {
+                if !matches!(name.as_str(), "multi" | "exec" | "discard" | "watch") {
+                    let parsed = match name.as_str() {
+                        "psync" | "wait" | "subscribe" | "psubscribe" | "unsubscribe" | "punsubscribe" => {
+                            Err(CmdError::NotAllowedInMulti)
+                        }
+                        _ => cmd.map(|_| ()),
+                    };
+                    return Box::new(Queue { resp, parsed, client }) as Box<dyn Cmd + Send>;
+                }
+            }
             return match cmd {
+                // writes reach the replicas once they ran
+                Ok(cmd) if cmd.cmd_type().is_write() => Box::new(Propagate {
+                    cmd,
+                    resp: RespType::Array(array.clone()),
+                    slaves: propagate_to,
+                    db: db_index,
+                    databases: propagate_databases,
+                    in_exec,
+                }),
                 Ok(cmd) => cmd,
                 Err(err_msg) => Box::new(ErrCmd {
                     err_msg: err_msg.to_string(),
@@ -100,6 +314,51 @@ impl CmdBuilder {
         unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::NoCmdsProvided)?)
     }
 
+    fn pubsub_cmd<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        pubsub: AMPubSub,
+    ) -> Result<Box<dyn Cmd + Send>, CmdError> {
+        let subcommand = unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
+        match subcommand.to_lowercase().as_str() {
+            "channels" => PubSubChannels::new(&mut args_iter, pubsub).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+            "numsub" => PubSubNumSub::new(&mut args_iter, pubsub).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+            "numpat" => PubSubNumPat::new(&mut args_iter, pubsub).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+            _ => Err(CmdError::UnknownSubcommand(subcommand)),
+        }
+    }
+
+    fn xgroup_cmd<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Box<dyn Cmd + Send>, CmdError> {
+        let subcommand = unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
+        match subcommand.to_lowercase().as_str() {
+            "create" => XGroupCreate::new(&mut args_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+            "destroy" => XGroupDestroy::new(&mut args_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+            "createconsumer" => {
+                XGroupConsumer::new(&mut args_iter, dict, true).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
+            }
+            "delconsumer" => {
+                XGroupConsumer::new(&mut args_iter, dict, false).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>)
+            }
+            "setid" => XGroupSetId::new(&mut args_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+            _ => Err(CmdError::UnknownSubcommand(subcommand)),
+        }
+    }
+
+    fn xinfo_cmd<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Box<dyn Cmd + Send>, CmdError> {
+        let subcommand = unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
+        match subcommand.to_lowercase().as_str() {
+            "stream" => XInfoStream::new(&mut args_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+            "groups" => XInfoGroups::new(&mut args_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+            "consumers" => XInfoConsumers::new(&mut args_iter, dict).map(|cmd| Box::new(cmd) as Box<dyn Cmd + Send>),
+            _ => Err(CmdError::UnknownSubcommand(subcommand)),
+        }
+    }
+
     fn replconf_cmd<'a>(
         mut args_iter: &mut impl Iterator<Item = &'a RespType>,
         slaves: AMSlaves,
@@ -0,0 +1,249 @@
+use async_trait::async_trait;
+
+use crate::client::AMClient;
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::data_entry::{DataValue, ValueType};
+use crate::keyspace_events::EventClass;
+use crate::redis::{get_alive_entry, AMRedisDB, Databases};
+use crate::resp::RespType;
+use crate::utils::{next_arg, unpack_bulk_string};
+
+use tokio::sync::{Mutex, MutexGuard};
+
+fn next_db_index<'a>(
+    args_iter: &mut impl Iterator<Item = &'a RespType>,
+    databases: &Databases,
+    invalid: CmdError,
+) -> Result<usize, CmdError> {
+    let index = next_arg(args_iter)?.parse::<i64>().map_err(|_| invalid)?;
+    usize::try_from(index)
+        .ok()
+        .filter(|index| *index < databases.len())
+        .ok_or(CmdError::DBIndexOutOfRange)
+}
+
+// locks the same part of two different databases, always the lower index first so MOVE and
+// SWAPDB can't deadlock each other
+async fn lock_pair<'a, T>(
+    first: (usize, &'a Mutex<T>),
+    second: (usize, &'a Mutex<T>),
+) -> (MutexGuard<'a, T>, MutexGuard<'a, T>) {
+    if first.0 < second.0 {
+        let first_guard = first.1.lock().await;
+        (first_guard, second.1.lock().await)
+    } else {
+        let second_guard = second.1.lock().await;
+        (first.1.lock().await, second_guard)
+    }
+}
+
+pub struct Select {
+    pub index: usize,
+    pub client: AMClient,
+}
+
+#[async_trait]
+impl Cmd for Select {
+    async fn run(&mut self) -> RespType {
+        self.client.lock().await.db = self.index;
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::SELECT
+    }
+}
+
+impl Select {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        databases: &Databases,
+        client: AMClient,
+    ) -> Result<Self, CmdError> {
+        let index = next_db_index(&mut args_iter, databases, CmdError::NotInteger)?;
+        Ok(Self { index, client })
+    }
+}
+
+pub struct Move {
+    pub key: String,
+    pub source: usize,
+    pub destination: usize,
+    pub databases: Databases,
+}
+
+#[async_trait]
+impl Cmd for Move {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let source = &self.databases[self.source];
+        let destination = &self.databases[self.destination];
+        let (mut source_dict, mut destination_dict) =
+            lock_pair((self.source, &source.dict), (self.destination, &destination.dict)).await;
+        let in_source = get_alive_entry(&mut source_dict, &key).is_some();
+        let in_destination = get_alive_entry(&mut destination_dict, &key).is_some();
+        if !in_source || in_destination {
+            return RespType::Integer(0);
+        }
+        // the TTL moves along with the value
+        source_dict.notify(EventClass::Generic, "move_from", &key);
+        destination_dict.notify(EventClass::Generic, "move_to", &key);
+        if let Some(entry) = source_dict.remove(&key) {
+            let is_list = matches!(entry.value, DataValue::List(_));
+            destination_dict.insert(key, entry);
+            if is_list {
+                destination.blocked_clients.lock().await.signal_key_ready(&self.key);
+            }
+        }
+        RespType::Integer(1)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::MOVE
+    }
+}
+
+impl Move {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        databases: Databases,
+        source: usize,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let destination = next_db_index(&mut args_iter, &databases, CmdError::NotInteger)?;
+        if source == destination {
+            return Err(CmdError::SameObject);
+        }
+        Ok(Self {
+            key,
+            source,
+            destination,
+            databases,
+        })
+    }
+}
+
+pub struct SwapDb {
+    pub first: usize,
+    pub second: usize,
+    pub databases: Databases,
+}
+
+#[async_trait]
+impl Cmd for SwapDb {
+    async fn run(&mut self) -> RespType {
+        if self.first != self.second {
+            let first = &self.databases[self.first];
+            let second = &self.databases[self.second];
+            let (mut first_dict, mut second_dict) =
+                lock_pair((self.first, &first.dict), (self.second, &second.dict)).await;
+            first_dict.swap_keys(&mut second_dict);
+            // clients stay blocked in their database, which may hold their keys now
+            for db in [first, second] {
+                db.blocked_clients.lock().await.signal_all_keys_ready();
+            }
+        }
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::SWAPDB
+    }
+}
+
+impl SwapDb {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        databases: Databases,
+    ) -> Result<Self, CmdError> {
+        let first = next_db_index(&mut args_iter, &databases, CmdError::InvalidFirstDBIndex)?;
+        let second = next_db_index(&mut args_iter, &databases, CmdError::InvalidSecondDBIndex)?;
+        Ok(Self {
+            first,
+            second,
+            databases,
+        })
+    }
+}
+
+pub struct DbSize {
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for DbSize {
+    async fn run(&mut self) -> RespType {
+        RespType::Integer(self.dict.lock().await.len() as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::DBSIZE
+    }
+}
+
+impl DbSize {
+    pub fn new(dict: AMRedisDB) -> Result<Self, CmdError> {
+        Ok(Self { dict })
+    }
+}
+
+/// FLUSHDB and FLUSHALL
+pub struct Flush {
+    // `None` flushes all the databases
+    pub db: Option<usize>,
+    pub lazy: bool,
+    pub databases: Databases,
+}
+
+#[async_trait]
+impl Cmd for Flush {
+    async fn run(&mut self) -> RespType {
+        let targets = match self.db {
+            Some(index) => index..index + 1,
+            None => 0..self.databases.len(),
+        };
+        for index in targets {
+            let db = &self.databases[index];
+            let mut dict_guard = db.dict.lock().await;
+            let flushed = dict_guard.flush();
+            drop(dict_guard);
+            // ASYNC leaves freeing the old keys to a background thread
+            if self.lazy {
+                tokio::task::spawn_blocking(move || drop(flushed));
+            }
+        }
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match self.db {
+            Some(_) => CmdType::FLUSHDB,
+            None => CmdType::FLUSHALL,
+        }
+    }
+}
+
+impl Flush {
+    pub fn new<'a>(
+        args_iter: &mut impl Iterator<Item = &'a RespType>,
+        databases: Databases,
+        db: Option<usize>,
+    ) -> Result<Self, CmdError> {
+        let lazy = match args_iter.next() {
+            Some(mode) => match unpack_bulk_string(mode)?.to_lowercase().as_str() {
+                "async" => true,
+                "sync" => false,
+                _ => return Err(CmdError::SyntaxError),
+            },
+            None => false,
+        };
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self {
+            db,
+            lazy,
+            databases,
+        })
+    }
+}
@@ -0,0 +1,117 @@
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum CmdError {
+    #[error("ERROR: No commands where provided")]
+    NoCmdsProvided,
+    #[error("ERROR: Invalid command RESP type")]
+    InvalidCmdType,
+    #[error("ERROR: Missing arguments for current command")]
+    MissingArgs,
+    #[error("ERROR: Invalid argument type")]
+    InvalidArg,
+    #[error("ERROR: Provided command is not implemented")]
+    NotImplementedCmd,
+    #[error("ERROR: value is not an integer or out of range")]
+    NotInteger,
+    #[error("ERROR: value is out of range, must be positive")]
+    NotPositive,
+    #[error("ERROR: value is not a valid float")]
+    NotFloat,
+    #[error("ERROR: hash value is not an integer")]
+    HashValueNotInteger,
+    #[error("ERROR: hash value is not a float")]
+    HashValueNotFloat,
+    #[error("ERROR: increment or decrement would overflow")]
+    Overflow,
+    #[error("ERROR: decrement would overflow")]
+    DecrementOverflow,
+    #[error("ERROR: increment would produce NaN or Infinity")]
+    NanOrInfinity,
+    #[error("ERROR: invalid cursor")]
+    InvalidCursor,
+    #[error("ERROR: syntax error")]
+    SyntaxError,
+    #[error("ERROR: index out of range")]
+    IndexOutOfRange,
+    #[error("ERROR: no such key")]
+    NoSuchKey,
+    #[error("ERROR: timeout is not a float or out of range")]
+    InvalidTimeout,
+    #[error("ERROR: timeout is negative")]
+    NegativeTimeout,
+    #[error("ERROR: invalid expire time in '{0}' command")]
+    InvalidExpireTime(&'static str),
+    #[error("ERROR: Unsupported option {0}")]
+    UnsupportedOption(String),
+    #[error("ERROR: NX and XX, GT or LT options at the same time are not compatible")]
+    NXAndXXGTLT,
+    #[error("ERROR: GT and LT options at the same time are not compatible")]
+    GTAndLT,
+    #[error("ERROR: XX and NX options at the same time are not compatible")]
+    XXAndNX,
+    #[error("ERROR: GT, LT, and/or NX options at the same time are not compatible")]
+    GTLTAndNX,
+    #[error("ERROR: INCR option supports a single increment-element pair")]
+    IncrSinglePair,
+    #[error("ERROR: resulting score is not a number (NaN)")]
+    ScoreIsNan,
+    #[error("ERROR: min or max is not a float")]
+    InvalidScoreRange,
+    #[error("ERROR: min or max not valid string range item")]
+    InvalidLexRange,
+    #[error("ERROR: syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX")]
+    LimitWithoutBy,
+    #[error("ERROR: syntax error, WITHSCORES not supported in combination with BYLEX")]
+    WithScoresByLex,
+    #[error("ERROR: DB index is out of range")]
+    DBIndexOutOfRange,
+    #[error("ERROR: invalid first DB index")]
+    InvalidFirstDBIndex,
+    #[error("ERROR: invalid second DB index")]
+    InvalidSecondDBIndex,
+    #[error("ERROR: source and destination objects are the same")]
+    SameObject,
+    #[error("ERROR: MULTI calls can not be nested")]
+    NestedMulti,
+    #[error("ERROR: EXEC without MULTI")]
+    ExecWithoutMulti,
+    #[error("ERROR: DISCARD without MULTI")]
+    DiscardWithoutMulti,
+    #[error("ERROR: WATCH inside MULTI is not allowed")]
+    WatchInMulti,
+    #[error("ERROR: Command not allowed inside a transaction")]
+    NotAllowedInMulti,
+    #[error("EXECABORT Transaction discarded because of previous errors.")]
+    ExecAbort,
+    #[error("ERROR: Can't execute '{0}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING are allowed in this context")]
+    NotAllowedInSubscribe(String),
+    #[error("ERROR: unknown subcommand '{0}'")]
+    UnknownSubcommand(String),
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("ERROR: Invalid stream ID specified as stream command argument")]
+    InvalidStreamID,
+    #[error("ERROR: Unbalanced '{0}' list of streams: for each stream key an ID or '{1}' must be specified.")]
+    UnbalancedStreams(&'static str, &'static str),
+    #[error("BUSYGROUP Consumer Group name already exists")]
+    BusyGroup,
+    #[error("NOGROUP No such consumer group '{1}' for key name '{0}'")]
+    NoGroup(String, String),
+    #[error("NOGROUP No such key '{0}' or consumer group '{1}' in XREADGROUP with GROUP option")]
+    NoGroupRead(String, String),
+    #[error("ERROR: The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.")]
+    XGroupKeyMissing,
+    #[error("ERROR: The MAXLEN argument must be >= 0.")]
+    NegativeMaxLen,
+    #[error("ERROR: syntax error, LIMIT cannot be used without the special ~ option")]
+    LimitWithoutApprox,
+    #[error("ERROR: invalid start ID for the interval")]
+    InvalidRangeStart,
+    #[error("ERROR: invalid end ID for the interval")]
+    InvalidRangeEnd,
+    #[error("ERROR: The ID specified in XSETID is smaller than the target stream top item")]
+    XSetIdSmallerThanTop,
+    #[error("ERROR: The entries_added specified in XSETID is smaller than the target stream length")]
+    XSetIdEntriesAddedTooSmall,
+    #[error("ERROR: The ID specified in XSETID is smaller than the provided max_deleted_entry_id")]
+    XSetIdSmallerThanMaxDeleted,
+}
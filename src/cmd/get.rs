@@ -2,8 +2,8 @@ use async_trait::async_trait;
 use crate::resp::RespType;
 
 use crate::cmd::{Cmd, CmdError, CmdType};
-use crate::redis::AMRedisDB;
-use crate::data_entry::ValueType;
+use crate::redis::{AMRedisDB, get_alive_entry};
+use crate::data_entry::{DataEntry, DataValue, ValueType};
 use crate::utils::unpack_bulk_string;
 
 pub struct Get {
@@ -16,15 +16,11 @@ impl Cmd for Get {
     async fn run(&mut self) -> RespType {
         let key = ValueType::new(self.key.clone());
         let mut dict_guard = self.dict.lock().await;
-        match dict_guard.get(&key) {
-            Some(data) => {
-                if data.is_expired() {
-                    dict_guard.remove(&key);
-                    RespType::Null
-                } else {
-                    RespType::BulkString(data.value.as_string())
-                }
+        match get_alive_entry(&mut dict_guard, &key) {
+            Some(DataEntry { value: DataValue::String(value), .. }) => {
+                RespType::BulkString(value.as_string())
             }
+            Some(_) => RespType::SimpleError(CmdError::WrongType.to_string()),
             None => RespType::Null,
         }
     }
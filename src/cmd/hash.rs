@@ -0,0 +1,511 @@
+use async_trait::async_trait;
+
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::data_entry::{DataEntry, DataValue, ValueType};
+use crate::keyspace_events::EventClass;
+use crate::glob::glob_match;
+use crate::hash_entry::HashEntry;
+use crate::redis::{get_alive_entry, AMRedisDB, RedisDB};
+use crate::resp::RespType;
+use crate::utils::{add_floats, next_arg, next_float_text_arg, next_int_arg, unpack_bulk_string};
+
+/// Returns the hash stored at `key`, `Ok(None)` if there is no such key.
+fn get_hash<'a>(dict: &'a mut RedisDB, key: &ValueType) -> Result<Option<&'a mut HashEntry>, CmdError> {
+    match get_alive_entry(dict, key) {
+        Some(DataEntry { value: DataValue::Hash(hash), .. }) => Ok(Some(hash)),
+        Some(_) => Err(CmdError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn get_or_create_hash<'a>(dict: &'a mut RedisDB, key: &ValueType) -> Result<&'a mut HashEntry, CmdError> {
+    if get_hash(dict, key)?.is_none() {
+        dict.insert(
+            ValueType::new(key.as_string()),
+            DataEntry::with_value(DataValue::Hash(HashEntry::new())),
+        );
+    }
+    Ok(get_hash(dict, key)?.unwrap())
+}
+
+fn collect_args<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<Vec<String>, CmdError> {
+    let args = args_iter
+        .map(unpack_bulk_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    match args.is_empty() {
+        true => Err(CmdError::MissingArgs),
+        false => Ok(args),
+    }
+}
+
+pub struct HSet {
+    pub key: String,
+    pub pairs: Vec<(String, String)>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for HSet {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        match get_or_create_hash(&mut dict_guard, &key) {
+            Ok(hash) => {
+                let added = self
+                    .pairs
+                    .iter()
+                    .filter(|(field, value)| hash.set(field.clone(), value.clone()))
+                    .count();
+                dict_guard.touch(&key);
+                dict_guard.notify(EventClass::Hash, "hset", &key);
+                RespType::Integer(added as i64)
+            }
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::HSET
+    }
+}
+
+impl HSet {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let args = collect_args(&mut args_iter)?;
+        if args.len() % 2 != 0 {
+            return Err(CmdError::MissingArgs);
+        }
+        let pairs = args
+            .chunks_exact(2)
+            .map(|pair| (pair[0].clone(), pair[1].clone()))
+            .collect();
+        Ok(Self { key, pairs, dict })
+    }
+}
+
+pub struct HGet {
+    pub key: String,
+    pub field: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for HGet {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_hash(&mut *self.dict.lock().await, &key) {
+            Ok(hash) => hash
+                .and_then(|hash| hash.get(&self.field))
+                .map(|value| RespType::BulkString(value.clone()))
+                .unwrap_or(RespType::Null),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::HGET
+    }
+}
+
+impl HGet {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let field = next_arg(&mut args_iter)?;
+        Ok(Self { key, field, dict })
+    }
+}
+
+pub struct HMGet {
+    pub key: String,
+    pub fields: Vec<String>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for HMGet {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_hash(&mut *self.dict.lock().await, &key) {
+            Ok(hash) => RespType::Array(
+                self.fields
+                    .iter()
+                    .map(|field| {
+                        hash.as_ref()
+                            .and_then(|hash| hash.get(field))
+                            .map(|value| RespType::BulkString(value.clone()))
+                            .unwrap_or(RespType::Null)
+                    })
+                    .collect(),
+            ),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::HMGET
+    }
+}
+
+impl HMGet {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let fields = collect_args(&mut args_iter)?;
+        Ok(Self { key, fields, dict })
+    }
+}
+
+pub struct HDel {
+    pub key: String,
+    pub fields: Vec<String>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for HDel {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let (removed, is_empty) = match get_hash(&mut dict_guard, &key) {
+            Ok(Some(hash)) => {
+                let removed = self.fields.iter().filter(|field| hash.remove(field)).count();
+                (removed, hash.is_empty())
+            }
+            Ok(None) => (0, false),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        if removed > 0 {
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::Hash, "hdel", &key);
+        }
+        if is_empty {
+            dict_guard.remove(&key);
+            dict_guard.notify(EventClass::Generic, "del", &key);
+        }
+        RespType::Integer(removed as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::HDEL
+    }
+}
+
+impl HDel {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let fields = collect_args(&mut args_iter)?;
+        Ok(Self { key, fields, dict })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum HashPart {
+    Fields,
+    Values,
+    All,
+}
+
+/// HKEYS, HVALS and HGETALL
+pub struct HGetAll {
+    pub key: String,
+    pub part: HashPart,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for HGetAll {
+    async fn run(&mut self) -> RespType {
+        use RespType::BulkString;
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let hash = match get_hash(&mut dict_guard, &key) {
+            Ok(Some(hash)) => hash,
+            Ok(None) => return RespType::Array(vec![]),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let mut result = Vec::new();
+        for (field, value) in hash.iter() {
+            match self.part {
+                HashPart::Fields => result.push(BulkString(field.clone())),
+                HashPart::Values => result.push(BulkString(value.clone())),
+                HashPart::All => {
+                    result.push(BulkString(field.clone()));
+                    result.push(BulkString(value.clone()));
+                }
+            }
+        }
+        RespType::Array(result)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match self.part {
+            HashPart::Fields => CmdType::HKEYS,
+            HashPart::Values => CmdType::HVALS,
+            HashPart::All => CmdType::HGETALL,
+        }
+    }
+}
+
+impl HGetAll {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+        part: HashPart,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        Ok(Self { key, part, dict })
+    }
+}
+
+pub struct HLen {
+    pub key: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for HLen {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_hash(&mut *self.dict.lock().await, &key) {
+            Ok(hash) => RespType::Integer(hash.map_or(0, |hash| hash.len()) as i64),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::HLEN
+    }
+}
+
+impl HLen {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        Ok(Self { key, dict })
+    }
+}
+
+pub struct HExists {
+    pub key: String,
+    pub field: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for HExists {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_hash(&mut *self.dict.lock().await, &key) {
+            Ok(hash) => RespType::Integer(hash.is_some_and(|hash| hash.contains(&self.field)) as i64),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::HEXISTS
+    }
+}
+
+impl HExists {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let field = next_arg(&mut args_iter)?;
+        Ok(Self { key, field, dict })
+    }
+}
+
+pub struct HIncrBy {
+    pub key: String,
+    pub field: String,
+    pub increment: i64,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for HIncrBy {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let hash = match get_or_create_hash(&mut dict_guard, &key) {
+            Ok(hash) => hash,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let current = match hash.get(&self.field).map(|value| value.parse::<i64>()) {
+            Some(Ok(current)) => current,
+            Some(Err(_)) => return RespType::SimpleError(CmdError::HashValueNotInteger.to_string()),
+            None => 0,
+        };
+        match current.checked_add(self.increment) {
+            Some(value) => {
+                hash.set(self.field.clone(), value.to_string());
+                dict_guard.touch(&key);
+                dict_guard.notify(EventClass::Hash, "hincrby", &key);
+                RespType::Integer(value)
+            }
+            None => RespType::SimpleError(CmdError::Overflow.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::HINCRBY
+    }
+}
+
+impl HIncrBy {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let field = next_arg(&mut args_iter)?;
+        let increment = next_int_arg(&mut args_iter)?;
+        Ok(Self {
+            key,
+            field,
+            increment,
+            dict,
+        })
+    }
+}
+
+pub struct HIncrByFloat {
+    pub key: String,
+    pub field: String,
+    // as typed, see `add_floats`
+    pub increment: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for HIncrByFloat {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let hash = match get_or_create_hash(&mut dict_guard, &key) {
+            Ok(hash) => hash,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let current = match hash.get(&self.field) {
+            Some(current) if current.parse::<f64>().is_ok_and(f64::is_finite) => current.clone(),
+            Some(_) => return RespType::SimpleError(CmdError::HashValueNotFloat.to_string()),
+            None => "0".to_string(),
+        };
+        let value = add_floats(&current, &self.increment);
+        if !value.parse::<f64>().is_ok_and(f64::is_finite) {
+            return RespType::SimpleError(CmdError::NanOrInfinity.to_string());
+        }
+        hash.set(self.field.clone(), value.clone());
+        dict_guard.touch(&key);
+        dict_guard.notify(EventClass::Hash, "hincrbyfloat", &key);
+        RespType::BulkString(value)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::HINCRBYFLOAT
+    }
+}
+
+impl HIncrByFloat {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let field = next_arg(&mut args_iter)?;
+        let increment = next_float_text_arg(&mut args_iter)?;
+        Ok(Self {
+            key,
+            field,
+            increment,
+            dict,
+        })
+    }
+}
+
+pub struct HScan {
+    pub key: String,
+    pub cursor: u64,
+    pub pattern: Option<String>,
+    pub count: usize,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for HScan {
+    async fn run(&mut self) -> RespType {
+        use RespType::{Array, BulkString};
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let hash = match get_hash(&mut dict_guard, &key) {
+            Ok(Some(hash)) => hash,
+            Ok(None) => return Array(vec![BulkString("0".to_string()), Array(vec![])]),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let (cursor, pairs) = hash.scan(self.cursor, self.count);
+        let mut result = Vec::with_capacity(pairs.len() * 2);
+        let matching = pairs.into_iter().filter(|(field, _)| {
+            self.pattern
+                .as_ref()
+                .map_or(true, |pattern| glob_match(pattern.as_bytes(), field.as_bytes()))
+        });
+        for (field, value) in matching {
+            result.push(BulkString(field.clone()));
+            result.push(BulkString(value.clone()));
+        }
+        Array(vec![BulkString(cursor.to_string()), Array(result)])
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::HSCAN
+    }
+}
+
+impl HScan {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let cursor = next_arg(&mut args_iter)?
+            .parse::<u64>()
+            .map_err(|_| CmdError::InvalidCursor)?;
+        let mut pattern = None;
+        let mut count = 10;
+        while let Some(option) = args_iter.next() {
+            match unpack_bulk_string(option)?.to_lowercase().as_str() {
+                "match" => pattern = Some(next_arg(&mut args_iter)?),
+                "count" => {
+                    count = match next_int_arg(&mut args_iter)? {
+                        count if count >= 1 => count as usize,
+                        _ => return Err(CmdError::SyntaxError),
+                    }
+                }
+                _ => return Err(CmdError::SyntaxError),
+            }
+        }
+        Ok(Self {
+            key,
+            cursor,
+            pattern,
+            count,
+            dict,
+        })
+    }
+}
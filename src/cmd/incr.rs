@@ -0,0 +1,148 @@
+use async_trait::async_trait;
+
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::data_entry::{DataEntry, DataValue, ValueType};
+use crate::keyspace_events::EventClass;
+use crate::redis::{get_alive_entry, AMRedisDB, RedisDB};
+use crate::resp::RespType;
+use crate::utils::{add_floats, next_arg, next_float_text_arg, next_int_arg};
+
+/// Replaces the string stored at `key` with `update(current)`, a missing key counts as `None`.
+/// The entry is updated in place so any TTL on the key is kept.
+fn update_string(
+    dict: &mut RedisDB,
+    key: &ValueType,
+    event: &'static str,
+    update: impl FnOnce(Option<&ValueType>) -> Result<ValueType, CmdError>,
+) -> Result<String, CmdError> {
+    let reply = match get_alive_entry(dict, key) {
+        Some(DataEntry { value: DataValue::String(value), .. }) => {
+            *value = update(Some(value))?;
+            let reply = value.as_string();
+            dict.touch(key);
+            reply
+        }
+        Some(_) => return Err(CmdError::WrongType),
+        None => {
+            let value = update(None)?;
+            let reply = value.as_string();
+            dict.insert(ValueType::new(key.as_string()), DataEntry::with_value(DataValue::String(value)));
+            reply
+        }
+    };
+    dict.notify(EventClass::String, event, key);
+    Ok(reply)
+}
+
+/// INCR, DECR, INCRBY and DECRBY, `increment` is already negated for the DECR variants.
+pub struct IncrBy {
+    pub key: String,
+    pub increment: i64,
+    pub decrement: bool,
+    pub by_one: bool,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for IncrBy {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let increment = self.increment;
+        let result = update_string(&mut *self.dict.lock().await, &key, "incrby", |value| {
+            let current = match value {
+                Some(value) => value.as_int().ok_or_else(|| CmdError::NotInteger)?,
+                None => 0,
+            };
+            current
+                .checked_add(increment)
+                .map(ValueType::from_int)
+                .ok_or_else(|| CmdError::Overflow)
+        });
+        match result {
+            Ok(value) => RespType::Integer(value.parse().unwrap()),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match (self.decrement, self.by_one) {
+            (false, true) => CmdType::INCR,
+            (true, true) => CmdType::DECR,
+            (false, false) => CmdType::INCRBY,
+            (true, false) => CmdType::DECRBY,
+        }
+    }
+}
+
+impl IncrBy {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+        decrement: bool,
+        by_one: bool,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let increment = match by_one {
+            true => 1,
+            false => next_int_arg(&mut args_iter)?,
+        };
+        let increment = match decrement {
+            true => increment.checked_neg().ok_or_else(|| CmdError::DecrementOverflow)?,
+            false => increment,
+        };
+        Ok(Self {
+            key,
+            increment,
+            decrement,
+            by_one,
+            dict,
+        })
+    }
+}
+
+pub struct IncrByFloat {
+    pub key: String,
+    // as typed, see `add_floats`
+    pub increment: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for IncrByFloat {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let increment = &self.increment;
+        let result = update_string(&mut *self.dict.lock().await, &key, "incrbyfloat", |value| {
+            let current = match value {
+                Some(value) => Some(value.as_string())
+                    .filter(|current| current.parse::<f64>().is_ok_and(f64::is_finite))
+                    .ok_or_else(|| CmdError::NotFloat)?,
+                None => "0".to_string(),
+            };
+            let result = add_floats(&current, increment);
+            match result.parse::<f64>().is_ok_and(f64::is_finite) {
+                true => Ok(ValueType::new(result)),
+                false => Err(CmdError::NanOrInfinity),
+            }
+        });
+        match result {
+            Ok(value) => RespType::BulkString(value),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::INCRBYFLOAT
+    }
+}
+
+impl IncrByFloat {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let increment = next_float_text_arg(&mut args_iter)?;
+        Ok(Self { key, increment, dict })
+    }
+}
@@ -2,18 +2,30 @@ use async_trait::async_trait;
 use crate::resp::RespType;
 
 use crate::cmd::{Cmd, CmdError, CmdType};
-use crate::redis::AMConfig;
+use crate::redis::{AMConfig, Databases};
+use crate::redis_db::ExpireStats;
 use crate::utils::unpack_bulk_string;
 
 pub struct Info {
     section: Option<String>,
     config: AMConfig,
+    databases: Databases,
 }
 
 #[async_trait]
 impl Cmd for Info {
     async fn run(&mut self) -> RespType {
-        RespType::BulkString(self.config.lock().await.get_info(self.section.clone()))
+        let info = self.config.lock().await.get_info(self.section.clone());
+        let (stats, keyspace) = self.stats_and_keyspace().await;
+        let section = self.section.as_ref().map(|section| section.trim().to_lowercase());
+        match section.as_deref() {
+            None | Some("all" | "everything" | "default") => {
+                RespType::BulkString(format!("{}\n\n{}\n\n{}", info, stats, keyspace))
+            }
+            Some("stats") => RespType::BulkString(stats),
+            Some("keyspace") => RespType::BulkString(keyspace),
+            Some(_) => RespType::BulkString(info),
+        }
     }
 
     fn cmd_type(&self) -> CmdType {
@@ -22,9 +34,27 @@ impl Cmd for Info {
 }
 
 impl Info {
+    // expiry stats summed over the databases, and the keyspace section listing the non-empty ones
+    async fn stats_and_keyspace(&self) -> (String, String) {
+        let mut stats = ExpireStats::default();
+        let mut keyspace = String::from("# Keyspace");
+        for (index, db) in self.databases.iter().enumerate() {
+            let dict_guard = db.dict.lock().await;
+            stats.expired_keys += dict_guard.stats.expired_keys;
+            stats.expired_time_cap_reached_count += dict_guard.stats.expired_time_cap_reached_count;
+            stats.expire_cycle_cpu_micros += dict_guard.stats.expire_cycle_cpu_micros;
+            let keys = dict_guard.len();
+            if keys > 0 {
+                keyspace += &format!("\ndb{}:keys={},expires={},avg_ttl=0", index, keys, dict_guard.expires());
+            }
+        }
+        (stats.to_string(), keyspace)
+    }
+
     pub fn new<'a>(
         mut args_iter: &mut impl Iterator<Item = &'a RespType>,
         config: AMConfig,
+        databases: Databases,
     ) -> Result<Self, CmdError> {
         let section = match args_iter.next() {
             Some(section) => Some(unpack_bulk_string(section)?),
@@ -33,6 +63,7 @@ impl Info {
         Ok(Self {
             section,
             config,
+            databases,
         })
     }
 }
@@ -3,7 +3,8 @@ use async_trait::async_trait;
 use crate::cmd::{Cmd, CmdError, CmdType};
 use crate::resp::RespType;
 use crate::redis::AMRedisDB;
-use crate::utils::unpack_bulk_string;
+use crate::glob::glob_match;
+use crate::utils::{next_arg, next_int_arg, unpack_bulk_string};
 
 pub struct Keys {
     pub pattern: String,
@@ -13,13 +14,15 @@ pub struct Keys {
 #[async_trait]
 impl Cmd for Keys {
     async fn run(&mut self) -> RespType {
-        // TODO: should match the given pattern instead
-        let mut dict_guard = self.dict.lock().await;
-        dict_guard.retain(|_, v| !v.is_expired());
-        let mut result = Vec::with_capacity(dict_guard.len());
-        for (key, _value) in dict_guard.iter() {
-            result.push(RespType::BulkString(key.as_string()));
-        }
+        // expired keys are skipped here and left to the active expiry cycle
+        let dict_guard = self.dict.lock().await;
+        let result = dict_guard
+            .iter()
+            .filter(|(_key, value)| !value.is_expired())
+            .map(|(key, _value)| key.as_string())
+            .filter(|key| glob_match(self.pattern.as_bytes(), key.as_bytes()))
+            .map(RespType::BulkString)
+            .collect();
         drop(dict_guard);
         RespType::Array(result)
     }
@@ -39,3 +42,77 @@ impl Keys {
         Ok(Self { pattern, dict })
     }
 }
+
+pub struct Scan {
+    pub cursor: u64,
+    pub pattern: Option<String>,
+    pub count: usize,
+    pub type_name: Option<String>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for Scan {
+    async fn run(&mut self) -> RespType {
+        use RespType::{Array, BulkString};
+        let dict_guard = self.dict.lock().await;
+        let (cursor, entries) = dict_guard.scan(self.cursor, self.count);
+        // like redis, MATCH and TYPE filter the keys after they were picked so a call may return
+        // no keys while the iteration isn't over yet
+        let keys = entries
+            .into_iter()
+            .filter(|(_key, value)| !value.is_expired())
+            .filter(|(_key, value)| {
+                self.type_name
+                    .as_ref()
+                    .map_or(true, |type_name| value.value.type_as_string() == *type_name)
+            })
+            .map(|(key, _value)| key.as_string())
+            .filter(|key| {
+                self.pattern
+                    .as_ref()
+                    .map_or(true, |pattern| glob_match(pattern.as_bytes(), key.as_bytes()))
+            })
+            .map(BulkString)
+            .collect();
+        Array(vec![BulkString(cursor.to_string()), Array(keys)])
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::SCAN
+    }
+}
+
+impl Scan {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let cursor = next_arg(&mut args_iter)?
+            .parse::<u64>()
+            .map_err(|_| CmdError::InvalidCursor)?;
+        let mut pattern = None;
+        let mut count = 10;
+        let mut type_name = None;
+        while let Some(option) = args_iter.next() {
+            match unpack_bulk_string(option)?.to_lowercase().as_str() {
+                "match" => pattern = Some(next_arg(&mut args_iter)?),
+                "count" => {
+                    count = match next_int_arg(&mut args_iter)? {
+                        count if count >= 1 => count as usize,
+                        _ => return Err(CmdError::SyntaxError),
+                    }
+                }
+                "type" => type_name = Some(next_arg(&mut args_iter)?.to_lowercase()),
+                _ => return Err(CmdError::SyntaxError),
+            }
+        }
+        Ok(Self {
+            cursor,
+            pattern,
+            count,
+            type_name,
+            dict,
+        })
+    }
+}
@@ -0,0 +1,386 @@
+use async_trait::async_trait;
+
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::data_entry::ValueType;
+use crate::keyspace_events::EventClass;
+use crate::redis::{get_alive_entry, AMRedisDB, RedisDB};
+use crate::resp::RespType;
+use crate::resp_array_of_bulks;
+use crate::utils::{next_arg, unpack_bulk_string};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn collect_keys<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<Vec<String>, CmdError> {
+    let keys = args_iter
+        .map(unpack_bulk_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    match keys.is_empty() {
+        true => Err(CmdError::MissingArgs),
+        false => Ok(keys),
+    }
+}
+
+/// Expiry slot of `key`, `None` if there is no such key.
+fn expiry_of<'a>(dict: &'a mut RedisDB, key: &ValueType) -> Option<&'a mut Option<SystemTime>> {
+    get_alive_entry(dict, key).map(|entry| &mut entry.expired_at_unix_millis)
+}
+
+fn remove_key(dict: &mut RedisDB, key: &ValueType, unlink: bool) -> bool {
+    let removed = match get_alive_entry(dict, key) {
+        Some(_) => dict.remove(key),
+        None => None,
+    };
+    if removed.is_some() {
+        dict.notify(EventClass::Generic, "del", key);
+    }
+    match removed {
+        // UNLINK leaves freeing the value to a background thread
+        Some(value) if unlink => {
+            tokio::task::spawn_blocking(move || drop(value));
+            true
+        }
+        Some(_) => true,
+        None => false,
+    }
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as i64,
+        Err(_) => 0,
+    }
+}
+
+/// DEL and UNLINK
+pub struct Del {
+    pub keys: Vec<String>,
+    pub unlink: bool,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for Del {
+    async fn run(&mut self) -> RespType {
+        let mut dict_guard = self.dict.lock().await;
+        let removed = self
+            .keys
+            .iter()
+            .filter(|key| {
+                let key = ValueType::new(key.to_string());
+                remove_key(&mut dict_guard, &key, self.unlink)
+            })
+            .count();
+        RespType::Integer(removed as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match self.unlink {
+            true => CmdType::UNLINK,
+            false => CmdType::DEL,
+        }
+    }
+
+    fn propagated(&self, resp: RespType, reply: &RespType) -> Vec<RespType> {
+        match reply {
+            RespType::Integer(0) => Vec::new(),
+            _ => vec![resp],
+        }
+    }
+}
+
+impl Del {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+        unlink: bool,
+    ) -> Result<Self, CmdError> {
+        let keys = collect_keys(&mut args_iter)?;
+        Ok(Self { keys, unlink, dict })
+    }
+}
+
+pub struct Exists {
+    pub keys: Vec<String>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for Exists {
+    async fn run(&mut self) -> RespType {
+        let mut dict_guard = self.dict.lock().await;
+        // a key given multiple times is counted multiple times
+        let count = self
+            .keys
+            .iter()
+            .filter(|key| {
+                let key = ValueType::new(key.to_string());
+                expiry_of(&mut dict_guard, &key).is_some()
+            })
+            .count();
+        RespType::Integer(count as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::EXISTS
+    }
+}
+
+impl Exists {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let keys = collect_keys(&mut args_iter)?;
+        Ok(Self { keys, dict })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ExpireUnit {
+    Seconds,
+    Millis,
+}
+
+impl ExpireUnit {
+    fn as_millis(&self) -> i64 {
+        match self {
+            Self::Seconds => 1000,
+            Self::Millis => 1,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ExpireConditions {
+    pub nx: bool,
+    pub xx: bool,
+    pub gt: bool,
+    pub lt: bool,
+}
+
+impl ExpireConditions {
+    // a key without a TTL counts as a key with an infinite TTL for GT and LT
+    fn allow(&self, current: Option<SystemTime>, new: SystemTime) -> bool {
+        match current {
+            Some(current) => !self.nx && (!self.gt || new > current) && (!self.lt || new < current),
+            None => !self.xx && !self.gt,
+        }
+    }
+}
+
+/// EXPIRE, PEXPIRE, EXPIREAT and PEXPIREAT
+pub struct Expire {
+    pub key: String,
+    pub time: i64,
+    pub unit: ExpireUnit,
+    pub absolute: bool,
+    pub conditions: ExpireConditions,
+    // what replicas get once it ran, the absolute expiry or the DEL of an expiry in the past
+    pub update: Option<RespType>,
+    pub dict: AMRedisDB,
+}
+
+impl Expire {
+    fn name(&self) -> &'static str {
+        match (self.unit, self.absolute) {
+            (ExpireUnit::Seconds, false) => "expire",
+            (ExpireUnit::Millis, false) => "pexpire",
+            (ExpireUnit::Seconds, true) => "expireat",
+            (ExpireUnit::Millis, true) => "pexpireat",
+        }
+    }
+
+    // unix time in millis the key should expire at, `None` on overflow
+    fn expires_at_millis(&self) -> Option<i64> {
+        let millis = self.time.checked_mul(self.unit.as_millis())?;
+        match self.absolute {
+            true => Some(millis),
+            false => millis.checked_add(unix_millis(SystemTime::now())),
+        }
+    }
+}
+
+#[async_trait]
+impl Cmd for Expire {
+    async fn run(&mut self) -> RespType {
+        let expires_at_millis = match self.expires_at_millis() {
+            Some(millis) => millis,
+            None => return RespType::SimpleError(CmdError::InvalidExpireTime(self.name()).to_string()),
+        };
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let expires_at = UNIX_EPOCH + Duration::from_millis(expires_at_millis.max(0) as u64);
+        let expiry = match expiry_of(&mut dict_guard, &key) {
+            Some(expiry) => expiry,
+            None => return RespType::Integer(0),
+        };
+        if !self.conditions.allow(*expiry, expires_at) {
+            return RespType::Integer(0);
+        }
+        // an expiry in the past deletes the key right away
+        let update = if expires_at <= SystemTime::now() {
+            remove_key(&mut dict_guard, &key, false);
+            resp_array_of_bulks!("DEL", self.key)
+        } else {
+            *expiry = Some(expires_at);
+            dict_guard.track_expiry(&key);
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::Generic, "expire", &key);
+            resp_array_of_bulks!("PEXPIREAT", self.key, expires_at_millis)
+        };
+        self.update = Some(update);
+        RespType::Integer(1)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match (self.unit, self.absolute) {
+            (ExpireUnit::Seconds, false) => CmdType::EXPIRE,
+            (ExpireUnit::Millis, false) => CmdType::PEXPIRE,
+            (ExpireUnit::Seconds, true) => CmdType::EXPIREAT,
+            (ExpireUnit::Millis, true) => CmdType::PEXPIREAT,
+        }
+    }
+
+    fn propagated(&self, _resp: RespType, _reply: &RespType) -> Vec<RespType> {
+        self.update.iter().cloned().collect()
+    }
+}
+
+impl Expire {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+        unit: ExpireUnit,
+        absolute: bool,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let time = next_arg(&mut args_iter)?
+            .parse::<i64>()
+            .map_err(|_| CmdError::NotInteger)?;
+        let mut conditions = ExpireConditions::default();
+        for option in args_iter {
+            match unpack_bulk_string(option)?.to_lowercase().as_str() {
+                "nx" => conditions.nx = true,
+                "xx" => conditions.xx = true,
+                "gt" => conditions.gt = true,
+                "lt" => conditions.lt = true,
+                _ => return Err(CmdError::UnsupportedOption(unpack_bulk_string(option)?)),
+            }
+        }
+        if conditions.nx && (conditions.xx || conditions.gt || conditions.lt) {
+            return Err(CmdError::NXAndXXGTLT);
+        }
+        if conditions.gt && conditions.lt {
+            return Err(CmdError::GTAndLT);
+        }
+        Ok(Self {
+            key,
+            time,
+            unit,
+            absolute,
+            conditions,
+            update: None,
+            dict,
+        })
+    }
+}
+
+/// TTL, PTTL, EXPIRETIME and PEXPIRETIME
+pub struct Ttl {
+    pub key: String,
+    pub unit: ExpireUnit,
+    pub absolute: bool,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for Ttl {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let expires_at = match expiry_of(&mut dict_guard, &key) {
+            Some(Some(expires_at)) => unix_millis(*expires_at),
+            Some(None) => return RespType::Integer(-1),
+            None => return RespType::Integer(-2),
+        };
+        let millis = match self.absolute {
+            true => expires_at,
+            false => (expires_at - unix_millis(SystemTime::now())).max(0),
+        };
+        match self.unit {
+            ExpireUnit::Millis => RespType::Integer(millis),
+            ExpireUnit::Seconds if self.absolute => RespType::Integer(millis / 1000),
+            // rounded to the closest second like redis does
+            ExpireUnit::Seconds => RespType::Integer((millis + 500) / 1000),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match (self.unit, self.absolute) {
+            (ExpireUnit::Seconds, false) => CmdType::TTL,
+            (ExpireUnit::Millis, false) => CmdType::PTTL,
+            (ExpireUnit::Seconds, true) => CmdType::EXPIRETIME,
+            (ExpireUnit::Millis, true) => CmdType::PEXPIRETIME,
+        }
+    }
+}
+
+impl Ttl {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+        unit: ExpireUnit,
+        absolute: bool,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        Ok(Self {
+            key,
+            unit,
+            absolute,
+            dict,
+        })
+    }
+}
+
+pub struct Persist {
+    pub key: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for Persist {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let persisted = match expiry_of(&mut dict_guard, &key) {
+            Some(expiry) => expiry.take().is_some(),
+            None => false,
+        };
+        if persisted {
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::Generic, "persist", &key);
+        }
+        RespType::Integer(persisted as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::PERSIST
+    }
+
+    fn propagated(&self, resp: RespType, reply: &RespType) -> Vec<RespType> {
+        match reply {
+            RespType::Integer(0) => Vec::new(),
+            _ => vec![resp],
+        }
+    }
+}
+
+impl Persist {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        Ok(Self { key, dict })
+    }
+}
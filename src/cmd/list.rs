@@ -0,0 +1,419 @@
+use async_trait::async_trait;
+
+use crate::blocked_clients::AMBlockedClients;
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::data_entry::{DataEntry, DataValue, ValueType};
+use crate::keyspace_events::EventClass;
+use crate::list_entry::{ListEnd, ListEntry};
+use crate::redis::{get_alive_entry, AMRedisDB, RedisDB};
+use crate::resp::RespType;
+use crate::utils::{next_arg, next_int_arg, unpack_bulk_string};
+
+/// Returns the list stored at `key`, `Ok(None)` if there is no such key.
+fn get_list<'a>(dict: &'a mut RedisDB, key: &ValueType) -> Result<Option<&'a mut ListEntry>, CmdError> {
+    match get_alive_entry(dict, key) {
+        Some(DataEntry { value: DataValue::List(list), .. }) => Ok(Some(list)),
+        Some(_) => Err(CmdError::WrongType),
+        None => Ok(None),
+    }
+}
+
+// redis never keeps empty lists around
+fn remove_if_empty(dict: &mut RedisDB, key: &ValueType) {
+    if let Ok(Some(list)) = get_list(dict, key) {
+        if list.is_empty() {
+            dict.remove(key);
+            dict.notify(EventClass::Generic, "del", key);
+        }
+    }
+}
+
+pub struct Push {
+    pub key: String,
+    pub values: Vec<String>,
+    pub end: ListEnd,
+    pub dict: AMRedisDB,
+    pub blocked_clients: AMBlockedClients,
+}
+
+#[async_trait]
+impl Cmd for Push {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        match get_list(&mut dict_guard, &key) {
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                dict_guard.insert(
+                    ValueType::new(self.key.clone()),
+                    DataEntry::with_value(DataValue::List(ListEntry::new())),
+                );
+            }
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let list = get_list(&mut dict_guard, &key).unwrap().unwrap();
+        for value in self.values.iter() {
+            list.push(self.end, value.clone());
+        }
+        let len = list.len();
+        dict_guard.touch(&key);
+        dict_guard.notify(EventClass::List, self.end.push_event(), &key);
+        self.blocked_clients.lock().await.signal_key_ready(&self.key);
+        RespType::Integer(len as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match self.end {
+            ListEnd::Left => CmdType::LPUSH,
+            ListEnd::Right => CmdType::RPUSH,
+        }
+    }
+}
+
+impl Push {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+        blocked_clients: AMBlockedClients,
+        end: ListEnd,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let values = args_iter
+            .map(unpack_bulk_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.is_empty() {
+            return Err(CmdError::MissingArgs);
+        }
+        Ok(Self {
+            key,
+            values,
+            end,
+            dict,
+            blocked_clients,
+        })
+    }
+}
+
+pub struct Pop {
+    pub key: String,
+    pub count: Option<usize>,
+    pub end: ListEnd,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for Pop {
+    async fn run(&mut self) -> RespType {
+        use RespType::{Array, BulkString, Null, SimpleError, WildCard};
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let list = match get_list(&mut dict_guard, &key) {
+            Ok(Some(list)) => list,
+            Ok(None) if self.count.is_some() => return WildCard("*-1\r\n".into()),
+            Ok(None) => return Null,
+            Err(err) => return SimpleError(err.to_string()),
+        };
+        let resp = match self.count {
+            Some(count) => Array(
+                (0..count)
+                    .map_while(|_| list.pop(self.end))
+                    .map(BulkString)
+                    .collect(),
+            ),
+            None => list.pop(self.end).map(BulkString).unwrap_or(Null),
+        };
+        if !matches!(&resp, Array(values) if values.is_empty()) {
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::List, self.end.pop_event(), &key);
+        }
+        remove_if_empty(&mut dict_guard, &key);
+        resp
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match self.end {
+            ListEnd::Left => CmdType::LPOP,
+            ListEnd::Right => CmdType::RPOP,
+        }
+    }
+}
+
+impl Pop {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+        end: ListEnd,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let count = match args_iter.next() {
+            Some(count) => match unpack_bulk_string(count)?.parse::<i64>() {
+                Ok(count) if count >= 0 => Some(count as usize),
+                Ok(_) => return Err(CmdError::NotPositive),
+                Err(_) => return Err(CmdError::NotInteger),
+            },
+            None => None,
+        };
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self {
+            key,
+            count,
+            end,
+            dict,
+        })
+    }
+}
+
+pub struct LRange {
+    pub key: String,
+    pub start: i64,
+    pub stop: i64,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for LRange {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_list(&mut *self.dict.lock().await, &key) {
+            Ok(Some(list)) => RespType::Array(
+                list.range(self.start, self.stop)
+                    .into_iter()
+                    .map(RespType::BulkString)
+                    .collect(),
+            ),
+            Ok(None) => RespType::Array(vec![]),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::LRANGE
+    }
+}
+
+impl LRange {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let start = next_int_arg(&mut args_iter)?;
+        let stop = next_int_arg(&mut args_iter)?;
+        Ok(Self {
+            key,
+            start,
+            stop,
+            dict,
+        })
+    }
+}
+
+pub struct LLen {
+    pub key: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for LLen {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_list(&mut *self.dict.lock().await, &key) {
+            Ok(list) => RespType::Integer(list.map_or(0, |list| list.len()) as i64),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::LLEN
+    }
+}
+
+impl LLen {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        Ok(Self { key, dict })
+    }
+}
+
+pub struct LIndex {
+    pub key: String,
+    pub index: i64,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for LIndex {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_list(&mut *self.dict.lock().await, &key) {
+            Ok(list) => list
+                .and_then(|list| list.index(self.index))
+                .map(|value| RespType::BulkString(value.clone()))
+                .unwrap_or(RespType::Null),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::LINDEX
+    }
+}
+
+impl LIndex {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let index = next_int_arg(&mut args_iter)?;
+        Ok(Self { key, index, dict })
+    }
+}
+
+pub struct LSet {
+    pub key: String,
+    pub index: i64,
+    pub value: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for LSet {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let err = match get_list(&mut dict_guard, &key) {
+            Ok(Some(list)) => match list.set(self.index, self.value.clone()) {
+                true => {
+                    dict_guard.touch(&key);
+                    dict_guard.notify(EventClass::List, "lset", &key);
+                    return RespType::SimpleString("OK".to_string());
+                }
+                false => CmdError::IndexOutOfRange,
+            },
+            Ok(None) => CmdError::NoSuchKey,
+            Err(err) => err,
+        };
+        RespType::SimpleError(err.to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::LSET
+    }
+}
+
+impl LSet {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let index = next_int_arg(&mut args_iter)?;
+        let value = next_arg(&mut args_iter)?;
+        Ok(Self {
+            key,
+            index,
+            value,
+            dict,
+        })
+    }
+}
+
+pub struct LRem {
+    pub key: String,
+    pub count: i64,
+    pub value: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for LRem {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let removed = match get_list(&mut dict_guard, &key) {
+            Ok(Some(list)) => list.remove(self.count, &self.value),
+            Ok(None) => 0,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        if removed > 0 {
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::List, "lrem", &key);
+        }
+        remove_if_empty(&mut dict_guard, &key);
+        RespType::Integer(removed as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::LREM
+    }
+}
+
+impl LRem {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let count = next_int_arg(&mut args_iter)?;
+        let value = next_arg(&mut args_iter)?;
+        Ok(Self {
+            key,
+            count,
+            value,
+            dict,
+        })
+    }
+}
+
+pub struct LTrim {
+    pub key: String,
+    pub start: i64,
+    pub stop: i64,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for LTrim {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        match get_list(&mut dict_guard, &key) {
+            Ok(Some(list)) => {
+                list.trim(self.start, self.stop);
+                dict_guard.touch(&key);
+                dict_guard.notify(EventClass::List, "ltrim", &key);
+            }
+            Ok(None) => (),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        remove_if_empty(&mut dict_guard, &key);
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::LTRIM
+    }
+}
+
+impl LTrim {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let start = next_int_arg(&mut args_iter)?;
+        let stop = next_int_arg(&mut args_iter)?;
+        Ok(Self {
+            key,
+            start,
+            stop,
+            dict,
+        })
+    }
+}
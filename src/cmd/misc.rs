@@ -1,14 +1,21 @@
 use async_trait::async_trait;
 use crate::resp::RespType;
+use crate::resp_array_of_bulks;
 
 use crate::cmd::{Cmd, CmdType};
 
-pub struct Ping;
+pub struct Ping {
+    // subscribed clients get their PONG in the same shape as the messages
+    pub subscribed: bool,
+}
 
 #[async_trait]
 impl Cmd for Ping {
     async fn run(&mut self) -> RespType {
-        RespType::SimpleString("PONG".to_string())
+        match self.subscribed {
+            true => resp_array_of_bulks!("pong", ""),
+            false => RespType::SimpleString("PONG".to_string()),
+        }
     }
 
     fn cmd_type(&self) -> CmdType {
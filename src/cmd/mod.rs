@@ -1,18 +1,38 @@
 pub mod ack;
+pub mod list;
+pub mod blocking_pop;
+pub mod hash;
+pub mod sets;
+pub mod zset;
+pub mod object;
 pub mod info;
 pub mod misc;
 pub mod echo;
 pub mod get;
+pub mod incr;
 pub mod set;
 pub mod psync;
 pub mod keys;
+pub mod keyspace;
 pub mod typ;
 pub mod wait;
 pub mod xadd;
 pub mod xrange;
 pub mod xread;
+pub mod xgroup;
+pub mod xreadgroup;
+pub mod xpending;
+pub mod xtrim;
+pub mod xinfo;
 pub mod config_get;
+pub mod db;
+pub mod tx;
+pub mod pubsub;
 pub mod cmd_builder;
+pub mod propagate;
+mod error;
+
+pub use error::CmdError;
 
 use async_trait::async_trait;
 use crate::resp::RespType;
@@ -22,6 +42,13 @@ pub trait Cmd {
     async fn run(&mut self) -> RespType;
 
     fn cmd_type(&self) -> CmdType;
+
+    /// What replicas run to end up with the same changes, asked once a write command replied
+    /// anything but an error. `resp` is the command as the client sent it, which is all it takes
+    /// unless the outcome depends on when or where it ran, like `*` ids or relative expiries.
+    fn propagated(&self, resp: RespType, _reply: &RespType) -> Vec<RespType> {
+        vec![resp]
+    }
 }
 
 pub enum CmdType {
@@ -32,6 +59,7 @@ pub enum CmdType {
     ERR_CMD,
 
     KEYS,
+    SCAN,
     TYPE,
 
     SET,
@@ -45,20 +73,184 @@ pub enum CmdType {
 
     XADD,
     XRANGE,
+    XREVRANGE,
     XREAD,
+    XGROUP_CREATE,
+    XGROUP_DESTROY,
+    XGROUP_CREATECONSUMER,
+    XGROUP_DELCONSUMER,
+    XGROUP_SETID,
+    XREADGROUP,
+    XACK,
+    XPENDING,
+    XCLAIM,
+    XAUTOCLAIM,
+    XLEN,
+    XDEL,
+    XTRIM,
+    XSETID,
+    XINFO_STREAM,
+    XINFO_GROUPS,
+    XINFO_CONSUMERS,
+
+    LPUSH,
+    RPUSH,
+    LPOP,
+    RPOP,
+    LRANGE,
+    LLEN,
+    LINDEX,
+    LSET,
+    LREM,
+    LTRIM,
+    BLPOP,
+    BRPOP,
+    BLMOVE,
+
+    HSET,
+    HGET,
+    HMGET,
+    HDEL,
+    HGETALL,
+    HKEYS,
+    HVALS,
+    HLEN,
+    HEXISTS,
+    HINCRBY,
+    HINCRBYFLOAT,
+    HSCAN,
+
+    SADD,
+    SREM,
+    SMEMBERS,
+    SISMEMBER,
+    SCARD,
+    SINTER,
+    SUNION,
+    SDIFF,
+    SINTERSTORE,
+    SUNIONSTORE,
+    SDIFFSTORE,
+
+    OBJECT_ENCODING,
+
+    DEL,
+    UNLINK,
+    EXISTS,
+    EXPIRE,
+    PEXPIRE,
+    EXPIREAT,
+    PEXPIREAT,
+    TTL,
+    PTTL,
+    EXPIRETIME,
+    PEXPIRETIME,
+    PERSIST,
+
+    INCR,
+    DECR,
+    INCRBY,
+    DECRBY,
+    INCRBYFLOAT,
+
+    ZADD,
+    ZINCRBY,
+    ZREM,
+    ZSCORE,
+    ZCARD,
+    ZRANK,
+    ZRANGE,
+    ZRANGEBYSCORE,
+
+    SELECT,
+    MOVE,
+    SWAPDB,
+    DBSIZE,
+    FLUSHDB,
+    FLUSHALL,
+
+    MULTI,
+    EXEC,
+    DISCARD,
+    WATCH,
+    UNWATCH,
+    QUEUED,
+
+    SUBSCRIBE,
+    PSUBSCRIBE,
+    UNSUBSCRIBE,
+    PUNSUBSCRIBE,
+    PUBLISH,
+    PUBSUB_CHANNELS,
+    PUBSUB_NUMSUB,
+    PUBSUB_NUMPAT,
 }
 
-#[derive(Debug, PartialEq, thiserror::Error)]
-pub enum CmdError {
-    #[error("ERROR: No commands where provided")]
-    NoCmdsProvided,
-    #[error("ERROR: Invalid command RESP type")]
-    InvalidCmdType,
-    #[error("ERROR: Missing arguments for current command")]
-    MissingArgs,
-    #[error("ERROR: Invalid argument type")]
-    InvalidArg,
-    #[error("ERROR: Provided command is not implemented")]
-    NotImplementedCmd,
+impl CmdType {
+    /// Commands that may change keys, they reach the replicas through `Propagate`. PUBLISH
+    /// touches no key and the blocking commands can't hold the replicas' lock while they wait,
+    /// so those propagate on their own.
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Self::SET
+                | Self::XADD
+                | Self::XGROUP_CREATE
+                | Self::XGROUP_DESTROY
+                | Self::XGROUP_CREATECONSUMER
+                | Self::XGROUP_DELCONSUMER
+                | Self::XGROUP_SETID
+                | Self::XACK
+                | Self::XCLAIM
+                | Self::XAUTOCLAIM
+                | Self::XDEL
+                | Self::XTRIM
+                | Self::XSETID
+                | Self::LPUSH
+                | Self::RPUSH
+                | Self::LPOP
+                | Self::RPOP
+                | Self::LSET
+                | Self::LREM
+                | Self::LTRIM
+                | Self::HSET
+                | Self::HDEL
+                | Self::HINCRBY
+                | Self::HINCRBYFLOAT
+                | Self::SADD
+                | Self::SREM
+                | Self::SINTERSTORE
+                | Self::SUNIONSTORE
+                | Self::SDIFFSTORE
+                | Self::DEL
+                | Self::UNLINK
+                | Self::EXPIRE
+                | Self::PEXPIRE
+                | Self::EXPIREAT
+                | Self::PEXPIREAT
+                | Self::PERSIST
+                | Self::INCR
+                | Self::DECR
+                | Self::INCRBY
+                | Self::DECRBY
+                | Self::INCRBYFLOAT
+                | Self::ZADD
+                | Self::ZINCRBY
+                | Self::ZREM
+                | Self::MOVE
+                | Self::SWAPDB
+                | Self::FLUSHDB
+                | Self::FLUSHALL
+        )
+    }
+
+    /// Commands that take the transaction lock on their own instead of holding it while they
+    /// run, either because they may block for long or because they are EXEC.
+    pub fn manages_tx_lock(&self) -> bool {
+        matches!(
+            self,
+            Self::BLPOP | Self::BRPOP | Self::BLMOVE | Self::XREAD | Self::XREADGROUP | Self::WAIT | Self::EXEC
+        )
+    }
 }
 
@@ -0,0 +1,41 @@
+use async_trait::async_trait;
+
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::data_entry::ValueType;
+use crate::redis::{get_alive_entry, AMRedisDB};
+use crate::resp::RespType;
+use crate::utils::next_arg;
+
+pub struct ObjectEncoding {
+    pub key: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for ObjectEncoding {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_alive_entry(&mut *self.dict.lock().await, &key) {
+            Some(data) => RespType::BulkString(data.value.encoding().to_string()),
+            None => RespType::Null,
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::OBJECT_ENCODING
+    }
+}
+
+impl ObjectEncoding {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let subcommand = next_arg(&mut args_iter)?;
+        if subcommand.to_lowercase().as_str() != "encoding" {
+            return Err(CmdError::NotImplementedCmd);
+        }
+        let key = next_arg(&mut args_iter)?;
+        Ok(Self { key, dict })
+    }
+}
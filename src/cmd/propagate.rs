@@ -0,0 +1,49 @@
+use async_trait::async_trait;
+
+use crate::blocked_clients::serve_all_blocked_clients;
+use crate::cmd::{Cmd, CmdType};
+use crate::redis::{queue_db_update, queue_expired, AMSlaves, Databases};
+use crate::resp::RespType;
+
+/// Runs a write command and hands what it changed to the replicas, in the deterministic form
+/// the command gives through `Cmd::propagated`. Inside a transaction `slaves` are the shadow
+/// ones of EXEC, so the writes end up wrapped in MULTI/EXEC. The lock of `slaves` is held while
+/// the command runs, so concurrent writes reach the replicas in the order they were applied, and
+/// until the clients blocked on the keys it pushed into were served, so no other pop gets ahead
+/// of them.
+pub struct Propagate {
+    pub cmd: Box<dyn Cmd + Send>,
+    // the command as the client sent it
+    pub resp: RespType,
+    pub slaves: AMSlaves,
+    pub db: usize,
+    pub databases: Databases,
+    // EXEC serves the blocked clients once the whole transaction ran
+    pub in_exec: bool,
+}
+
+#[async_trait]
+impl Cmd for Propagate {
+    async fn run(&mut self) -> RespType {
+        let mut slaves_guard = self.slaves.lock().await;
+        let reply = self.cmd.run().await;
+        // failed commands change nothing
+        if !matches!(reply, RespType::SimpleError(_)) {
+            // the keys it found expired were gone before it ran, MOVE looks into two databases
+            for (index, db) in self.databases.iter().enumerate() {
+                queue_expired(&mut slaves_guard, index, &mut *db.dict.lock().await);
+            }
+            for update in self.cmd.propagated(self.resp.clone(), &reply) {
+                queue_db_update(&mut slaves_guard, self.db, &update);
+            }
+        }
+        if !self.in_exec {
+            serve_all_blocked_clients(&self.databases, &mut slaves_guard).await;
+        }
+        reply
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        self.cmd.cmd_type()
+    }
+}
@@ -2,17 +2,17 @@ use async_trait::async_trait;
 
 use crate::resp::RespType;
 use crate::cmd::{Cmd, CmdError, CmdType};
-use crate::redis::{AMRedisDB, AMConfig, AMSlaves, db_as_rdb};
+use crate::redis::{AMConfig, AMSlaves, Databases};
 use crate::slave_meta::{WriteStream, SlaveMeta};
 use crate::rdb::RDBHeader;
-use crate::utils::unpack_bulk_string;
+use crate::utils::{dump_rdb_file, unpack_bulk_string};
 
 use std::net::SocketAddr;
 
 pub struct Psync {
     pub replid: String,
     pub offset: i64,
-    pub dict: AMRedisDB,
+    pub databases: Databases,
     pub config: AMConfig,
     pub slaves: AMSlaves,
     pub wr: WriteStream,
@@ -24,12 +24,13 @@ impl Cmd for Psync {
     async fn run(&mut self) -> RespType {
         let rdb_header = RDBHeader {
             magic: String::from("REDIS"),
-            rdb_version: 3,
+            // the first version with STREAM_LISTPACKS_3, the type streams are dumped as
+            rdb_version: 11,
             aux_settings: std::collections::HashMap::new(),
         };
-        let mut rdb_content = rdb_header.as_rdb();
-        rdb_content.extend_from_slice(&db_as_rdb(self.dict.clone()).await[..]);
-        rdb_content.push(crate::constants::EOF);
+        // registered along with the snapshot, so no write can land between the two and be missed
+        let mut slaves_guard = self.slaves.lock().await;
+        let rdb_content = dump_rdb_file(&rdb_header, &self.databases).await;
 
         let mut msg: Vec<u8> = format!(
             "+FULLRESYNC {} 0\r\n",
@@ -40,9 +41,7 @@ impl Cmd for Psync {
         msg.extend_from_slice(format!("${}\r\n", rdb_content.len()).as_bytes());
         msg.extend_from_slice(&rdb_content);
 
-        self.slaves
-            .lock()
-            .await
+        slaves_guard
             .entry(self.socket_addr)
             .or_insert(SlaveMeta {
                 expected_offset: 0,
@@ -51,6 +50,7 @@ impl Cmd for Psync {
                 socket_addr: self.socket_addr,
                 wr: self.wr.clone(),
                 pending_updates: Vec::new(),
+                selected_db: None,
             });
 
         RespType::WildCard(msg)
@@ -64,7 +64,7 @@ impl Cmd for Psync {
 impl Psync {
     pub fn new<'a>(
         mut args_iter: &mut impl Iterator<Item = &'a RespType>,
-        dict: AMRedisDB,
+        databases: Databases,
         config: AMConfig,
         slaves: AMSlaves,
         wr: Option<WriteStream>,
@@ -78,7 +78,7 @@ impl Psync {
         Ok(Self {
             replid,
             offset,
-            dict,
+            databases,
             config,
             slaves,
             wr: wr.unwrap(),
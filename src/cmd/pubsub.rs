@@ -0,0 +1,313 @@
+use async_trait::async_trait;
+
+use crate::client::AMClient;
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::pubsub::AMPubSub;
+use crate::redis::{add_pending_update_resp, AMConfig, AMSlaves};
+use crate::resp::RespType;
+use crate::resp_array_of_bulks;
+use crate::utils::{next_arg, unpack_bulk_string};
+
+// SUBSCRIBE and friends reply once per channel, with how many subscriptions the client has left
+fn subscription_reply(kind: &str, name: Option<&str>, subscriptions: usize) -> Vec<u8> {
+    use RespType::{Array, BulkString, Integer, Null};
+    Array(vec![
+        BulkString(kind.to_string()),
+        name.map_or(Null, |name| BulkString(name.to_string())),
+        Integer(subscriptions as i64),
+    ])
+    .serialize()
+}
+
+/// SUBSCRIBE and PSUBSCRIBE
+pub struct Subscribe {
+    pub names: Vec<String>,
+    // subscribes to patterns instead of channels
+    pub pattern: bool,
+    pub client: AMClient,
+    pub pubsub: AMPubSub,
+}
+
+#[async_trait]
+impl Cmd for Subscribe {
+    async fn run(&mut self) -> RespType {
+        let mut client_guard = self.client.lock().await;
+        let mut pubsub_guard = self.pubsub.lock().await;
+        let (client_id, subscriber) = (client_guard.id, client_guard.subscriber.clone());
+        let mut replies = Vec::new();
+        for name in self.names.iter() {
+            match self.pattern {
+                true => {
+                    if client_guard.patterns.insert(name.clone()) {
+                        pubsub_guard.psubscribe(name, client_id, &subscriber);
+                    }
+                }
+                false => {
+                    if client_guard.channels.insert(name.clone()) {
+                        pubsub_guard.subscribe(name, client_id, &subscriber);
+                    }
+                }
+            }
+            let kind = if self.pattern { "psubscribe" } else { "subscribe" };
+            replies.extend(subscription_reply(kind, Some(name), client_guard.subscriptions()));
+        }
+        RespType::WildCard(replies)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match self.pattern {
+            true => CmdType::PSUBSCRIBE,
+            false => CmdType::SUBSCRIBE,
+        }
+    }
+}
+
+impl Subscribe {
+    pub fn new<'a>(
+        args_iter: &mut impl Iterator<Item = &'a RespType>,
+        pattern: bool,
+        client: AMClient,
+        pubsub: AMPubSub,
+    ) -> Result<Self, CmdError> {
+        let names = args_iter
+            .map(unpack_bulk_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        if names.is_empty() {
+            return Err(CmdError::MissingArgs);
+        }
+        Ok(Self {
+            names,
+            pattern,
+            client,
+            pubsub,
+        })
+    }
+}
+
+/// UNSUBSCRIBE and PUNSUBSCRIBE, without arguments they drop every channel (or pattern).
+pub struct Unsubscribe {
+    pub names: Vec<String>,
+    pub pattern: bool,
+    pub client: AMClient,
+    pub pubsub: AMPubSub,
+}
+
+#[async_trait]
+impl Cmd for Unsubscribe {
+    async fn run(&mut self) -> RespType {
+        let mut client_guard = self.client.lock().await;
+        let mut pubsub_guard = self.pubsub.lock().await;
+        let client_id = client_guard.id;
+        let kind = if self.pattern { "punsubscribe" } else { "unsubscribe" };
+        let names = match self.names.is_empty() {
+            true => {
+                let subscribed = match self.pattern {
+                    true => &client_guard.patterns,
+                    false => &client_guard.channels,
+                };
+                let mut subscribed = subscribed.iter().cloned().collect::<Vec<_>>();
+                subscribed.sort();
+                subscribed
+            }
+            false => self.names.clone(),
+        };
+        if names.is_empty() {
+            return RespType::WildCard(subscription_reply(kind, None, client_guard.subscriptions()));
+        }
+        let mut replies = Vec::new();
+        for name in names.iter() {
+            match self.pattern {
+                true => {
+                    if client_guard.patterns.remove(name) {
+                        pubsub_guard.punsubscribe(name, client_id);
+                    }
+                }
+                false => {
+                    if client_guard.channels.remove(name) {
+                        pubsub_guard.unsubscribe(name, client_id);
+                    }
+                }
+            }
+            replies.extend(subscription_reply(kind, Some(name), client_guard.subscriptions()));
+        }
+        RespType::WildCard(replies)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match self.pattern {
+            true => CmdType::PUNSUBSCRIBE,
+            false => CmdType::UNSUBSCRIBE,
+        }
+    }
+}
+
+impl Unsubscribe {
+    pub fn new<'a>(
+        args_iter: &mut impl Iterator<Item = &'a RespType>,
+        pattern: bool,
+        client: AMClient,
+        pubsub: AMPubSub,
+    ) -> Result<Self, CmdError> {
+        let names = args_iter
+            .map(unpack_bulk_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            names,
+            pattern,
+            client,
+            pubsub,
+        })
+    }
+}
+
+/// Drops every subscription of a client, used when it disconnects.
+pub async fn unsubscribe_all(client: &AMClient, pubsub: &AMPubSub) {
+    let mut client_guard = client.lock().await;
+    let mut pubsub_guard = pubsub.lock().await;
+    let client_id = client_guard.id;
+    for channel in std::mem::take(&mut client_guard.channels) {
+        pubsub_guard.unsubscribe(&channel, client_id);
+    }
+    for pattern in std::mem::take(&mut client_guard.patterns) {
+        pubsub_guard.punsubscribe(&pattern, client_id);
+    }
+}
+
+pub struct Publish {
+    pub channel: String,
+    pub message: String,
+    pub pubsub: AMPubSub,
+    pub config: AMConfig,
+    pub slaves: AMSlaves,
+}
+
+#[async_trait]
+impl Cmd for Publish {
+    async fn run(&mut self) -> RespType {
+        let limit = self.config.lock().await.pubsub_output_buffer_limit();
+        let receivers = self.pubsub.lock().await.publish(&self.channel, &self.message, &limit);
+        // replicas deliver it to their own subscribers
+        let update = resp_array_of_bulks!("PUBLISH", self.channel, self.message);
+        add_pending_update_resp(self.slaves.clone(), &update).await;
+        RespType::Integer(receivers as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::PUBLISH
+    }
+}
+
+impl Publish {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        pubsub: AMPubSub,
+        config: AMConfig,
+        slaves: AMSlaves,
+    ) -> Result<Self, CmdError> {
+        let channel = next_arg(&mut args_iter)?;
+        let message = next_arg(&mut args_iter)?;
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self {
+            channel,
+            message,
+            pubsub,
+            config,
+            slaves,
+        })
+    }
+}
+
+pub struct PubSubChannels {
+    pub pattern: Option<String>,
+    pub pubsub: AMPubSub,
+}
+
+#[async_trait]
+impl Cmd for PubSubChannels {
+    async fn run(&mut self) -> RespType {
+        let mut channels = self.pubsub.lock().await.active_channels(self.pattern.as_deref());
+        channels.sort();
+        RespType::Array(channels.into_iter().map(RespType::BulkString).collect())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::PUBSUB_CHANNELS
+    }
+}
+
+impl PubSubChannels {
+    pub fn new<'a>(
+        args_iter: &mut impl Iterator<Item = &'a RespType>,
+        pubsub: AMPubSub,
+    ) -> Result<Self, CmdError> {
+        let pattern = args_iter.next().map(unpack_bulk_string).transpose()?;
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self { pattern, pubsub })
+    }
+}
+
+pub struct PubSubNumSub {
+    pub channels: Vec<String>,
+    pub pubsub: AMPubSub,
+}
+
+#[async_trait]
+impl Cmd for PubSubNumSub {
+    async fn run(&mut self) -> RespType {
+        use RespType::{Array, BulkString, Integer};
+        let pubsub_guard = self.pubsub.lock().await;
+        let mut reply = Vec::with_capacity(self.channels.len() * 2);
+        for channel in self.channels.iter() {
+            reply.push(BulkString(channel.clone()));
+            reply.push(Integer(pubsub_guard.numsub(channel) as i64));
+        }
+        Array(reply)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::PUBSUB_NUMSUB
+    }
+}
+
+impl PubSubNumSub {
+    pub fn new<'a>(
+        args_iter: &mut impl Iterator<Item = &'a RespType>,
+        pubsub: AMPubSub,
+    ) -> Result<Self, CmdError> {
+        let channels = args_iter
+            .map(unpack_bulk_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { channels, pubsub })
+    }
+}
+
+pub struct PubSubNumPat {
+    pub pubsub: AMPubSub,
+}
+
+#[async_trait]
+impl Cmd for PubSubNumPat {
+    async fn run(&mut self) -> RespType {
+        RespType::Integer(self.pubsub.lock().await.numpat() as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::PUBSUB_NUMPAT
+    }
+}
+
+impl PubSubNumPat {
+    pub fn new<'a>(
+        args_iter: &mut impl Iterator<Item = &'a RespType>,
+        pubsub: AMPubSub,
+    ) -> Result<Self, CmdError> {
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self { pubsub })
+    }
+}
@@ -2,74 +2,169 @@ use async_trait::async_trait;
 
 use crate::resp::RespType;
 use crate::resp_array_of_bulks;
-use crate::utils::unpack_bulk_string;
+use crate::utils::{next_arg, unpack_bulk_string};
 use crate::cmd::{Cmd, CmdError, CmdType};
-use crate::redis::{AMRedisDB, AMSlaves, add_pending_update_resp};
-use crate::data_entry::{ValueType, DataEntry};
+use crate::redis::{AMRedisDB, get_alive_entry};
+use crate::data_entry::{ValueType, DataEntry, DataValue};
+use crate::keyspace_events::EventClass;
 
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy)]
+pub enum SetExpiry {
+    /// EX and PX, relative to the time the command runs.
+    After(Duration),
+    /// EXAT and PXAT.
+    At(SystemTime),
+    KeepTtl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetCondition {
+    Nx,
+    Xx,
+}
 
 pub struct Set {
     pub key: String,
     pub value: String,
-    pub px: Option<u64>,
+    pub expiry: Option<SetExpiry>,
+    pub condition: Option<SetCondition>,
+    pub get: bool,
+    /// Absolute expiry resolved when the command ran, this is what replicas get.
+    pub expires_at: Option<SystemTime>,
+    // false when NX or XX kept it from writing
+    pub written: bool,
     pub dict: AMRedisDB,
-    pub slaves: AMSlaves,
 }
 
 #[async_trait]
 impl Cmd for Set {
     async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
         let mut dict_guard = self.dict.lock().await;
+        let (exists, old_value, old_expiry) = match get_alive_entry(&mut dict_guard, &key) {
+            Some(DataEntry { value: DataValue::String(value), expired_at_unix_millis, .. }) => {
+                (true, Some(value.as_string()), *expired_at_unix_millis)
+            }
+            Some(_) if self.get => return RespType::SimpleError(CmdError::WrongType.to_string()),
+            Some(entry) => (true, None, entry.expired_at_unix_millis),
+            None => (false, None, None),
+        };
+        let reply = match self.get {
+            true => old_value.map(RespType::BulkString).unwrap_or(RespType::Null),
+            false => RespType::SimpleString("OK".to_string()),
+        };
+        let aborted = match self.condition {
+            Some(SetCondition::Nx) => exists,
+            Some(SetCondition::Xx) => !exists,
+            None => false,
+        };
+        if aborted {
+            return match self.get {
+                true => reply,
+                false => RespType::Null,
+            };
+        }
+        self.expires_at = match self.expiry {
+            Some(SetExpiry::After(duration)) => match SystemTime::now().checked_add(duration) {
+                Some(expires_at) => Some(expires_at),
+                None => return RespType::SimpleError(CmdError::InvalidExpireTime("set").to_string()),
+            },
+            Some(SetExpiry::At(expires_at)) => Some(expires_at),
+            Some(SetExpiry::KeepTtl) => old_expiry,
+            None => None,
+        };
         dict_guard.insert(
             ValueType::new(self.key.clone()),
-            DataEntry::new(self.value.clone(), self.px),
+            DataEntry::new(self.value.clone(), self.expires_at),
         );
-        drop(dict_guard);
-        add_pending_update_resp(self.slaves.clone(), &self.as_resp()).await;
-        RespType::SimpleString("OK".to_string())
+        dict_guard.notify(EventClass::String, "set", &key);
+        if matches!(self.expiry, Some(SetExpiry::After(_) | SetExpiry::At(_))) {
+            dict_guard.notify(EventClass::Generic, "expire", &key);
+        }
+        self.written = true;
+        reply
     }
 
     fn cmd_type(&self) -> CmdType {
         CmdType::SET
     }
+
+    fn propagated(&self, _resp: RespType, _reply: &RespType) -> Vec<RespType> {
+        match self.written {
+            true => vec![self.as_resp()],
+            false => Vec::new(),
+        }
+    }
+}
+
+// EX/PX/EXAT/PXAT only accept positive times that don't overflow once converted to millis
+fn next_expire_millis<'a>(
+    args_iter: &mut impl Iterator<Item = &'a RespType>,
+    unit_millis: u64,
+) -> Result<Duration, CmdError> {
+    let time = next_arg(args_iter)?
+        .parse::<i64>()
+        .map_err(|_| CmdError::NotInteger)?;
+    u64::try_from(time)
+        .ok()
+        .filter(|time| *time > 0)
+        .and_then(|time| time.checked_mul(unit_millis))
+        .filter(|millis| *millis <= i64::MAX as u64)
+        .map(Duration::from_millis)
+        .ok_or_else(|| CmdError::InvalidExpireTime("set"))
 }
 
 impl Set {
     pub fn new<'a>(
         mut args_iter: &mut impl Iterator<Item = &'a RespType>,
         dict: AMRedisDB,
-        slaves: AMSlaves,
     ) -> Result<Self, CmdError> {
-        let key =
-            unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
-        let value =
-            unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
-        let px = match args_iter.next() {
-            Some(_) => {
-                let px_value = unpack_bulk_string(
-                    args_iter.next().ok_or_else(|| CmdError::MissingArgs)?,
-                )?;
-                match px_value.parse::<u64>() {
-                    Ok(px_value) => Some(px_value),
-                    Err(_) => return Err(CmdError::InvalidArg),
+        let key = next_arg(&mut args_iter)?;
+        let value = next_arg(&mut args_iter)?;
+        let mut expiry = None;
+        let mut condition = None;
+        let mut get = false;
+        while let Some(option) = args_iter.next() {
+            let option = unpack_bulk_string(option)?.to_lowercase();
+            match option.as_str() {
+                "nx" | "xx" if condition.is_some() => return Err(CmdError::SyntaxError),
+                "nx" => condition = Some(SetCondition::Nx),
+                "xx" => condition = Some(SetCondition::Xx),
+                "get" => get = true,
+                "keepttl" | "ex" | "px" | "exat" | "pxat" if expiry.is_some() => {
+                    return Err(CmdError::SyntaxError)
+                }
+                "keepttl" => expiry = Some(SetExpiry::KeepTtl),
+                "ex" => expiry = Some(SetExpiry::After(next_expire_millis(&mut args_iter, 1000)?)),
+                "px" => expiry = Some(SetExpiry::After(next_expire_millis(&mut args_iter, 1)?)),
+                "exat" | "pxat" => {
+                    let unit_millis = if option == "exat" { 1000 } else { 1 };
+                    let since_epoch = next_expire_millis(&mut args_iter, unit_millis)?;
+                    expiry = Some(SetExpiry::At(UNIX_EPOCH + since_epoch));
                 }
+                _ => return Err(CmdError::SyntaxError),
             }
-            None => None,
-        };
+        }
         Ok(Set {
-            key: key.to_string(),
-            value: value.to_string(),
-            px,
+            key,
+            value,
+            expiry,
+            condition,
+            get,
+            expires_at: None,
+            written: false,
             dict,
-            slaves,
         })
     }
 
-    // TODO: can be part of the Cmd trait
     pub fn as_resp(&self) -> RespType {
-        match self.px {
-            Some(millis) => resp_array_of_bulks!("SET", self.key, self.value, "px", millis),
+        let expires_at_millis = self
+            .expires_at
+            .map(|expires_at| expires_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis());
+        match expires_at_millis {
+            Some(millis) => resp_array_of_bulks!("SET", self.key, self.value, "PXAT", millis),
             None => resp_array_of_bulks!("SET", self.key, self.value),
         }
     }
@@ -0,0 +1,337 @@
+use async_trait::async_trait;
+
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::data_entry::{DataEntry, DataValue, ValueType};
+use crate::keyspace_events::EventClass;
+use crate::redis::{get_alive_entry, AMRedisDB, RedisDB};
+use crate::resp::RespType;
+use crate::set_entry::SetEntry;
+use crate::utils::{next_arg, unpack_bulk_string};
+
+use std::collections::HashSet;
+
+/// Returns the set stored at `key`, `Ok(None)` if there is no such key.
+fn get_set<'a>(dict: &'a mut RedisDB, key: &ValueType) -> Result<Option<&'a mut SetEntry>, CmdError> {
+    match get_alive_entry(dict, key) {
+        Some(DataEntry { value: DataValue::Set(set), .. }) => Ok(Some(set)),
+        Some(_) => Err(CmdError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn collect_args<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<Vec<String>, CmdError> {
+    let args = args_iter
+        .map(unpack_bulk_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    match args.is_empty() {
+        true => Err(CmdError::MissingArgs),
+        false => Ok(args),
+    }
+}
+
+fn members_as_resp(members: Vec<String>) -> RespType {
+    RespType::Array(members.into_iter().map(RespType::BulkString).collect())
+}
+
+pub struct SAdd {
+    pub key: String,
+    pub members: Vec<String>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for SAdd {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        match get_set(&mut dict_guard, &key) {
+            Ok(Some(_)) => (),
+            Ok(None) => {
+                dict_guard.insert(
+                    ValueType::new(self.key.clone()),
+                    DataEntry::with_value(DataValue::Set(SetEntry::new())),
+                );
+            }
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let set = get_set(&mut dict_guard, &key).unwrap().unwrap();
+        let added = self
+            .members
+            .iter()
+            .filter(|member| set.add(member.to_string()))
+            .count();
+        if added > 0 {
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::Set, "sadd", &key);
+        }
+        RespType::Integer(added as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::SADD
+    }
+}
+
+impl SAdd {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let members = collect_args(&mut args_iter)?;
+        Ok(Self { key, members, dict })
+    }
+}
+
+pub struct SRem {
+    pub key: String,
+    pub members: Vec<String>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for SRem {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let (removed, is_empty) = match get_set(&mut dict_guard, &key) {
+            Ok(Some(set)) => {
+                let removed = self.members.iter().filter(|member| set.remove(member)).count();
+                (removed, set.is_empty())
+            }
+            Ok(None) => (0, false),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        if removed > 0 {
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::Set, "srem", &key);
+        }
+        if is_empty {
+            dict_guard.remove(&key);
+            dict_guard.notify(EventClass::Generic, "del", &key);
+        }
+        RespType::Integer(removed as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::SREM
+    }
+}
+
+impl SRem {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let members = collect_args(&mut args_iter)?;
+        Ok(Self { key, members, dict })
+    }
+}
+
+pub struct SMembers {
+    pub key: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for SMembers {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_set(&mut *self.dict.lock().await, &key) {
+            Ok(set) => members_as_resp(set.map(|set| set.members()).unwrap_or_default()),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::SMEMBERS
+    }
+}
+
+impl SMembers {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        Ok(Self { key, dict })
+    }
+}
+
+pub struct SIsMember {
+    pub key: String,
+    pub member: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for SIsMember {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_set(&mut *self.dict.lock().await, &key) {
+            Ok(set) => RespType::Integer(set.is_some_and(|set| set.contains(&self.member)) as i64),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::SISMEMBER
+    }
+}
+
+impl SIsMember {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let member = next_arg(&mut args_iter)?;
+        Ok(Self { key, member, dict })
+    }
+}
+
+pub struct SCard {
+    pub key: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for SCard {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_set(&mut *self.dict.lock().await, &key) {
+            Ok(set) => RespType::Integer(set.map_or(0, |set| set.len()) as i64),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::SCARD
+    }
+}
+
+impl SCard {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        Ok(Self { key, dict })
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+impl SetOp {
+    fn store_event(&self) -> &'static str {
+        match self {
+            Self::Inter => "sinterstore",
+            Self::Union => "sunionstore",
+            Self::Diff => "sdiffstore",
+        }
+    }
+}
+
+/// SINTER, SUNION, SDIFF and their *STORE variants when `destination` is given
+pub struct SetAlgebra {
+    pub op: SetOp,
+    pub keys: Vec<String>,
+    pub destination: Option<String>,
+    pub dict: AMRedisDB,
+}
+
+impl SetAlgebra {
+    fn compute(&self, dict: &mut RedisDB) -> Result<HashSet<String>, CmdError> {
+        // a missing key behaves like an empty set
+        let mut sets = Vec::with_capacity(self.keys.len());
+        for key in self.keys.iter() {
+            let key = ValueType::new(key.clone());
+            sets.push(get_set(dict, &key)?.map(|set| set.members()).unwrap_or_default());
+        }
+        let mut sets = sets.into_iter();
+        let mut result: HashSet<String> = sets.next().unwrap_or_default().into_iter().collect();
+        for set in sets {
+            match self.op {
+                SetOp::Inter => {
+                    let set: HashSet<String> = set.into_iter().collect();
+                    result.retain(|member| set.contains(member));
+                }
+                SetOp::Union => result.extend(set),
+                SetOp::Diff => {
+                    for member in set.iter() {
+                        result.remove(member);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Cmd for SetAlgebra {
+    async fn run(&mut self) -> RespType {
+        let mut dict_guard = self.dict.lock().await;
+        let result = match self.compute(&mut dict_guard) {
+            Ok(result) => result,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        match &self.destination {
+            Some(destination) => {
+                let len = result.len();
+                let destination = ValueType::new(destination.clone());
+                if result.is_empty() {
+                    if dict_guard.remove(&destination).is_some() {
+                        dict_guard.notify(EventClass::Generic, "del", &destination);
+                    }
+                } else {
+                    dict_guard.insert(
+                        destination.clone(),
+                        DataEntry::with_value(DataValue::Set(result.into_iter().collect())),
+                    );
+                    dict_guard.notify(EventClass::Set, self.op.store_event(), &destination);
+                }
+                RespType::Integer(len as i64)
+            }
+            None => members_as_resp(result.into_iter().collect()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match (self.op, self.destination.is_some()) {
+            (SetOp::Inter, false) => CmdType::SINTER,
+            (SetOp::Union, false) => CmdType::SUNION,
+            (SetOp::Diff, false) => CmdType::SDIFF,
+            (SetOp::Inter, true) => CmdType::SINTERSTORE,
+            (SetOp::Union, true) => CmdType::SUNIONSTORE,
+            (SetOp::Diff, true) => CmdType::SDIFFSTORE,
+        }
+    }
+}
+
+impl SetAlgebra {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+        op: SetOp,
+        store: bool,
+    ) -> Result<Self, CmdError> {
+        let destination = match store {
+            true => Some(next_arg(&mut args_iter)?),
+            false => None,
+        };
+        let keys = collect_args(&mut args_iter)?;
+        Ok(Self {
+            op,
+            keys,
+            destination,
+            dict,
+        })
+    }
+}
@@ -0,0 +1,312 @@
+use async_trait::async_trait;
+
+use crate::blocked_clients::serve_all_blocked_clients;
+use crate::client::{AMClient, Transaction, WatchFlag};
+use crate::cmd::cmd_builder::CmdBuilder;
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::data_entry::ValueType;
+use crate::pubsub::AMPubSub;
+use crate::redis::{get_alive_entry, AMConfig, AMSlaves, Databases, TxLock};
+use crate::resp::RespType;
+use crate::resp_array_of_bulks;
+use crate::slave_meta::{SlaveMeta, WriteStream};
+use crate::utils::unpack_bulk_string;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Forgets every key the client watches, used by EXEC, DISCARD, UNWATCH and on disconnect.
+pub async fn unwatch_all(client: &AMClient, databases: &Databases) {
+    let mut client_guard = client.lock().await;
+    let watched = std::mem::take(&mut client_guard.watched);
+    let flag = std::mem::take(&mut client_guard.watch_dirty);
+    drop(client_guard);
+    for (db, key) in watched {
+        databases[db].dict.lock().await.unwatch(&key, &flag);
+    }
+}
+
+pub struct Multi {
+    pub client: AMClient,
+}
+
+#[async_trait]
+impl Cmd for Multi {
+    async fn run(&mut self) -> RespType {
+        let mut client_guard = self.client.lock().await;
+        if client_guard.tx.is_some() {
+            return RespType::SimpleError(CmdError::NestedMulti.to_string());
+        }
+        client_guard.tx = Some(Transaction::default());
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::MULTI
+    }
+}
+
+impl Multi {
+    pub fn new(client: AMClient) -> Result<Self, CmdError> {
+        Ok(Self { client })
+    }
+}
+
+/// What a command sent after MULTI gets instead of running, the command itself was already
+/// parsed so a malformed one makes EXEC fail.
+pub struct Queue {
+    pub resp: RespType,
+    pub parsed: Result<(), CmdError>,
+    pub client: AMClient,
+}
+
+#[async_trait]
+impl Cmd for Queue {
+    async fn run(&mut self) -> RespType {
+        let mut client_guard = self.client.lock().await;
+        let tx = client_guard.tx.get_or_insert_with(Transaction::default);
+        match &self.parsed {
+            Ok(()) => {
+                tx.queued.push(self.resp.clone());
+                RespType::SimpleString("QUEUED".to_string())
+            }
+            Err(err) => {
+                tx.aborted = true;
+                RespType::SimpleError(err.to_string())
+            }
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::QUEUED
+    }
+}
+
+pub struct Exec {
+    pub databases: Databases,
+    pub client: AMClient,
+    pub config: AMConfig,
+    pub slaves: AMSlaves,
+    pub pubsub: AMPubSub,
+    pub socket_addr: Option<SocketAddr>,
+    pub wr: Option<WriteStream>,
+    pub tx_lock: TxLock,
+}
+
+impl Exec {
+    // replicas with nothing pending, the queued commands propagate into them so their writes
+    // can be wrapped in MULTI/EXEC afterwards
+    async fn shadow_slaves(&self) -> AMSlaves {
+        let shadow = self
+            .slaves
+            .lock()
+            .await
+            .iter()
+            .map(|(socket_addr, slave_meta)| {
+                let slave_meta = SlaveMeta {
+                    pending_updates: Vec::new(),
+                    ..slave_meta.clone()
+                };
+                (*socket_addr, slave_meta)
+            })
+            .collect::<HashMap<_, _>>();
+        Arc::new(Mutex::new(shadow))
+    }
+
+    async fn propagate(&self, shadow: AMSlaves) {
+        let mut slaves_guard = self.slaves.lock().await;
+        for (socket_addr, shadow_meta) in shadow.lock().await.iter() {
+            if shadow_meta.pending_updates.is_empty() {
+                continue;
+            }
+            if let Some(slave_meta) = slaves_guard.get_mut(socket_addr) {
+                slave_meta.append_update(&resp_array_of_bulks!("MULTI").serialize());
+                slave_meta.append_update(&shadow_meta.pending_updates);
+                slave_meta.append_update(&resp_array_of_bulks!("EXEC").serialize());
+                slave_meta.selected_db = shadow_meta.selected_db;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Cmd for Exec {
+    async fn run(&mut self) -> RespType {
+        let tx = match self.client.lock().await.tx.take() {
+            Some(tx) => tx,
+            None => return RespType::SimpleError(CmdError::ExecWithoutMulti.to_string()),
+        };
+        let _tx_guard = self.tx_lock.write().await;
+        // checked while holding the lock, so no one can touch the watched keys from now on
+        let dirty = self.client.lock().await.watch_dirty.load(Ordering::SeqCst);
+        unwatch_all(&self.client, &self.databases).await;
+        if tx.aborted {
+            return RespType::SimpleError(CmdError::ExecAbort.to_string());
+        }
+        if dirty {
+            return RespType::WildCard("*-1\r\n".into());
+        }
+
+        let shadow = self.shadow_slaves().await;
+        self.client.lock().await.in_exec = true;
+        let mut replies = Vec::with_capacity(tx.queued.len());
+        for resp in tx.queued {
+            let mut cmd = CmdBuilder::from_resp(
+                resp,
+                self.databases.clone(),
+                self.client.clone(),
+                self.config.clone(),
+                shadow.clone(),
+                self.pubsub.clone(),
+                self.socket_addr,
+                self.wr.clone(),
+                self.tx_lock.clone(),
+            )
+            .await;
+            replies.push(cmd.run().await);
+        }
+        self.client.lock().await.in_exec = false;
+        // the pops of the clients served go into the transaction too
+        serve_all_blocked_clients(&self.databases, &mut *shadow.lock().await).await;
+        self.propagate(shadow).await;
+        RespType::Array(replies)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::EXEC
+    }
+}
+
+impl Exec {
+    pub fn new(
+        databases: Databases,
+        client: AMClient,
+        config: AMConfig,
+        slaves: AMSlaves,
+        pubsub: AMPubSub,
+        socket_addr: Option<SocketAddr>,
+        wr: Option<WriteStream>,
+        tx_lock: TxLock,
+    ) -> Result<Self, CmdError> {
+        Ok(Self {
+            databases,
+            client,
+            config,
+            slaves,
+            pubsub,
+            socket_addr,
+            wr,
+            tx_lock,
+        })
+    }
+}
+
+pub struct Discard {
+    pub databases: Databases,
+    pub client: AMClient,
+}
+
+#[async_trait]
+impl Cmd for Discard {
+    async fn run(&mut self) -> RespType {
+        if self.client.lock().await.tx.take().is_none() {
+            return RespType::SimpleError(CmdError::DiscardWithoutMulti.to_string());
+        }
+        unwatch_all(&self.client, &self.databases).await;
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::DISCARD
+    }
+}
+
+impl Discard {
+    pub fn new(databases: Databases, client: AMClient) -> Result<Self, CmdError> {
+        Ok(Self { databases, client })
+    }
+}
+
+pub struct Watch {
+    pub keys: Vec<String>,
+    pub db: usize,
+    pub databases: Databases,
+    pub client: AMClient,
+}
+
+#[async_trait]
+impl Cmd for Watch {
+    async fn run(&mut self) -> RespType {
+        let client_guard = self.client.lock().await;
+        if client_guard.tx.is_some() {
+            return RespType::SimpleError(CmdError::WatchInMulti.to_string());
+        }
+        let flag: WatchFlag = client_guard.watch_dirty.clone();
+        drop(client_guard);
+        let db = &self.databases[self.db];
+        let mut dict_guard = db.dict.lock().await;
+        let mut watched = Vec::with_capacity(self.keys.len());
+        for key in self.keys.iter() {
+            let key = ValueType::new(key.clone());
+            // a key whose TTL already passed goes away now, so its expiry doesn't count as a change
+            get_alive_entry(&mut dict_guard, &key);
+            dict_guard.watch(key.clone(), &flag);
+            watched.push((self.db, key));
+        }
+        drop(dict_guard);
+        self.client.lock().await.watched.extend(watched);
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::WATCH
+    }
+}
+
+impl Watch {
+    pub fn new<'a>(
+        args_iter: &mut impl Iterator<Item = &'a RespType>,
+        db: usize,
+        databases: Databases,
+        client: AMClient,
+    ) -> Result<Self, CmdError> {
+        let keys = args_iter
+            .map(unpack_bulk_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err(CmdError::MissingArgs);
+        }
+        Ok(Self {
+            keys,
+            db,
+            databases,
+            client,
+        })
+    }
+}
+
+pub struct Unwatch {
+    pub databases: Databases,
+    pub client: AMClient,
+}
+
+#[async_trait]
+impl Cmd for Unwatch {
+    async fn run(&mut self) -> RespType {
+        unwatch_all(&self.client, &self.databases).await;
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::UNWATCH
+    }
+}
+
+impl Unwatch {
+    pub fn new(databases: Databases, client: AMClient) -> Result<Self, CmdError> {
+        Ok(Self { databases, client })
+    }
+}
@@ -1,11 +1,12 @@
 use async_trait::async_trait;
 
-use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::cmd::{xtrim::{parse_trim, trim_args, trimmed_as}, Cmd, CmdError, CmdType};
 use crate::resp::RespType;
-use crate::redis::{AMStreams, AMStreamSenders};
-use crate::data_entry::ValueType;
-use crate::stream_entry::StreamEntry;
-use crate::utils::unpack_bulk_string;
+use crate::redis::{AMRedisDB, AMStreamSenders, get_alive_stream};
+use crate::data_entry::{DataEntry, DataValue, ValueType};
+use crate::keyspace_events::EventClass;
+use crate::stream_entry::{StreamEntry, StreamID, StreamTrim};
+use crate::utils::{next_arg, unpack_bulk_string};
 
 use std::collections::BTreeMap;
 
@@ -13,7 +14,13 @@ pub struct XAdd {
     pub stream_key: String,
     pub stream_id: String,
     pub stream_data: BTreeMap<String, String>,
-    pub streams: AMStreams,
+    // a missing stream isn't created, XADD replies with nil instead
+    pub nomkstream: bool,
+    // trims the stream once the entry is added
+    pub trim: Option<StreamTrim>,
+    // set by `run` once the stream is trimmed, see `trimmed_as`
+    pub propagated_trim: Option<StreamTrim>,
+    pub dict: AMRedisDB,
     pub stream_senders: AMStreamSenders,
 }
 
@@ -22,10 +29,26 @@ impl Cmd for XAdd {
     async fn run(&mut self) -> RespType {
         use RespType::{BulkString, SimpleError, Array};
         let key = ValueType::new(self.stream_key.clone());
-        let mut stream_guard = self.streams.lock().await;
-        let stream_entry = stream_guard.entry(key).or_insert(StreamEntry::new());
-        match stream_entry.append_stream(self.stream_id.clone(), self.stream_data.clone()) {
+        let mut dict_guard = self.dict.lock().await;
+        let created = match get_alive_stream(&mut dict_guard, &key) {
+            Ok(Some(_)) => false,
+            Ok(None) if self.nomkstream => return RespType::Null,
+            Ok(None) => {
+                dict_guard.insert(key.clone(), DataEntry::with_value(DataValue::Stream(StreamEntry::new())));
+                true
+            }
+            Err(err) => return SimpleError(err.to_string()),
+        };
+        let stream_entry = get_alive_stream(&mut dict_guard, &key).unwrap().unwrap();
+        match stream_entry.append_stream(&self.stream_id, self.stream_data.clone()) {
             Ok(stored_id) => {
+                let trimmed = self.trim.as_ref().map_or(0, |trim| stream_entry.trim(trim));
+                self.propagated_trim = self.trim.as_ref().map(|trim| trimmed_as(trim, stream_entry));
+                dict_guard.touch(&key);
+                dict_guard.notify(EventClass::Stream, "xadd", &key);
+                if trimmed > 0 {
+                    dict_guard.notify(EventClass::Stream, "xtrim", &key);
+                }
                 if let Some(sender) = self.stream_senders.lock().await.get(&self.stream_key) {
                     let mut stream_id_array = Vec::new();
                     for (key, value) in self.stream_data.iter() {
@@ -37,27 +60,67 @@ impl Cmd for XAdd {
                 }
                 BulkString(stored_id)
             }
-            Err(reason) => SimpleError(reason),
+            Err(reason) => {
+                // a stream is only created along with its first entry
+                if created {
+                    dict_guard.remove(&key);
+                }
+                SimpleError(reason)
+            }
         }
     }
 
     fn cmd_type(&self) -> CmdType {
         CmdType::XADD
     }
+
+    // replicas get the id the entry was stored with instead of `*`
+    fn propagated(&self, _resp: RespType, reply: &RespType) -> Vec<RespType> {
+        let stored_id = match reply {
+            RespType::BulkString(stored_id) => stored_id.clone(),
+            _ => return Vec::new(),
+        };
+        let mut args = vec!["XADD".to_string(), self.stream_key.clone()];
+        if self.nomkstream {
+            args.push("NOMKSTREAM".to_string());
+        }
+        args.extend(self.propagated_trim.iter().flat_map(trim_args));
+        args.push(stored_id);
+        for (key, value) in self.stream_data.iter() {
+            args.extend([key.clone(), value.clone()]);
+        }
+        vec![RespType::Array(args.into_iter().map(RespType::BulkString).collect())]
+    }
 }
 
 impl XAdd {
     pub fn new<'a>(
         mut args_iter: &mut impl Iterator<Item = &'a RespType>,
-        streams: AMStreams,
+        dict: AMRedisDB,
         stream_senders: AMStreamSenders,
     ) -> Result<Self, CmdError> {
         let stream_key =
             unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
-        let stream_id =
-            unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
+        let mut args_iter = args_iter.peekable();
+        let (mut nomkstream, mut trim) = (false, None);
+        // the options go before the id
+        let stream_id = loop {
+            let arg = next_arg(&mut args_iter)?;
+            match arg.to_lowercase().as_str() {
+                "nomkstream" => nomkstream = true,
+                strategy @ ("maxlen" | "minid") => trim = Some(parse_trim(strategy, &mut args_iter)?),
+                _ => break arg,
+            }
+        };
+        // checked here so a bad id is refused before anything runs, the id itself is only
+        // generated once the entry is appended
+        StreamID::to_xadd(&stream_id)?;
+        let fields = args_iter.collect::<Vec<_>>();
+        if fields.is_empty() || fields.len() % 2 != 0 {
+            return Err(CmdError::MissingArgs);
+        }
         let mut stream_data = BTreeMap::new();
-        for key_value in args_iter.collect::<Vec<_>>().as_slice().chunks_exact(2) {
+        for key_value in fields.as_slice().chunks_exact(2) {
             let key = unpack_bulk_string(&key_value[0])?;
             let value = unpack_bulk_string(&key_value[1])?;
             stream_data.insert(key, value);
@@ -66,7 +129,10 @@ impl XAdd {
             stream_key,
             stream_id,
             stream_data,
-            streams,
+            nomkstream,
+            trim,
+            propagated_trim: None,
+            dict,
             stream_senders,
         })
     }
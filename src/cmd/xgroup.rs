@@ -0,0 +1,308 @@
+use async_trait::async_trait;
+
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::consumer_group::ConsumerGroup;
+use crate::data_entry::{DataEntry, DataValue, ValueType};
+use crate::keyspace_events::EventClass;
+use crate::redis::{get_alive_stream, AMRedisDB};
+use crate::resp::RespType;
+use crate::resp_array_of_bulks;
+use crate::stream_entry::{StreamEntry, StreamID};
+use crate::utils::{next_arg, next_int_arg, unpack_bulk_string};
+
+// `$` stands for the last id of the stream, which is only known once it runs
+fn next_group_id<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<Option<StreamID>, CmdError> {
+    StreamID::to_xread(&next_arg(args_iter)?)
+}
+
+/// Where a group created or moved by XGROUP starts reading from, and how many entries it counts
+/// as read. Unless ENTRIESREAD tells, that is only known at the start and the end of the stream.
+fn group_position(stream: &StreamEntry, id: &Option<StreamID>, entries_read: Option<i64>) -> (StreamID, Option<u64>) {
+    let id = id.clone().unwrap_or_else(|| stream.last_id().clone());
+    let entries_read = match entries_read {
+        // -1 explicitly leaves it unknown
+        Some(entries_read) => (entries_read >= 0).then_some(entries_read as u64),
+        None if id >= *stream.last_id() => Some(stream.entries_added()),
+        None if id == StreamID::MIN => Some(0),
+        None => None,
+    };
+    (id, entries_read)
+}
+
+/// XGROUP SETID to where `group` is now, replicas get it along with ENTRIESREAD so they don't
+/// have to work it out on their own.
+pub fn setid_as_resp(key: &str, name: &str, group: &ConsumerGroup) -> RespType {
+    let entries_read = group.entries_read.map_or(-1, |entries_read| entries_read as i64);
+    resp_array_of_bulks!("XGROUP", "SETID", key, name, group.last_delivered_id, "ENTRIESREAD", entries_read)
+}
+
+// the options after the id, ENTRIESREAD for both and MKSTREAM for CREATE alone
+fn group_options<'a>(
+    args_iter: &mut impl Iterator<Item = &'a RespType>,
+    allow_mkstream: bool,
+) -> Result<(bool, Option<i64>), CmdError> {
+    let (mut mkstream, mut entries_read) = (false, None);
+    while let Some(option) = args_iter.next() {
+        match unpack_bulk_string(option)?.to_lowercase().as_str() {
+            "mkstream" if allow_mkstream => mkstream = true,
+            "entriesread" => entries_read = Some(next_int_arg(args_iter)?),
+            _ => return Err(CmdError::SyntaxError),
+        }
+    }
+    Ok((mkstream, entries_read))
+}
+
+pub struct XGroupCreate {
+    pub key: String,
+    pub group: String,
+    // `None` for `$`
+    pub id: Option<StreamID>,
+    // creates an empty stream if there is none
+    pub mkstream: bool,
+    pub entries_read: Option<i64>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XGroupCreate {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let result = match get_alive_stream(&mut dict_guard, &key) {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) if self.mkstream => {
+                dict_guard.insert(key.clone(), DataEntry::with_value(DataValue::Stream(StreamEntry::new())));
+                Ok(())
+            }
+            Ok(None) => Err(CmdError::XGroupKeyMissing),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            return RespType::SimpleError(err.to_string());
+        }
+        let stream = get_alive_stream(&mut dict_guard, &key).unwrap().unwrap();
+        if stream.groups.contains_key(&self.group) {
+            return RespType::SimpleError(CmdError::BusyGroup.to_string());
+        }
+        let (id, entries_read) = group_position(stream, &self.id, self.entries_read);
+        // what replicas get, `$` resolved
+        self.id = Some(id.clone());
+        self.entries_read = Some(entries_read.map_or(-1, |entries_read| entries_read as i64));
+        stream.groups.insert(self.group.clone(), ConsumerGroup::new(id, entries_read));
+        dict_guard.touch(&key);
+        dict_guard.notify(EventClass::Stream, "xgroup-create", &key);
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XGROUP_CREATE
+    }
+
+    fn propagated(&self, resp: RespType, _reply: &RespType) -> Vec<RespType> {
+        let (id, entries_read) = match (&self.id, self.entries_read) {
+            (Some(id), Some(entries_read)) => (id, entries_read),
+            _ => return vec![resp],
+        };
+        let create = match self.mkstream {
+            true => resp_array_of_bulks!("XGROUP", "CREATE", self.key, self.group, id, "MKSTREAM", "ENTRIESREAD", entries_read),
+            false => resp_array_of_bulks!("XGROUP", "CREATE", self.key, self.group, id, "ENTRIESREAD", entries_read),
+        };
+        vec![create]
+    }
+}
+
+impl XGroupCreate {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let group = next_arg(&mut args_iter)?;
+        let id = next_group_id(&mut args_iter)?;
+        let (mkstream, entries_read) = group_options(&mut args_iter, true)?;
+        Ok(Self {
+            key,
+            group,
+            id,
+            mkstream,
+            entries_read,
+            dict,
+        })
+    }
+}
+
+pub struct XGroupDestroy {
+    pub key: String,
+    pub group: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XGroupDestroy {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let stream = match get_alive_stream(&mut dict_guard, &key) {
+            Ok(Some(stream)) => stream,
+            Ok(None) => return RespType::SimpleError(CmdError::XGroupKeyMissing.to_string()),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let destroyed = stream.groups.remove(&self.group).is_some();
+        if destroyed {
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::Stream, "xgroup-destroy", &key);
+        }
+        RespType::Integer(destroyed as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XGROUP_DESTROY
+    }
+}
+
+impl XGroupDestroy {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let group = next_arg(&mut args_iter)?;
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self { key, group, dict })
+    }
+}
+
+/// XGROUP CREATECONSUMER and XGROUP DELCONSUMER
+pub struct XGroupConsumer {
+    pub key: String,
+    pub group: String,
+    pub consumer: String,
+    pub create: bool,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XGroupConsumer {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let group = match get_alive_stream(&mut dict_guard, &key) {
+            Ok(Some(stream)) => stream.groups.get_mut(&self.group),
+            Ok(None) => return RespType::SimpleError(CmdError::XGroupKeyMissing.to_string()),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let group = match group {
+            Some(group) => group,
+            None => return RespType::SimpleError(CmdError::NoGroup(self.key.clone(), self.group.clone()).to_string()),
+        };
+        let (reply, event) = match self.create {
+            true => {
+                let created = group.create_consumer(&self.consumer);
+                (RespType::Integer(created as i64), created.then_some("xgroup-createconsumer"))
+            }
+            false => match group.delete_consumer(&self.consumer) {
+                Some(pending) => (RespType::Integer(pending as i64), Some("xgroup-delconsumer")),
+                None => (RespType::Integer(0), None),
+            },
+        };
+        if let Some(event) = event {
+            dict_guard.notify(EventClass::Stream, event, &key);
+        }
+        reply
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match self.create {
+            true => CmdType::XGROUP_CREATECONSUMER,
+            false => CmdType::XGROUP_DELCONSUMER,
+        }
+    }
+}
+
+impl XGroupConsumer {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+        create: bool,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let group = next_arg(&mut args_iter)?;
+        let consumer = next_arg(&mut args_iter)?;
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self {
+            key,
+            group,
+            consumer,
+            create,
+            dict,
+        })
+    }
+}
+
+pub struct XGroupSetId {
+    pub key: String,
+    pub group: String,
+    // `None` for `$`
+    pub id: Option<StreamID>,
+    pub entries_read: Option<i64>,
+    // what replicas get, `$` resolved
+    pub update: Option<RespType>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XGroupSetId {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let stream = match get_alive_stream(&mut dict_guard, &key) {
+            Ok(Some(stream)) => stream,
+            Ok(None) => return RespType::SimpleError(CmdError::XGroupKeyMissing.to_string()),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let (id, entries_read) = group_position(stream, &self.id, self.entries_read);
+        match stream.groups.get_mut(&self.group) {
+            Some(group) => {
+                group.last_delivered_id = id;
+                group.entries_read = entries_read;
+                self.update = Some(setid_as_resp(&self.key, &self.group, group));
+            }
+            None => return RespType::SimpleError(CmdError::NoGroup(self.key.clone(), self.group.clone()).to_string()),
+        }
+        dict_guard.touch(&key);
+        dict_guard.notify(EventClass::Stream, "xgroup-setid", &key);
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XGROUP_SETID
+    }
+
+    fn propagated(&self, _resp: RespType, _reply: &RespType) -> Vec<RespType> {
+        self.update.iter().cloned().collect()
+    }
+}
+
+impl XGroupSetId {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let group = next_arg(&mut args_iter)?;
+        let id = next_group_id(&mut args_iter)?;
+        let (_, entries_read) = group_options(&mut args_iter, false)?;
+        Ok(Self {
+            key,
+            group,
+            id,
+            entries_read,
+            update: None,
+            dict,
+        })
+    }
+}
@@ -0,0 +1,282 @@
+use async_trait::async_trait;
+
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::consumer_group::ConsumerGroup;
+use crate::data_entry::ValueType;
+use crate::redis::{get_alive_stream, AMRedisDB, RedisDB};
+use crate::resp::RespType;
+use crate::stream_entry::{StreamEntry, StreamID};
+use crate::clock::gen_millis;
+use crate::utils::{next_arg, next_int_arg, unpack_bulk_string};
+
+// XINFO replies are flat lists of field names followed by their values
+fn fields(fields: Vec<(&str, RespType)>) -> RespType {
+    let mut reply = Vec::with_capacity(fields.len() * 2);
+    for (name, value) in fields {
+        reply.push(RespType::BulkString(name.to_string()));
+        reply.push(value);
+    }
+    RespType::Array(reply)
+}
+
+fn id(id: &StreamID) -> RespType {
+    RespType::BulkString(id.to_string())
+}
+
+fn optional_int(value: Option<u64>) -> RespType {
+    value.map_or(RespType::Null, |value| RespType::Integer(value as i64))
+}
+
+fn lookup_stream<'a>(dict: &'a mut RedisDB, key: &str) -> Result<&'a mut StreamEntry, CmdError> {
+    get_alive_stream(dict, &ValueType::new(key.to_string()))?.ok_or(CmdError::NoSuchKey)
+}
+
+pub struct XInfoStream {
+    pub key: String,
+    // FULL with its COUNT, 0 lists everything
+    pub full: Option<usize>,
+    pub dict: AMRedisDB,
+}
+
+impl XInfoStream {
+    fn summary(stream: &StreamEntry) -> RespType {
+        let entry = |entry_id: Option<StreamID>| {
+            entry_id
+                .and_then(|entry_id| stream.entry_as_resp(&entry_id))
+                .unwrap_or(RespType::Null)
+        };
+        fields(vec![
+            ("length", RespType::Integer(stream.len() as i64)),
+            ("last-generated-id", id(stream.last_id())),
+            ("max-deleted-entry-id", id(stream.max_deleted_entry_id())),
+            ("entries-added", RespType::Integer(stream.entries_added() as i64)),
+            ("recorded-first-entry-id", id(&stream.first_entry_id().unwrap_or(StreamID::MIN))),
+            ("groups", RespType::Integer(stream.groups.len() as i64)),
+            ("first-entry", entry(stream.first_entry_id())),
+            ("last-entry", entry(stream.last_entry_id())),
+        ])
+    }
+
+    fn full(stream: &StreamEntry, count: usize) -> RespType {
+        use RespType::{Array, BulkString, Integer};
+        let limit = (count > 0).then_some(count);
+        let groups = stream
+            .groups
+            .iter()
+            .map(|(name, group)| {
+                let pending = group
+                    .pending
+                    .iter()
+                    .take(limit.unwrap_or(usize::MAX))
+                    .map(|(entry_id, entry)| {
+                        Array(vec![
+                            id(entry_id),
+                            BulkString(entry.consumer.clone()),
+                            Integer(entry.delivery_time as i64),
+                            Integer(entry.delivery_count as i64),
+                        ])
+                    })
+                    .collect();
+                let consumers = group
+                    .consumers
+                    .iter()
+                    .map(|(name, consumer)| {
+                        let pending = consumer
+                            .pending
+                            .iter()
+                            .take(limit.unwrap_or(usize::MAX))
+                            .filter_map(|entry_id| {
+                                let entry = group.pending.get(entry_id)?;
+                                Some(Array(vec![
+                                    id(entry_id),
+                                    Integer(entry.delivery_time as i64),
+                                    Integer(entry.delivery_count as i64),
+                                ]))
+                            })
+                            .collect();
+                        fields(vec![
+                            ("name", BulkString(name.clone())),
+                            ("seen-time", Integer(consumer.seen_time as i64)),
+                            ("active-time", Integer(consumer.active_time.map_or(-1, |time| time as i64))),
+                            ("pel-count", Integer(consumer.pending.len() as i64)),
+                            ("pending", Array(pending)),
+                        ])
+                    })
+                    .collect();
+                fields(vec![
+                    ("name", BulkString(name.clone())),
+                    ("last-delivered-id", id(&group.last_delivered_id)),
+                    ("entries-read", optional_int(stream.group_entries_read(group))),
+                    ("lag", optional_int(stream.group_lag(group))),
+                    ("pel-count", Integer(group.pending.len() as i64)),
+                    ("pending", Array(pending)),
+                    ("consumers", Array(consumers)),
+                ])
+            })
+            .collect();
+        fields(vec![
+            ("length", Integer(stream.len() as i64)),
+            ("last-generated-id", id(stream.last_id())),
+            ("max-deleted-entry-id", id(stream.max_deleted_entry_id())),
+            ("entries-added", Integer(stream.entries_added() as i64)),
+            ("recorded-first-entry-id", id(&stream.first_entry_id().unwrap_or(StreamID::MIN))),
+            ("entries", stream.query_xrange(&StreamID::MIN, &StreamID::MAX, limit, false)),
+            ("groups", Array(groups)),
+        ])
+    }
+}
+
+#[async_trait]
+impl Cmd for XInfoStream {
+    async fn run(&mut self) -> RespType {
+        let mut dict_guard = self.dict.lock().await;
+        match lookup_stream(&mut dict_guard, &self.key) {
+            Ok(stream) => match self.full {
+                Some(count) => Self::full(stream, count),
+                None => Self::summary(stream),
+            },
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XINFO_STREAM
+    }
+}
+
+impl XInfoStream {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let full = match args_iter.next() {
+            Some(option) if unpack_bulk_string(option)?.eq_ignore_ascii_case("full") => {
+                match args_iter.next() {
+                    Some(option) if unpack_bulk_string(option)?.eq_ignore_ascii_case("count") => {
+                        Some(next_int_arg(&mut args_iter)?.max(0) as usize)
+                    }
+                    Some(_) => return Err(CmdError::SyntaxError),
+                    None => Some(10),
+                }
+            }
+            Some(_) => return Err(CmdError::SyntaxError),
+            None => None,
+        };
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self { key, full, dict })
+    }
+}
+
+pub struct XInfoGroups {
+    pub key: String,
+    pub dict: AMRedisDB,
+}
+
+impl XInfoGroups {
+    fn group(stream: &StreamEntry, name: &str, group: &ConsumerGroup) -> RespType {
+        use RespType::{BulkString, Integer};
+        fields(vec![
+            ("name", BulkString(name.to_string())),
+            ("consumers", Integer(group.consumers.len() as i64)),
+            ("pending", Integer(group.pending.len() as i64)),
+            ("last-delivered-id", id(&group.last_delivered_id)),
+            ("entries-read", optional_int(stream.group_entries_read(group))),
+            ("lag", optional_int(stream.group_lag(group))),
+        ])
+    }
+}
+
+#[async_trait]
+impl Cmd for XInfoGroups {
+    async fn run(&mut self) -> RespType {
+        let mut dict_guard = self.dict.lock().await;
+        match lookup_stream(&mut dict_guard, &self.key) {
+            Ok(stream) => RespType::Array(
+                stream
+                    .groups
+                    .iter()
+                    .map(|(name, group)| Self::group(stream, name, group))
+                    .collect(),
+            ),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XINFO_GROUPS
+    }
+}
+
+impl XInfoGroups {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self { key, dict })
+    }
+}
+
+pub struct XInfoConsumers {
+    pub key: String,
+    pub group: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XInfoConsumers {
+    async fn run(&mut self) -> RespType {
+        use RespType::{Array, BulkString, Integer};
+        let mut dict_guard = self.dict.lock().await;
+        let group = match lookup_stream(&mut dict_guard, &self.key) {
+            Ok(stream) => stream.groups.get(&self.group),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let group = match group {
+            Some(group) => group,
+            None => return RespType::SimpleError(CmdError::NoGroup(self.key.clone(), self.group.clone()).to_string()),
+        };
+        let now = gen_millis();
+        let consumers = group
+            .consumers
+            .iter()
+            .map(|(name, consumer)| {
+                // -1 for a consumer that never got anything
+                let inactive = consumer
+                    .active_time
+                    .map_or(-1, |active_time| now.saturating_sub(active_time) as i64);
+                fields(vec![
+                    ("name", BulkString(name.clone())),
+                    ("pending", Integer(consumer.pending.len() as i64)),
+                    ("idle", Integer(now.saturating_sub(consumer.seen_time) as i64)),
+                    ("inactive", Integer(inactive)),
+                ])
+            })
+            .collect();
+        Array(consumers)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XINFO_CONSUMERS
+    }
+}
+
+impl XInfoConsumers {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let group = next_arg(&mut args_iter)?;
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self { key, group, dict })
+    }
+}
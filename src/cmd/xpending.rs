@@ -0,0 +1,470 @@
+use async_trait::async_trait;
+
+use crate::cmd::{xgroup::setid_as_resp, Cmd, CmdError, CmdType};
+use crate::consumer_group::{ConsumerGroup, PendingEntry};
+use crate::data_entry::ValueType;
+use crate::redis::{get_alive_stream, AMRedisDB, RedisDB};
+use crate::resp::RespType;
+use crate::resp_array_of_bulks;
+use crate::stream_entry::{StreamEntry, StreamID};
+use crate::clock::gen_millis;
+use crate::utils::{next_arg, next_int_arg, unpack_bulk_string};
+
+fn next_stream_id<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<StreamID, CmdError> {
+    StreamID::parse(&next_arg(args_iter)?)
+}
+
+// `-` and `+` stand for the smallest and the greatest ids
+fn next_range_id<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<StreamID, CmdError> {
+    match next_arg(args_iter)?.as_str() {
+        "-" => Ok(StreamID::MIN),
+        "+" => Ok(StreamID::MAX),
+        id => StreamID::parse(id),
+    }
+}
+
+// min-idle-time and friends are in millis, negative ones count as 0
+fn next_millis_arg<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<u128, CmdError> {
+    Ok(next_int_arg(args_iter)?.max(0) as u128)
+}
+
+/// Looks up the stream stored at `key`, NOGROUP unless it has the group.
+fn stream_and_group<'a>(
+    dict: &'a mut RedisDB,
+    key: &str,
+    group: &str,
+) -> Result<&'a mut StreamEntry, CmdError> {
+    match get_alive_stream(dict, &ValueType::new(key.to_string()))? {
+        Some(stream) if stream.groups.contains_key(group) => Ok(stream),
+        _ => Err(CmdError::NoGroup(key.to_string(), group.to_string())),
+    }
+}
+
+/// XCLAIM of `id` by the consumer it is pending for, with the delivery time and count it has now
+/// so replicas end up with the same PEL no matter when they run it.
+pub fn claim_as_resp(key: &str, group: &str, id: &StreamID, entry: &PendingEntry) -> RespType {
+    resp_array_of_bulks!(
+        "XCLAIM", key, group, entry.consumer, 0, id,
+        "TIME", entry.delivery_time, "RETRYCOUNT", entry.delivery_count, "FORCE", "JUSTID"
+    )
+}
+
+fn ids_as_resp(ids: &[StreamID]) -> RespType {
+    RespType::Array(ids.iter().map(|id| RespType::BulkString(id.to_string())).collect())
+}
+
+pub struct XAck {
+    pub key: String,
+    pub group: String,
+    pub ids: Vec<StreamID>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XAck {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let group = match get_alive_stream(&mut dict_guard, &key) {
+            Ok(stream) => stream.and_then(|stream| stream.groups.get_mut(&self.group)),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        // acknowledging for a missing key or group is a no-op
+        let acked = match group {
+            Some(group) => self.ids.iter().filter(|id| group.ack(id)).count(),
+            None => 0,
+        };
+        RespType::Integer(acked as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XACK
+    }
+}
+
+impl XAck {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let group = next_arg(&mut args_iter)?;
+        let ids = args_iter
+            .map(|id| StreamID::parse(&unpack_bulk_string(id)?))
+            .collect::<Result<Vec<_>, _>>()?;
+        if ids.is_empty() {
+            return Err(CmdError::MissingArgs);
+        }
+        Ok(Self { key, group, ids, dict })
+    }
+}
+
+/// The extended form of XPENDING, listing the pending entries themselves.
+pub struct PendingRange {
+    pub min_idle: Option<u128>,
+    pub start: StreamID,
+    pub end: StreamID,
+    pub count: usize,
+    pub consumer: Option<String>,
+}
+
+pub struct XPending {
+    pub key: String,
+    pub group: String,
+    // `None` for the summary
+    pub range: Option<PendingRange>,
+    pub dict: AMRedisDB,
+}
+
+impl XPending {
+    // `[count, smallest id, greatest id, [[consumer, count], ...]]`
+    fn summary(group: &ConsumerGroup) -> RespType {
+        use RespType::{Array, BulkString, Integer, Null, WildCard};
+        let (first, last) = match (group.pending.keys().next(), group.pending.keys().next_back()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Array(vec![Integer(0), Null, Null, WildCard("*-1\r\n".into())]),
+        };
+        let consumers = group
+            .consumers
+            .iter()
+            .filter(|(_name, consumer)| !consumer.pending.is_empty())
+            .map(|(name, consumer)| {
+                Array(vec![BulkString(name.clone()), BulkString(consumer.pending.len().to_string())])
+            })
+            .collect();
+        Array(vec![
+            Integer(group.pending.len() as i64),
+            BulkString(first.to_string()),
+            BulkString(last.to_string()),
+            Array(consumers),
+        ])
+    }
+
+    // `[[id, consumer, idle, delivery count], ...]`
+    fn entries(group: &ConsumerGroup, range: &PendingRange) -> RespType {
+        use RespType::{Array, BulkString, Integer};
+        if range.start > range.end {
+            return Array(Vec::new());
+        }
+        let entries = group
+            .pending
+            .range(range.start.clone()..=range.end.clone())
+            .filter(|(_id, entry)| range.consumer.as_ref().map_or(true, |consumer| entry.consumer == *consumer))
+            .filter(|(_id, entry)| range.min_idle.map_or(true, |min_idle| entry.idle() >= min_idle))
+            .take(range.count)
+            .map(|(id, entry)| {
+                Array(vec![
+                    BulkString(id.to_string()),
+                    BulkString(entry.consumer.clone()),
+                    Integer(entry.idle() as i64),
+                    Integer(entry.delivery_count as i64),
+                ])
+            })
+            .collect();
+        Array(entries)
+    }
+}
+
+#[async_trait]
+impl Cmd for XPending {
+    async fn run(&mut self) -> RespType {
+        let mut dict_guard = self.dict.lock().await;
+        let group = match stream_and_group(&mut dict_guard, &self.key, &self.group) {
+            Ok(stream) => &stream.groups[&self.group],
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        match self.range.as_ref() {
+            Some(range) => Self::entries(group, range),
+            None => Self::summary(group),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XPENDING
+    }
+}
+
+impl XPending {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let group = next_arg(&mut args_iter)?;
+        let mut args_iter = args_iter.peekable();
+        let range = match args_iter.peek() {
+            None => None,
+            Some(arg) => {
+                let min_idle = match unpack_bulk_string(arg)?.to_lowercase().as_str() {
+                    "idle" => {
+                        args_iter.next();
+                        Some(next_millis_arg(&mut args_iter)?)
+                    }
+                    _ => None,
+                };
+                let start = next_range_id(&mut args_iter)?;
+                let end = next_range_id(&mut args_iter)?;
+                let count = next_int_arg(&mut args_iter)?.max(0) as usize;
+                let consumer = args_iter.next().map(unpack_bulk_string).transpose()?;
+                if args_iter.next().is_some() {
+                    return Err(CmdError::SyntaxError);
+                }
+                Some(PendingRange {
+                    min_idle,
+                    start,
+                    end,
+                    count,
+                    consumer,
+                })
+            }
+        };
+        Ok(Self { key, group, range, dict })
+    }
+}
+
+pub struct XClaim {
+    pub key: String,
+    pub group: String,
+    pub consumer: String,
+    pub min_idle: u128,
+    pub ids: Vec<StreamID>,
+    // sets the idle time of the claimed entries, TIME as a unix time in millis
+    pub idle: Option<u128>,
+    pub time: Option<u128>,
+    pub retry_count: Option<u64>,
+    // claims ids that aren't pending yet, as long as they are in the stream
+    pub force: bool,
+    // replies with the ids alone and leaves the delivery counts alone
+    pub justid: bool,
+    pub last_id: Option<StreamID>,
+    // what replicas get, which entries are claimed depends on when it runs
+    pub updates: Vec<RespType>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XClaim {
+    async fn run(&mut self) -> RespType {
+        let mut dict_guard = self.dict.lock().await;
+        let stream = match stream_and_group(&mut dict_guard, &self.key, &self.group) {
+            Ok(stream) => stream,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let in_stream = self.ids.iter().map(|id| stream.contains(id)).collect::<Vec<_>>();
+        let group = stream.groups.get_mut(&self.group).unwrap();
+        if !group.consumers.contains_key(&self.consumer) {
+            self.updates.push(resp_array_of_bulks!("XGROUP", "CREATECONSUMER", self.key, self.group, self.consumer));
+        }
+        if let Some(last_id) = self.last_id.as_ref().filter(|last_id| **last_id > group.last_delivered_id) {
+            group.last_delivered_id = last_id.clone();
+            self.updates.push(setid_as_resp(&self.key, &self.group, group));
+        }
+        let now = gen_millis();
+        let delivery_time = match (self.time, self.idle) {
+            (Some(time), _) => time,
+            (None, Some(idle)) => now.saturating_sub(idle),
+            (None, None) => now,
+        };
+        let mut claimed = Vec::new();
+        for (id, in_stream) in self.ids.iter().zip(in_stream) {
+            let (idle, delivery_count) = match group.pending.get(id) {
+                // entries deleted from the stream can't be claimed anymore
+                Some(_) if !in_stream => {
+                    group.ack(id);
+                    self.updates.push(resp_array_of_bulks!("XACK", self.key, self.group, id));
+                    continue;
+                }
+                Some(entry) => (entry.idle(), entry.delivery_count),
+                None if self.force && in_stream => (0, 1),
+                None => continue,
+            };
+            if self.min_idle > 0 && idle < self.min_idle {
+                continue;
+            }
+            let delivery_count = match (self.retry_count, self.justid) {
+                (Some(retry_count), _) => retry_count,
+                (None, true) => delivery_count,
+                (None, false) => delivery_count + 1,
+            };
+            group.claim(id, &self.consumer, delivery_time, delivery_count);
+            self.updates.push(claim_as_resp(&self.key, &self.group, id, &group.pending[id]));
+            claimed.push(id.clone());
+        }
+        group.touch_consumer(&self.consumer);
+        match self.justid {
+            true => ids_as_resp(&claimed),
+            false => RespType::Array(claimed.iter().filter_map(|id| stream.entry_as_resp(id)).collect()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XCLAIM
+    }
+
+    fn propagated(&self, _resp: RespType, _reply: &RespType) -> Vec<RespType> {
+        self.updates.clone()
+    }
+}
+
+impl XClaim {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let group = next_arg(&mut args_iter)?;
+        let consumer = next_arg(&mut args_iter)?;
+        let min_idle = next_millis_arg(&mut args_iter)?;
+        let mut args_iter = args_iter.peekable();
+        let mut ids = Vec::new();
+        // the ids go on until the first option
+        while let Some(id) = args_iter.peek() {
+            match StreamID::parse(&unpack_bulk_string(id)?) {
+                Ok(id) => ids.push(id),
+                Err(_) if !ids.is_empty() => break,
+                Err(err) => return Err(err),
+            }
+            args_iter.next();
+        }
+        let mut claim = Self {
+            key,
+            group,
+            consumer,
+            min_idle,
+            ids,
+            idle: None,
+            time: None,
+            retry_count: None,
+            force: false,
+            justid: false,
+            last_id: None,
+            updates: Vec::new(),
+            dict,
+        };
+        while let Some(option) = args_iter.next() {
+            match unpack_bulk_string(option)?.to_lowercase().as_str() {
+                "idle" => claim.idle = Some(next_millis_arg(&mut args_iter)?),
+                "time" => claim.time = Some(next_millis_arg(&mut args_iter)?),
+                "retrycount" => claim.retry_count = Some(next_int_arg(&mut args_iter)?.max(0) as u64),
+                "force" => claim.force = true,
+                "justid" => claim.justid = true,
+                "lastid" => claim.last_id = Some(next_stream_id(&mut args_iter)?),
+                _ => return Err(CmdError::SyntaxError),
+            }
+        }
+        Ok(claim)
+    }
+}
+
+pub struct XAutoClaim {
+    pub key: String,
+    pub group: String,
+    pub consumer: String,
+    pub min_idle: u128,
+    pub start: StreamID,
+    pub count: usize,
+    pub justid: bool,
+    // what replicas get, which entries are claimed depends on when it runs
+    pub updates: Vec<RespType>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XAutoClaim {
+    async fn run(&mut self) -> RespType {
+        use RespType::{Array, BulkString};
+        let mut dict_guard = self.dict.lock().await;
+        let stream = match stream_and_group(&mut dict_guard, &self.key, &self.group) {
+            Ok(stream) => stream,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        // like redis it looks at no more than ten entries per entry it may claim, plus one to
+        // know where the next call starts from
+        let scanned = stream.groups[&self.group]
+            .pending
+            .range(self.start.clone()..)
+            .take(self.count.saturating_mul(10).saturating_add(1))
+            .map(|(id, entry)| (id.clone(), entry.idle(), entry.delivery_count))
+            .collect::<Vec<_>>();
+        let in_stream = scanned.iter().map(|(id, ..)| stream.contains(id)).collect::<Vec<_>>();
+        let group = stream.groups.get_mut(&self.group).unwrap();
+        if !group.consumers.contains_key(&self.consumer) {
+            self.updates.push(resp_array_of_bulks!("XGROUP", "CREATECONSUMER", self.key, self.group, self.consumer));
+        }
+        let now = gen_millis();
+        let (mut claimed, mut deleted) = (Vec::new(), Vec::new());
+        let mut next = StreamID::MIN;
+        let mut attempts = self.count.saturating_mul(10);
+        for ((id, idle, delivery_count), in_stream) in scanned.into_iter().zip(in_stream) {
+            if attempts == 0 || claimed.len() == self.count {
+                next = id;
+                break;
+            }
+            attempts -= 1;
+            if !in_stream {
+                group.ack(&id);
+                self.updates.push(resp_array_of_bulks!("XACK", self.key, self.group, id));
+                deleted.push(id);
+                continue;
+            }
+            if idle < self.min_idle {
+                continue;
+            }
+            let delivery_count = if self.justid { delivery_count } else { delivery_count + 1 };
+            group.claim(&id, &self.consumer, now, delivery_count);
+            self.updates.push(claim_as_resp(&self.key, &self.group, &id, &group.pending[&id]));
+            claimed.push(id);
+        }
+        group.touch_consumer(&self.consumer);
+        let claimed = match self.justid {
+            true => ids_as_resp(&claimed),
+            false => Array(claimed.iter().filter_map(|id| stream.entry_as_resp(id)).collect()),
+        };
+        Array(vec![BulkString(next.to_string()), claimed, ids_as_resp(&deleted)])
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XAUTOCLAIM
+    }
+
+    fn propagated(&self, _resp: RespType, _reply: &RespType) -> Vec<RespType> {
+        self.updates.clone()
+    }
+}
+
+impl XAutoClaim {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let group = next_arg(&mut args_iter)?;
+        let consumer = next_arg(&mut args_iter)?;
+        let min_idle = next_millis_arg(&mut args_iter)?;
+        let start = next_range_id(&mut args_iter)?;
+        let (mut count, mut justid) = (100, false);
+        while let Some(option) = args_iter.next() {
+            match unpack_bulk_string(option)?.to_lowercase().as_str() {
+                "count" => {
+                    count = match next_int_arg(&mut args_iter)? {
+                        count if count > 0 => count as usize,
+                        _ => return Err(CmdError::InvalidArg),
+                    }
+                }
+                "justid" => justid = true,
+                _ => return Err(CmdError::SyntaxError),
+            }
+        }
+        Ok(Self {
+            key,
+            group,
+            consumer,
+            min_idle,
+            start,
+            count,
+            justid,
+            updates: Vec::new(),
+            dict,
+        })
+    }
+}
@@ -2,51 +2,78 @@ use async_trait::async_trait;
 
 use crate::cmd::{Cmd, CmdError, CmdType};
 use crate::resp::RespType;
-use crate::redis::AMStreams;
+use crate::redis::{AMRedisDB, get_alive_stream};
 use crate::data_entry::ValueType;
-use crate::utils::unpack_bulk_string;
+use crate::stream_entry::StreamID;
+use crate::utils::{next_int_arg, unpack_bulk_string};
 
+/// XRANGE and XREVRANGE
 pub struct XRange {
     pub stream_key: String,
-    pub start_id: String,
-    pub end_id: String,
-    pub streams: AMStreams,
+    pub start_id: StreamID,
+    pub end_id: StreamID,
+    pub count: Option<usize>,
+    // walks from `end_id` down to `start_id`
+    pub rev: bool,
+    pub dict: AMRedisDB,
 }
 
 #[async_trait]
 impl Cmd for XRange {
     async fn run(&mut self) -> RespType {
         let stream_key = ValueType::new(self.stream_key.clone());
-        match self.streams.lock().await.get(&stream_key) {
-            Some(stream_entry) => {
-                let (resp, is_resp_empty) = stream_entry.query_xrange(self.start_id.clone(), self.end_id.clone());
-                resp
-            }
-            None => RespType::WildCard("*0\r\n".into()),
+        match get_alive_stream(&mut *self.dict.lock().await, &stream_key) {
+            Ok(Some(stream_entry)) => stream_entry.query_xrange(&self.start_id, &self.end_id, self.count, self.rev),
+            Ok(None) => RespType::WildCard("*0\r\n".into()),
+            Err(err) => RespType::SimpleError(err.to_string()),
         }
     }
 
     fn cmd_type(&self) -> CmdType {
-        CmdType::XRANGE
+        match self.rev {
+            true => CmdType::XREVRANGE,
+            false => CmdType::XRANGE,
+        }
     }
 }
 
 impl XRange {
     pub fn new<'a>(
         mut args_iter: &mut impl Iterator<Item = &'a RespType>,
-        streams: AMStreams,
+        dict: AMRedisDB,
+        rev: bool,
     ) -> Result<Self, CmdError> {
         let stream_key =
             unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
-        let start_id =
+        let first_id =
             unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
-        let end_id =
+        let second_id =
             unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
+        // XREVRANGE takes the end first
+        let (start_id, end_id) = match rev {
+            true => (second_id, first_id),
+            false => (first_id, second_id),
+        };
+        let start_id = StreamID::to_xrange(&start_id, true)?;
+        let end_id = StreamID::to_xrange(&end_id, false)?;
+        let count = match args_iter.next() {
+            Some(option) if unpack_bulk_string(option)?.eq_ignore_ascii_case("count") => {
+                // a negative count replies with nothing, like COUNT 0
+                Some(next_int_arg(&mut args_iter)?.max(0) as usize)
+            }
+            Some(_) => return Err(CmdError::SyntaxError),
+            None => None,
+        };
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
         Ok(Self {
             stream_key,
             start_id,
             end_id,
-            streams,
+            count,
+            rev,
+            dict,
         })
     }
 }
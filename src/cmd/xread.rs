@@ -2,61 +2,114 @@ use async_trait::async_trait;
 
 use crate::cmd::{Cmd, CmdError, CmdType};
 use crate::resp::RespType;
-use crate::redis::{AMStreamSenders, AMStreams, get_stream_reciver};
-use crate::utils::unpack_bulk_string;
+use crate::redis::{AMRedisDB, AMStreamSenders, TxLock, get_alive_stream, get_stream_reciver};
+use crate::utils::{next_int_arg, unpack_bulk_string};
 use crate::data_entry::ValueType;
+use crate::stream_entry::StreamID;
 
-use tokio::task::JoinSet;
-use tokio::time::{self, Duration};
+use futures::future::select_all;
+use tokio::time::{self, Duration, Instant};
 
 pub struct XRead {
     pub timeout: Option<Duration>,
+    pub count: Option<usize>,
     pub keys: Vec<String>,
-    pub ids: Vec<String>,
-    pub streams: AMStreams,
+    // `None` for `$`
+    pub ids: Vec<Option<StreamID>>,
+    pub dict: AMRedisDB,
     pub stream_senders: AMStreamSenders,
+    pub tx_lock: TxLock,
+    pub in_exec: bool,
 }
 
-#[async_trait]
-impl Cmd for XRead {
-    async fn run(&mut self) -> RespType {
-        use RespType::{Array, BulkString, WildCard};
+impl XRead {
+    // `None` when none of the streams has entries past its id
+    async fn read(&self) -> Result<Option<RespType>, CmdError> {
+        use RespType::{Array, BulkString};
+        let mut dict_guard = self.dict.lock().await;
         let mut result = Vec::new();
-        let mut has_items = false;
         for (key, id) in self.keys.iter().zip(self.ids.iter()) {
             let stream_key = ValueType::new(key.clone());
-            let streams_guard = self.streams.lock().await;
-            if let Some(stream_entry) = streams_guard.get(&stream_key) {
-                let (resp, resp_has_empty) = stream_entry.query_xread(id.clone());
-                has_items = has_items | resp_has_empty;
-                result.push(Array(vec![BulkString(key.clone()), resp]))
+            // `$` was resolved when the command started
+            let id = id.as_ref().unwrap();
+            if let Some(stream_entry) = get_alive_stream(&mut dict_guard, &stream_key)? {
+                let (entries, has_items) = stream_entry.query_xread(id, self.count);
+                if has_items {
+                    result.push(Array(vec![BulkString(key.clone()), entries]));
+                }
             }
-            drop(streams_guard);
         }
-        if has_items == true {
-            return Array(result);
+        Ok((!result.is_empty()).then(|| Array(result)))
+    }
+
+    // `$` stands for the last id of the stream when the command started, so only later entries
+    // are replied no matter how long it waits
+    async fn resolve_last_ids(&mut self) -> Result<(), CmdError> {
+        let mut dict_guard = self.dict.lock().await;
+        for (key, id) in self.keys.iter().zip(self.ids.iter_mut()) {
+            if id.is_none() {
+                let stream_key = ValueType::new(key.clone());
+                let last_id = get_alive_stream(&mut dict_guard, &stream_key)?
+                    .map_or(StreamID::MIN, |stream_entry| stream_entry.last_id().clone());
+                *id = Some(last_id);
+            }
         }
-        if let Some(dur) = self.timeout {
-            let block_read = async {
-                let mut tasks = JoinSet::new();
-                for key in self.keys.clone() {
-                    let key = key.clone();
-                    let mut receiver = get_stream_reciver(self.stream_senders.clone(), &key).await;
-                    tasks.spawn(async move { (key, receiver.recv().await.unwrap()) });
-                }
-                tasks.join_next().await.expect("Join Set Tasks is Empty")
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Cmd for XRead {
+    async fn run(&mut self) -> RespType {
+        let null = RespType::WildCard("$-1\r\n".into());
+        // inside a transaction it never blocks
+        let timeout = self.timeout.filter(|_| !self.in_exec);
+        let deadline = timeout.filter(|dur| !dur.is_zero()).map(|dur| Instant::now() + dur);
+        // subscribed before reading so an XADD racing with the read still wakes it up
+        let mut receivers = Vec::new();
+        if timeout.is_some() {
+            for key in self.keys.iter() {
+                receivers.push(get_stream_reciver(self.stream_senders.clone(), key).await);
+            }
+        }
+        let tx_lock = self.tx_lock.clone();
+        {
+            // EXEC already holds the lock for the whole transaction
+            let _tx_guard = match self.in_exec {
+                true => None,
+                false => Some(tx_lock.read().await),
             };
-            let res = if dur == Duration::ZERO {
-                Some(block_read.await)
-            } else {
-                time::timeout(dur, block_read).await.ok()
+            if let Err(err) = self.resolve_last_ids().await {
+                return RespType::SimpleError(err.to_string());
+            }
+        }
+        loop {
+            let tx_guard = match self.in_exec {
+                true => None,
+                false => Some(tx_lock.read().await),
             };
-            if let Some(res) = res {
-                let (key, entry_resp) = res.unwrap();
-                return Array(vec![Array(vec![BulkString(key.clone()), Array(vec![entry_resp])])]);
+            match self.read().await {
+                Ok(Some(reply)) => return reply,
+                Ok(None) => (),
+                Err(err) => return RespType::SimpleError(err.to_string()),
+            }
+            drop(tx_guard);
+            if receivers.is_empty() {
+                return null;
+            }
+            // any XADD to one of the keys is worth another read, lagging behind included
+            let wakeup = select_all(receivers.iter_mut().map(|receiver| Box::pin(receiver.recv())));
+            match deadline {
+                Some(deadline) => {
+                    if time::timeout_at(deadline, wakeup).await.is_err() {
+                        return null;
+                    }
+                }
+                None => {
+                    wakeup.await;
+                }
             }
         }
-        WildCard("$-1\r\n".into())
     }
 
     fn cmd_type(&self) -> CmdType {
@@ -67,46 +120,55 @@ impl Cmd for XRead {
 impl XRead {
     pub fn new<'a>(
         mut args_iter: &mut impl Iterator<Item = &'a RespType>,
-        streams: AMStreams,
+        dict: AMRedisDB,
         stream_senders: AMStreamSenders,
+        tx_lock: TxLock,
+        in_exec: bool,
     ) -> Result<Self, CmdError> {
         let mut block = None;
-        let option =
-            unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
-        let array = match option.to_lowercase().as_str() {
-            "block" => {
-                let timeout = unpack_bulk_string(
-                    args_iter.next().ok_or_else(|| CmdError::MissingArgs)?,
-                )?;
-                let timeout = timeout.parse::<u64>().map_err(|_| CmdError::InvalidArg)?;
-                let timeout = Duration::from_millis(timeout);
-                block = Some(timeout);
-
-                let option = unpack_bulk_string(
-                    args_iter.next().ok_or_else(|| CmdError::MissingArgs)?,
-                )?;
-                if option.to_lowercase().as_str() != "streams" {
-                    return Err(CmdError::InvalidArg)?;
+        let mut count = None;
+        loop {
+            let option =
+                unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)?;
+            match option.to_lowercase().as_str() {
+                "count" => {
+                    // a non positive count reads everything
+                    let limit = next_int_arg(&mut args_iter)?;
+                    count = (limit > 0).then_some(limit as usize);
+                }
+                "block" => {
+                    let millis = next_int_arg(&mut args_iter)?;
+                    if millis < 0 {
+                        return Err(CmdError::NegativeTimeout);
+                    }
+                    block = Some(Duration::from_millis(millis as u64));
                 }
-                args_iter.collect::<Vec<_>>()
+                "streams" => break,
+                _ => return Err(CmdError::InvalidArg)?,
             }
-            "streams" => args_iter.collect::<Vec<_>>(),
-            _ => return Err(CmdError::InvalidArg)?,
-        };
-        let mut keys = Vec::new();
-        let mut ids = Vec::new();
-        for i in 0..array.len() / 2 {
-            keys.push(unpack_bulk_string(&array[i])?);
-            ids.push(unpack_bulk_string(&array[i + array.len() / 2])?);
         }
-        println!("{:?}", keys);
-        println!("{:?}", ids);
+        let args = args_iter.collect::<Vec<_>>();
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err(CmdError::UnbalancedStreams("xread", "$"));
+        }
+        let (keys, ids) = args.split_at(args.len() / 2);
+        let keys = keys
+            .iter()
+            .map(|key| unpack_bulk_string(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ids = ids
+            .iter()
+            .map(|id| StreamID::to_xread(&unpack_bulk_string(id)?))
+            .collect::<Result<Vec<_>, _>>()?;
         Ok(XRead {
             timeout: block,
+            count,
             keys,
             ids,
-            streams,
+            dict,
             stream_senders,
+            tx_lock,
+            in_exec,
         })
     }
 }
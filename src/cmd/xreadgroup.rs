@@ -0,0 +1,219 @@
+use async_trait::async_trait;
+use futures::future::select_all;
+
+use crate::cmd::{xgroup::setid_as_resp, xpending::claim_as_resp, Cmd, CmdError, CmdType};
+use crate::data_entry::ValueType;
+use crate::keyspace_events::EventClass;
+use crate::redis::{
+    get_alive_stream, get_stream_reciver, queue_db_update, queue_expired, AMRedisDB, AMSlaves,
+    AMStreamSenders, TxLock,
+};
+use crate::resp::RespType;
+use crate::resp_array_of_bulks;
+use crate::stream_entry::StreamID;
+use crate::utils::{next_arg, next_int_arg, unpack_bulk_string};
+
+use tokio::time::{self, Duration, Instant};
+
+pub struct XReadGroup {
+    pub group: String,
+    pub consumer: String,
+    pub count: Option<usize>,
+    pub timeout: Option<Duration>,
+    // delivered entries skip the pending entries list
+    pub noack: bool,
+    pub keys: Vec<String>,
+    // `None` for `>`, the entries never delivered to the group, otherwise the consumer's history
+    pub ids: Vec<Option<StreamID>>,
+    // what replicas get, they can't read the group on their own as delivering depends on when
+    pub updates: Vec<RespType>,
+    pub db: usize,
+    pub dict: AMRedisDB,
+    pub stream_senders: AMStreamSenders,
+    // it may block, so it queues its updates on its own instead of going through `Propagate`
+    pub slaves: AMSlaves,
+    pub tx_lock: TxLock,
+    pub in_exec: bool,
+}
+
+impl XReadGroup {
+    // `None` when there was nothing new to deliver
+    async fn read(&mut self) -> Result<Option<RespType>, CmdError> {
+        use RespType::{Array, BulkString};
+        let mut dict_guard = self.dict.lock().await;
+        // nothing is delivered unless every group exists
+        let mut new_consumers = Vec::new();
+        for key in self.keys.iter() {
+            let stream_key = ValueType::new(key.clone());
+            let group = get_alive_stream(&mut dict_guard, &stream_key)?
+                .and_then(|stream| stream.groups.get(&self.group))
+                .ok_or_else(|| CmdError::NoGroupRead(key.clone(), self.group.clone()))?;
+            if !group.consumers.contains_key(&self.consumer) {
+                self.updates.push(resp_array_of_bulks!("XGROUP", "CREATECONSUMER", key, self.group, self.consumer));
+                new_consumers.push(stream_key);
+            }
+        }
+        let mut result = Vec::new();
+        for (key, id) in self.keys.iter().zip(self.ids.iter()) {
+            let stream_key = ValueType::new(key.clone());
+            let stream = get_alive_stream(&mut dict_guard, &stream_key)?.unwrap();
+            let entries = match id {
+                Some(id) => stream.read_group_history(&self.group, &self.consumer, id, self.count),
+                None => stream.read_group_new(&self.group, &self.consumer, self.count, self.noack),
+            };
+            let entries = entries.unwrap();
+            let group = &stream.groups[&self.group];
+            for (entry_id, _) in entries.iter() {
+                // entries deleted since they were delivered would be acknowledged by XCLAIM
+                match group.pending.get(entry_id) {
+                    Some(pending) if stream.contains(entry_id) => {
+                        self.updates.push(claim_as_resp(key, &self.group, entry_id, pending));
+                    }
+                    _ => (),
+                }
+            }
+            if id.is_none() && !entries.is_empty() {
+                self.updates.push(setid_as_resp(key, &self.group, group));
+            }
+            // the history is replied even when empty
+            if id.is_some() || !entries.is_empty() {
+                let entries = entries.into_iter().map(|(_, entry)| entry).collect();
+                result.push(Array(vec![BulkString(key.clone()), Array(entries)]));
+            }
+        }
+        for key in new_consumers {
+            dict_guard.notify(EventClass::Stream, "xgroup-createconsumer", &key);
+        }
+        Ok((!result.is_empty()).then(|| Array(result)))
+    }
+}
+
+#[async_trait]
+impl Cmd for XReadGroup {
+    async fn run(&mut self) -> RespType {
+        let null = RespType::WildCard("*-1\r\n".into());
+        // only reads of new entries wait for them, inside a transaction it never blocks
+        let timeout = self
+            .timeout
+            .filter(|_| !self.in_exec && self.ids.iter().all(Option::is_none));
+        let deadline = timeout.filter(|dur| !dur.is_zero()).map(|dur| Instant::now() + dur);
+        // subscribed before reading so an XADD racing with the read still wakes it up
+        let mut receivers = Vec::new();
+        if timeout.is_some() {
+            for key in self.keys.iter() {
+                receivers.push(get_stream_reciver(self.stream_senders.clone(), key).await);
+            }
+        }
+        let (tx_lock, slaves) = (self.tx_lock.clone(), self.slaves.clone());
+        loop {
+            // EXEC already holds the lock for the whole transaction
+            let tx_guard = match self.in_exec {
+                true => None,
+                false => Some(tx_lock.read().await),
+            };
+            let mut slaves_guard = slaves.lock().await;
+            let read = self.read().await;
+            let updates = std::mem::take(&mut self.updates);
+            match read {
+                Ok(read) => {
+                    queue_expired(&mut slaves_guard, self.db, &mut *self.dict.lock().await);
+                    for update in updates.iter() {
+                        queue_db_update(&mut slaves_guard, self.db, update);
+                    }
+                    if let Some(reply) = read {
+                        return reply;
+                    }
+                }
+                Err(err) => return RespType::SimpleError(err.to_string()),
+            }
+            drop(slaves_guard);
+            drop(tx_guard);
+            if receivers.is_empty() {
+                return null;
+            }
+            // any XADD to one of the keys is worth another read, lagging behind included
+            let wakeup = select_all(receivers.iter_mut().map(|receiver| Box::pin(receiver.recv())));
+            match deadline {
+                Some(deadline) => {
+                    if time::timeout_at(deadline, wakeup).await.is_err() {
+                        return null;
+                    }
+                }
+                None => {
+                    wakeup.await;
+                }
+            }
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XREADGROUP
+    }
+}
+
+impl XReadGroup {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        db: usize,
+        dict: AMRedisDB,
+        stream_senders: AMStreamSenders,
+        slaves: AMSlaves,
+        tx_lock: TxLock,
+        in_exec: bool,
+    ) -> Result<Self, CmdError> {
+        if next_arg(&mut args_iter)?.to_lowercase() != "group" {
+            return Err(CmdError::SyntaxError);
+        }
+        let group = next_arg(&mut args_iter)?;
+        let consumer = next_arg(&mut args_iter)?;
+        let (mut count, mut timeout, mut noack) = (None, None, false);
+        loop {
+            match next_arg(&mut args_iter)?.to_lowercase().as_str() {
+                "count" => {
+                    // a non positive count reads everything
+                    let limit = next_int_arg(&mut args_iter)?;
+                    count = (limit > 0).then_some(limit as usize);
+                }
+                "block" => {
+                    let millis = next_int_arg(&mut args_iter)?;
+                    if millis < 0 {
+                        return Err(CmdError::NegativeTimeout);
+                    }
+                    timeout = Some(Duration::from_millis(millis as u64));
+                }
+                "noack" => noack = true,
+                "streams" => break,
+                _ => return Err(CmdError::SyntaxError),
+            }
+        }
+        let args = args_iter.collect::<Vec<_>>();
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err(CmdError::UnbalancedStreams("xreadgroup", ">"));
+        }
+        let (keys, ids) = args.split_at(args.len() / 2);
+        let keys = keys
+            .iter()
+            .map(|key| unpack_bulk_string(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ids = ids
+            .iter()
+            .map(|id| StreamID::to_xreadgroup(&unpack_bulk_string(id)?))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            group,
+            consumer,
+            count,
+            timeout,
+            noack,
+            keys,
+            ids,
+            updates: Vec::new(),
+            db,
+            dict,
+            stream_senders,
+            slaves,
+            tx_lock,
+            in_exec,
+        })
+    }
+}
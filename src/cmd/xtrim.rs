@@ -0,0 +1,281 @@
+use async_trait::async_trait;
+
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::constants::STREAM_NODE_MAX_ENTRIES;
+use crate::data_entry::ValueType;
+use crate::keyspace_events::EventClass;
+use crate::redis::{get_alive_stream, AMRedisDB};
+use crate::resp::RespType;
+use crate::stream_entry::{StreamEntry, StreamID, StreamTrim, TrimThreshold};
+use crate::utils::{next_arg, next_int_arg, unpack_bulk_string};
+
+use std::iter::Peekable;
+
+/// Parses what follows MAXLEN or MINID in XADD and XTRIM, `[=|~] <threshold> [LIMIT <count>]`.
+pub fn parse_trim<'a, I: Iterator<Item = &'a RespType>>(
+    strategy: &str,
+    args_iter: &mut Peekable<I>,
+) -> Result<StreamTrim, CmdError> {
+    let mut threshold = next_arg(args_iter)?;
+    let approx = threshold == "~";
+    if approx || threshold == "=" {
+        threshold = next_arg(args_iter)?;
+    }
+    let threshold = match strategy {
+        "maxlen" => match threshold.parse::<i64>() {
+            Ok(max_len) if max_len >= 0 => TrimThreshold::MaxLen(max_len as usize),
+            Ok(_) => return Err(CmdError::NegativeMaxLen),
+            Err(_) => return Err(CmdError::NotInteger),
+        },
+        "minid" => TrimThreshold::MinId(StreamID::parse(&threshold)?),
+        _ => return Err(CmdError::SyntaxError),
+    };
+    // approximate trims are kept short by default, like in redis
+    let mut limit = if approx { 100 * STREAM_NODE_MAX_ENTRIES } else { 0 };
+    let has_limit = match args_iter.peek() {
+        Some(arg) => unpack_bulk_string(arg)?.eq_ignore_ascii_case("limit"),
+        None => false,
+    };
+    if has_limit {
+        args_iter.next();
+        if !approx {
+            return Err(CmdError::LimitWithoutApprox);
+        }
+        limit = match next_int_arg(args_iter)? {
+            limit if limit >= 0 => limit as usize,
+            _ => return Err(CmdError::NotInteger),
+        };
+    }
+    Ok(StreamTrim {
+        threshold,
+        approx,
+        limit,
+    })
+}
+
+/// The exact trim leaving a stream the way `trim` just left `stream`, it's what replicas get as
+/// what an approximate trim removes depends on how the entries are laid out in nodes.
+pub fn trimmed_as(trim: &StreamTrim, stream: &StreamEntry) -> StreamTrim {
+    let threshold = match (&trim.threshold, stream.first_entry_id()) {
+        (TrimThreshold::MinId(_), Some(first_id)) => TrimThreshold::MinId(first_id),
+        _ => TrimThreshold::MaxLen(stream.len()),
+    };
+    StreamTrim {
+        threshold,
+        approx: false,
+        limit: 0,
+    }
+}
+
+/// The arguments `parse_trim` reads back into an exact `trim`, its strategy included.
+pub fn trim_args(trim: &StreamTrim) -> Vec<String> {
+    match &trim.threshold {
+        TrimThreshold::MaxLen(max_len) => vec!["MAXLEN".to_string(), "=".to_string(), max_len.to_string()],
+        TrimThreshold::MinId(min_id) => vec!["MINID".to_string(), "=".to_string(), min_id.to_string()],
+    }
+}
+
+pub struct XLen {
+    pub key: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XLen {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_alive_stream(&mut *self.dict.lock().await, &key) {
+            Ok(stream) => RespType::Integer(stream.map_or(0, |stream| stream.len()) as i64),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XLEN
+    }
+}
+
+impl XLen {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self { key, dict })
+    }
+}
+
+pub struct XDel {
+    pub key: String,
+    pub ids: Vec<StreamID>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XDel {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let deleted = match get_alive_stream(&mut dict_guard, &key) {
+            Ok(Some(stream)) => self.ids.iter().filter(|id| stream.delete(id)).count(),
+            Ok(None) => 0,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        // an emptied stream stays around, it still holds its last id and groups
+        if deleted > 0 {
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::Stream, "xdel", &key);
+        }
+        RespType::Integer(deleted as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XDEL
+    }
+}
+
+impl XDel {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let ids = args_iter
+            .map(|id| StreamID::parse(&unpack_bulk_string(id)?))
+            .collect::<Result<Vec<_>, _>>()?;
+        if ids.is_empty() {
+            return Err(CmdError::MissingArgs);
+        }
+        Ok(Self { key, ids, dict })
+    }
+}
+
+pub struct XTrim {
+    pub key: String,
+    pub trim: StreamTrim,
+    // set by `run` once the stream is trimmed, see `trimmed_as`
+    pub propagated_trim: Option<StreamTrim>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XTrim {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let trimmed = match get_alive_stream(&mut dict_guard, &key) {
+            Ok(Some(stream)) => {
+                let trimmed = stream.trim(&self.trim);
+                self.propagated_trim = Some(trimmed_as(&self.trim, stream));
+                trimmed
+            }
+            Ok(None) => 0,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        if trimmed > 0 {
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::Stream, "xtrim", &key);
+        }
+        RespType::Integer(trimmed as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XTRIM
+    }
+
+    fn propagated(&self, _resp: RespType, _reply: &RespType) -> Vec<RespType> {
+        let Some(trim) = &self.propagated_trim else {
+            return Vec::new();
+        };
+        let mut args = vec!["XTRIM".to_string(), self.key.clone()];
+        args.extend(trim_args(trim));
+        vec![RespType::Array(args.into_iter().map(RespType::BulkString).collect())]
+    }
+}
+
+impl XTrim {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let strategy = next_arg(&mut args_iter)?.to_lowercase();
+        let mut args_iter = args_iter.peekable();
+        let trim = parse_trim(&strategy, &mut args_iter)?;
+        if args_iter.next().is_some() {
+            return Err(CmdError::SyntaxError);
+        }
+        Ok(Self {
+            key,
+            trim,
+            propagated_trim: None,
+            dict,
+        })
+    }
+}
+
+pub struct XSetId {
+    pub key: String,
+    pub last_id: StreamID,
+    pub entries_added: Option<u64>,
+    pub max_deleted_entry_id: Option<StreamID>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for XSetId {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let set = match get_alive_stream(&mut dict_guard, &key) {
+            Ok(Some(stream)) => stream.set_last_id(
+                self.last_id.clone(),
+                self.entries_added,
+                self.max_deleted_entry_id.clone(),
+            ),
+            Ok(None) => Err(CmdError::NoSuchKey),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = set {
+            return RespType::SimpleError(err.to_string());
+        }
+        dict_guard.touch(&key);
+        dict_guard.notify(EventClass::Stream, "xsetid", &key);
+        RespType::SimpleString("OK".to_string())
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::XSETID
+    }
+}
+
+impl XSetId {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let last_id = StreamID::parse(&next_arg(&mut args_iter)?)?;
+        let (mut entries_added, mut max_deleted_entry_id) = (None, None);
+        while let Some(option) = args_iter.next() {
+            match unpack_bulk_string(option)?.to_lowercase().as_str() {
+                "entriesadded" => match next_int_arg(&mut args_iter)? {
+                    entries_added_arg if entries_added_arg >= 0 => entries_added = Some(entries_added_arg as u64),
+                    _ => return Err(CmdError::NotInteger),
+                },
+                "maxdeletedid" => max_deleted_entry_id = Some(StreamID::parse(&next_arg(&mut args_iter)?)?),
+                _ => return Err(CmdError::SyntaxError),
+            }
+        }
+        Ok(Self {
+            key,
+            last_id,
+            entries_added,
+            max_deleted_entry_id,
+            dict,
+        })
+    }
+}
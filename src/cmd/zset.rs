@@ -0,0 +1,534 @@
+use async_trait::async_trait;
+
+use crate::cmd::{Cmd, CmdError, CmdType};
+use crate::data_entry::{DataEntry, DataValue, ValueType};
+use crate::keyspace_events::EventClass;
+use crate::redis::{get_alive_entry, AMRedisDB, RedisDB};
+use crate::resp::RespType;
+use crate::utils::{format_float, next_arg, unpack_bulk_string};
+use crate::zset_entry::{parse_score, LexBound, ScoreBound, ZSetEntry};
+
+/// Returns the sorted set stored at `key`, `Ok(None)` if there is no such key.
+fn get_zset<'a>(dict: &'a mut RedisDB, key: &ValueType) -> Result<Option<&'a mut ZSetEntry>, CmdError> {
+    match get_alive_entry(dict, key) {
+        Some(DataEntry { value: DataValue::ZSet(zset), .. }) => Ok(Some(zset)),
+        Some(_) => Err(CmdError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn get_or_create_zset<'a>(dict: &'a mut RedisDB, key: &ValueType) -> Result<&'a mut ZSetEntry, CmdError> {
+    if get_zset(dict, key)?.is_none() {
+        dict.insert(
+            ValueType::new(key.as_string()),
+            DataEntry::with_value(DataValue::ZSet(ZSetEntry::new())),
+        );
+    }
+    Ok(get_zset(dict, key)?.unwrap())
+}
+
+fn remove_if_empty(dict: &mut RedisDB, key: &ValueType) {
+    if let Ok(Some(zset)) = get_zset(dict, key) {
+        if zset.is_empty() {
+            dict.remove(key);
+        }
+    }
+}
+
+fn next_score_arg<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<f64, CmdError> {
+    parse_score(&next_arg(args_iter)?).ok_or_else(|| CmdError::NotFloat)
+}
+
+pub struct ZAdd {
+    pub key: String,
+    pub pairs: Vec<(f64, String)>,
+    pub nx: bool,
+    pub xx: bool,
+    pub gt: bool,
+    pub lt: bool,
+    pub ch: bool,
+    pub incr: bool,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for ZAdd {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let zset = match get_or_create_zset(&mut dict_guard, &key) {
+            Ok(zset) => zset,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let (mut added, mut changed) = (0, 0);
+        let mut incr_result = None;
+        for (score, member) in self.pairs.iter() {
+            let current = zset.score(member);
+            if (self.nx && current.is_some()) || (self.xx && current.is_none()) {
+                continue;
+            }
+            let score = match (self.incr, current) {
+                (true, Some(current)) => current + score,
+                _ => *score,
+            };
+            if score.is_nan() {
+                remove_if_empty(&mut dict_guard, &key);
+                return RespType::SimpleError(CmdError::ScoreIsNan.to_string());
+            }
+            if let Some(current) = current {
+                if (self.gt && score <= current) || (self.lt && score >= current) {
+                    continue;
+                }
+            }
+            if zset.insert(member.clone(), score) {
+                added += 1;
+            } else if current != Some(score) {
+                changed += 1;
+            }
+            incr_result = Some(score);
+        }
+        if added + changed > 0 {
+            dict_guard.touch(&key);
+            let event = if self.incr { "zincr" } else { "zadd" };
+            dict_guard.notify(EventClass::ZSet, event, &key);
+        }
+        remove_if_empty(&mut dict_guard, &key);
+        match (self.incr, self.ch) {
+            (true, _) => incr_result
+                .map(|score| RespType::BulkString(format_float(score)))
+                .unwrap_or(RespType::Null),
+            (false, true) => RespType::Integer(added + changed),
+            (false, false) => RespType::Integer(added),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::ZADD
+    }
+}
+
+impl ZAdd {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let args = args_iter
+            .map(unpack_bulk_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut cmd = Self {
+            key,
+            pairs: vec![],
+            nx: false,
+            xx: false,
+            gt: false,
+            lt: false,
+            ch: false,
+            incr: false,
+            dict,
+        };
+        let mut args = args.into_iter().peekable();
+        while let Some(option) = args.peek() {
+            match option.to_lowercase().as_str() {
+                "nx" => cmd.nx = true,
+                "xx" => cmd.xx = true,
+                "gt" => cmd.gt = true,
+                "lt" => cmd.lt = true,
+                "ch" => cmd.ch = true,
+                "incr" => cmd.incr = true,
+                _ => break,
+            };
+            args.next();
+        }
+        let args = args.collect::<Vec<_>>();
+        if args.is_empty() || args.len() % 2 != 0 {
+            return Err(CmdError::SyntaxError);
+        }
+        for pair in args.chunks_exact(2) {
+            let score = parse_score(&pair[0]).ok_or_else(|| CmdError::NotFloat)?;
+            cmd.pairs.push((score, pair[1].clone()));
+        }
+        if cmd.nx && cmd.xx {
+            return Err(CmdError::XXAndNX);
+        }
+        if (cmd.gt && cmd.lt) || (cmd.nx && (cmd.gt || cmd.lt)) {
+            return Err(CmdError::GTLTAndNX);
+        }
+        if cmd.incr && cmd.pairs.len() > 1 {
+            return Err(CmdError::IncrSinglePair);
+        }
+        Ok(cmd)
+    }
+}
+
+pub struct ZIncrBy {
+    pub key: String,
+    pub increment: f64,
+    pub member: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for ZIncrBy {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let zset = match get_or_create_zset(&mut dict_guard, &key) {
+            Ok(zset) => zset,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let score = zset.score(&self.member).unwrap_or(0.0) + self.increment;
+        if score.is_nan() {
+            remove_if_empty(&mut dict_guard, &key);
+            return RespType::SimpleError(CmdError::ScoreIsNan.to_string());
+        }
+        zset.insert(self.member.clone(), score);
+        dict_guard.touch(&key);
+        dict_guard.notify(EventClass::ZSet, "zincr", &key);
+        RespType::BulkString(format_float(score))
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::ZINCRBY
+    }
+}
+
+impl ZIncrBy {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let increment = next_score_arg(&mut args_iter)?;
+        let member = next_arg(&mut args_iter)?;
+        Ok(Self {
+            key,
+            increment,
+            member,
+            dict,
+        })
+    }
+}
+
+pub struct ZRem {
+    pub key: String,
+    pub members: Vec<String>,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for ZRem {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let removed = match get_zset(&mut dict_guard, &key) {
+            Ok(Some(zset)) => self.members.iter().filter(|member| zset.remove(member)).count(),
+            Ok(None) => 0,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        if removed > 0 {
+            dict_guard.touch(&key);
+            dict_guard.notify(EventClass::ZSet, "zrem", &key);
+        }
+        remove_if_empty(&mut dict_guard, &key);
+        if removed > 0 && !dict_guard.contains_key(&key) {
+            dict_guard.notify(EventClass::Generic, "del", &key);
+        }
+        RespType::Integer(removed as i64)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::ZREM
+    }
+}
+
+impl ZRem {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let members = args_iter
+            .map(unpack_bulk_string)
+            .collect::<Result<Vec<_>, _>>()?;
+        if members.is_empty() {
+            return Err(CmdError::MissingArgs);
+        }
+        Ok(Self { key, members, dict })
+    }
+}
+
+pub struct ZScore {
+    pub key: String,
+    pub member: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for ZScore {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_zset(&mut *self.dict.lock().await, &key) {
+            Ok(zset) => zset
+                .and_then(|zset| zset.score(&self.member))
+                .map(|score| RespType::BulkString(format_float(score)))
+                .unwrap_or(RespType::Null),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::ZSCORE
+    }
+}
+
+impl ZScore {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let member = next_arg(&mut args_iter)?;
+        Ok(Self { key, member, dict })
+    }
+}
+
+pub struct ZCard {
+    pub key: String,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for ZCard {
+    async fn run(&mut self) -> RespType {
+        let key = ValueType::new(self.key.clone());
+        match get_zset(&mut *self.dict.lock().await, &key) {
+            Ok(zset) => RespType::Integer(zset.map_or(0, |zset| zset.len()) as i64),
+            Err(err) => RespType::SimpleError(err.to_string()),
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::ZCARD
+    }
+}
+
+impl ZCard {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        Ok(Self { key, dict })
+    }
+}
+
+pub struct ZRank {
+    pub key: String,
+    pub member: String,
+    pub with_score: bool,
+    pub dict: AMRedisDB,
+}
+
+#[async_trait]
+impl Cmd for ZRank {
+    async fn run(&mut self) -> RespType {
+        use RespType::{Array, BulkString, Integer, Null};
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let zset = match get_zset(&mut dict_guard, &key) {
+            Ok(Some(zset)) => zset,
+            Ok(None) => return Null,
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        match (zset.rank(&self.member), zset.score(&self.member)) {
+            (Some(rank), Some(score)) if self.with_score => {
+                Array(vec![Integer(rank as i64), BulkString(format_float(score))])
+            }
+            (Some(rank), _) => Integer(rank as i64),
+            _ => Null,
+        }
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        CmdType::ZRANK
+    }
+}
+
+impl ZRank {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let member = next_arg(&mut args_iter)?;
+        let with_score = match args_iter.next() {
+            Some(option) if unpack_bulk_string(option)?.to_lowercase() == "withscore" => true,
+            Some(_) => return Err(CmdError::SyntaxError),
+            None => false,
+        };
+        Ok(Self {
+            key,
+            member,
+            with_score,
+            dict,
+        })
+    }
+}
+
+pub enum ZRangeBy {
+    Rank(i64, i64),
+    Score(ScoreBound, ScoreBound),
+    Lex(LexBound, LexBound),
+}
+
+/// ZRANGE with its BYSCORE, BYLEX, REV and LIMIT options, also used for ZRANGEBYSCORE.
+pub struct ZRange {
+    pub key: String,
+    pub by: ZRangeBy,
+    pub rev: bool,
+    pub limit: Option<(i64, i64)>,
+    pub with_scores: bool,
+    pub by_score_cmd: bool,
+    pub dict: AMRedisDB,
+}
+
+impl ZRange {
+    // ranks [start, end) in ascending order that the range covers, before applying LIMIT
+    fn rank_range(&self, zset: &ZSetEntry) -> (usize, usize) {
+        let len = zset.len() as i64;
+        match &self.by {
+            ZRangeBy::Rank(start, stop) => {
+                let start = if *start < 0 { (len + start).max(0) } else { *start };
+                let stop = if *stop < 0 { len + stop } else { (*stop).min(len - 1) };
+                if start > stop || start >= len {
+                    return (0, 0);
+                }
+                match self.rev {
+                    true => ((len - 1 - stop) as usize, (len - start) as usize),
+                    false => (start as usize, (stop + 1) as usize),
+                }
+            }
+            ZRangeBy::Score(min, max) => zset.score_range(*min, *max),
+            ZRangeBy::Lex(min, max) => zset.lex_range(min, max),
+        }
+    }
+}
+
+#[async_trait]
+impl Cmd for ZRange {
+    async fn run(&mut self) -> RespType {
+        use RespType::{Array, BulkString};
+        let key = ValueType::new(self.key.clone());
+        let mut dict_guard = self.dict.lock().await;
+        let zset = match get_zset(&mut dict_guard, &key) {
+            Ok(Some(zset)) => zset,
+            Ok(None) => return Array(vec![]),
+            Err(err) => return RespType::SimpleError(err.to_string()),
+        };
+        let (mut start, mut end) = self.rank_range(zset);
+        if let Some((offset, count)) = self.limit {
+            if offset < 0 {
+                return Array(vec![]);
+            }
+            let offset = offset as usize;
+            let count = if count < 0 { usize::MAX } else { count as usize };
+            if self.rev {
+                end = end.saturating_sub(offset).max(start);
+                start = start.max(end.saturating_sub(count));
+            } else {
+                start = (start + offset).min(end);
+                end = end.min(start.saturating_add(count));
+            }
+        }
+        let mut elements = zset.range(start, end);
+        if self.rev {
+            elements.reverse();
+        }
+        let mut result = Vec::with_capacity(elements.len() * (1 + self.with_scores as usize));
+        for (member, score) in elements {
+            result.push(BulkString(member));
+            if self.with_scores {
+                result.push(BulkString(format_float(score)));
+            }
+        }
+        Array(result)
+    }
+
+    fn cmd_type(&self) -> CmdType {
+        match self.by_score_cmd {
+            true => CmdType::ZRANGEBYSCORE,
+            false => CmdType::ZRANGE,
+        }
+    }
+}
+
+impl ZRange {
+    pub fn new<'a>(
+        mut args_iter: &mut impl Iterator<Item = &'a RespType>,
+        dict: AMRedisDB,
+        by_score_cmd: bool,
+    ) -> Result<Self, CmdError> {
+        let key = next_arg(&mut args_iter)?;
+        let start = next_arg(&mut args_iter)?;
+        let stop = next_arg(&mut args_iter)?;
+        let (mut by_score, mut by_lex, mut rev, mut with_scores) = (by_score_cmd, false, false, false);
+        let mut limit = None;
+        while let Some(option) = args_iter.next() {
+            match unpack_bulk_string(option)?.to_lowercase().as_str() {
+                "byscore" if !by_score_cmd => by_score = true,
+                "bylex" if !by_score_cmd => by_lex = true,
+                "rev" if !by_score_cmd => rev = true,
+                "withscores" => with_scores = true,
+                "limit" => {
+                    let offset = next_arg(&mut args_iter)?;
+                    let count = next_arg(&mut args_iter)?;
+                    limit = Some((
+                        offset.parse::<i64>().map_err(|_| CmdError::NotInteger)?,
+                        count.parse::<i64>().map_err(|_| CmdError::NotInteger)?,
+                    ));
+                }
+                _ => return Err(CmdError::SyntaxError),
+            }
+        }
+        if by_score && by_lex {
+            return Err(CmdError::SyntaxError);
+        }
+        if limit.is_some() && !by_score && !by_lex {
+            return Err(CmdError::LimitWithoutBy);
+        }
+        if with_scores && by_lex {
+            return Err(CmdError::WithScoresByLex);
+        }
+        // with REV the range is given from the highest to the lowest
+        let (min, max) = match rev && (by_score || by_lex) {
+            true => (stop, start),
+            false => (start, stop),
+        };
+        let by = if by_score {
+            ZRangeBy::Score(
+                ScoreBound::parse(&min).ok_or_else(|| CmdError::InvalidScoreRange)?,
+                ScoreBound::parse(&max).ok_or_else(|| CmdError::InvalidScoreRange)?,
+            )
+        } else if by_lex {
+            ZRangeBy::Lex(
+                LexBound::parse(&min).ok_or_else(|| CmdError::InvalidLexRange)?,
+                LexBound::parse(&max).ok_or_else(|| CmdError::InvalidLexRange)?,
+            )
+        } else {
+            ZRangeBy::Rank(
+                min.parse::<i64>().map_err(|_| CmdError::NotInteger)?,
+                max.parse::<i64>().map_err(|_| CmdError::NotInteger)?,
+            )
+        };
+        Ok(Self {
+            key,
+            by,
+            rev,
+            limit,
+            with_scores,
+            by_score_cmd,
+            dict,
+        })
+    }
+}
@@ -2,8 +2,18 @@ use crate::{
     constants::{
         DEFAULT_PORT,
         DEFAULT_DB_FILEPATH,
+        DEFAULT_DATABASES,
+        DEFAULT_HZ,
+        MAX_HZ,
+        DEFAULT_ACTIVE_EXPIRE_CPU_PERCENT,
+        DEFAULT_PUBSUB_OUTPUT_BUFFER_HARD_LIMIT,
+        DEFAULT_PUBSUB_OUTPUT_BUFFER_SOFT_LIMIT,
+        DEFAULT_PUBSUB_OUTPUT_BUFFER_SOFT_SECONDS,
+        DEFAULT_CLIENT_QUERY_BUFFER_LIMIT,
     },
-    utils::random_string
+    keyspace_events::NotifyFlags,
+    pubsub::OutputBufferLimit,
+    utils::{parse_memory, random_string},
 };
 use anyhow::Context;
 use std::{
@@ -100,6 +110,75 @@ impl Config {
         self.replica_of.to_string()
     }
 
+    /// How many times per second background tasks like the active expiry cycle run.
+    pub fn hz(&self) -> u64 {
+        self.parameters
+            .get("hz")
+            .and_then(|hz| hz.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_HZ)
+            .clamp(1, MAX_HZ)
+    }
+
+    /// Share of each `1/hz` period the active expiry cycle may spend deleting keys.
+    pub fn active_expire_cpu_percent(&self) -> u64 {
+        self.parameters
+            .get("active-expire-cpu-percent")
+            .and_then(|percent| percent.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_ACTIVE_EXPIRE_CPU_PERCENT)
+            .clamp(1, 100)
+    }
+
+    /// Number of logical databases, they are all created at startup.
+    pub fn databases(&self) -> usize {
+        self.parameters
+            .get("databases")
+            .and_then(|databases| databases.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_DATABASES)
+            .max(1)
+    }
+
+    /// Which keyspace events get published, none by default.
+    pub fn notify_keyspace_events(&self) -> NotifyFlags {
+        self.parameters
+            .get("notify-keyspace-events")
+            .and_then(|flags| NotifyFlags::parse(flags))
+            .unwrap_or_default()
+    }
+
+    /// Output buffer limit of pub/sub subscribers, taken from the `pubsub` class of
+    /// `client-output-buffer-limit` i.e `pubsub <hard> <soft> <soft seconds>`.
+    pub fn pubsub_output_buffer_limit(&self) -> OutputBufferLimit {
+        let default = OutputBufferLimit {
+            hard: DEFAULT_PUBSUB_OUTPUT_BUFFER_HARD_LIMIT,
+            soft: DEFAULT_PUBSUB_OUTPUT_BUFFER_SOFT_LIMIT,
+            soft_seconds: DEFAULT_PUBSUB_OUTPUT_BUFFER_SOFT_SECONDS,
+        };
+        let limits = match self.parameters.get("client-output-buffer-limit") {
+            Some(limits) => limits.split_whitespace().collect::<Vec<_>>(),
+            None => return default,
+        };
+        limits
+            .chunks_exact(4)
+            .find(|class| class[0] == "pubsub")
+            .and_then(|class| {
+                Some(OutputBufferLimit {
+                    hard: parse_memory(class[1])?,
+                    soft: parse_memory(class[2])?,
+                    soft_seconds: class[3].parse::<u64>().ok()?,
+                })
+            })
+            .unwrap_or(default)
+    }
+
+    /// How many bytes of a command that's still incomplete a connection may buffer before it's
+    /// closed.
+    pub fn client_query_buffer_limit(&self) -> usize {
+        self.parameters
+            .get("client-query-buffer-limit")
+            .and_then(|limit| parse_memory(limit))
+            .unwrap_or(DEFAULT_CLIENT_QUERY_BUFFER_LIMIT)
+    }
+
     pub fn get_db_filepath(&self) -> PathBuf {
         let binding = String::default();
         let dir = self.parameters.get("dir").unwrap_or(&binding);
@@ -182,6 +261,79 @@ impl TryFrom<env::Args> for Config {
                         .to_owned();
                     cfg.parameters.insert("dbfilename".to_string(), db_filename);
                 }
+                "--hz" => {
+                    let hz = args
+                        .next()
+                        .context("usage --hz <number:u64>")?
+                        .trim()
+                        .parse::<u64>()
+                        .context("expected hz to be valid u64")?;
+                    cfg.parameters.insert("hz".to_string(), hz.to_string());
+                }
+                "--active-expire-cpu-percent" => {
+                    let percent = args
+                        .next()
+                        .context("usage --active-expire-cpu-percent <percent:u64>")?
+                        .trim()
+                        .parse::<u64>()
+                        .context("expected active-expire-cpu-percent to be valid u64")?;
+                    cfg.parameters.insert("active-expire-cpu-percent".to_string(), percent.to_string());
+                }
+                "--databases" => {
+                    let databases = args
+                        .next()
+                        .context("usage --databases <number:usize>")?
+                        .trim()
+                        .parse::<usize>()
+                        .context("expected databases to be valid usize")?;
+                    cfg.parameters.insert("databases".to_string(), databases.to_string());
+                }
+                "--notify-keyspace-events" => {
+                    let flags = args
+                        .next()
+                        .context("usage --notify-keyspace-events <flags:String>")?
+                        .trim()
+                        .to_owned();
+                    NotifyFlags::parse(&flags).context("expected notify-keyspace-events to be made of KEg$lshzxetmnA")?;
+                    cfg.parameters.insert("notify-keyspace-events".to_string(), flags);
+                }
+                "--client-output-buffer-limit" => {
+                    let usage = "usage --client-output-buffer-limit <class:normal|replica|pubsub> <hard:memory> <soft:memory> <soft_seconds:u64>";
+                    let class = args.next().context(usage)?.trim().to_lowercase();
+                    let class = match class.as_str() {
+                        "normal" | "pubsub" => class,
+                        "replica" | "slave" => "slave".to_string(),
+                        _ => anyhow::bail!("expected client-output-buffer-limit class to be normal, replica or pubsub"),
+                    };
+                    let hard = args.next().context(usage)?;
+                    let hard = parse_memory(&hard).context("expected hard limit to be a valid memory size")?;
+                    let soft = args.next().context(usage)?;
+                    let soft = parse_memory(&soft).context("expected soft limit to be a valid memory size")?;
+                    let soft_seconds = args
+                        .next()
+                        .context(usage)?
+                        .trim()
+                        .parse::<u64>()
+                        .context("expected soft_seconds to be valid u64")?;
+                    // kept in the same `<class> <hard> <soft> <seconds> ...` form CONFIG GET shows
+                    let limit = format!("{} {} {} {}", class, hard, soft, soft_seconds);
+                    let limits = cfg.parameters.entry("client-output-buffer-limit".to_string()).or_default();
+                    let others = limits
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .chunks_exact(4)
+                        .filter(|other| other[0] != class)
+                        .map(|other| other.join(" "))
+                        .collect::<Vec<_>>();
+                    *limits = others.into_iter().chain(std::iter::once(limit)).collect::<Vec<_>>().join(" ");
+                }
+                "--client-query-buffer-limit" => {
+                    let limit = args
+                        .next()
+                        .context("usage --client-query-buffer-limit <limit:memory>")?;
+                    let limit = parse_memory(&limit).context("expected client-query-buffer-limit to be a valid memory size")?;
+                    cfg.parameters.insert("client-query-buffer-limit".to_string(), limit.to_string());
+                }
                 _ => panic!("ERROR: unsported argument"),
             };
         }
@@ -3,6 +3,7 @@ pub const LF: u8 = b'\n';
 
 pub const DEFAULT_PORT: u16 = 6379;
 pub const DEFAULT_DB_FILEPATH: &str = "./dump.rdb";
+pub const DEFAULT_DATABASES: usize = 16;
 
 pub const MAGIC: &str = "REDIS";
 pub const MAGIC_BYTES: usize = MAGIC.len();
@@ -14,6 +15,27 @@ pub const COMPRESS_AT_LENGTH: u16 = 150;
 
 pub const SLAVE_LIFETIME_LIMIT: usize = 3;
 
+pub const DEFAULT_HZ: u64 = 10;
+pub const MAX_HZ: u64 = 500;
+pub const DEFAULT_ACTIVE_EXPIRE_CPU_PERCENT: u64 = 25;
+pub const ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP: usize = 20;
+pub const ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE: usize = 10;
+
+pub const SET_MAX_INTSET_ENTRIES: usize = 512;
+pub const EMBSTR_MAX_LENGTH: usize = 44;
+// a stream node takes new entries until it holds that many of them or that many bytes,
+// approximate trimming only ever removes whole nodes
+pub const STREAM_NODE_MAX_ENTRIES: usize = 100;
+pub const STREAM_NODE_MAX_BYTES: usize = 4096;
+
+// how much is read from a connection at once, bigger commands take several reads
+pub const READ_CHUNK_SIZE: usize = 16 * 1024;
+pub const DEFAULT_CLIENT_QUERY_BUFFER_LIMIT: usize = 1024 * 1024 * 1024;
+
+pub const DEFAULT_PUBSUB_OUTPUT_BUFFER_HARD_LIMIT: usize = 32 * 1024 * 1024;
+pub const DEFAULT_PUBSUB_OUTPUT_BUFFER_SOFT_LIMIT: usize = 8 * 1024 * 1024;
+pub const DEFAULT_PUBSUB_OUTPUT_BUFFER_SOFT_SECONDS: u64 = 60;
+
 macro_rules! rdb_opcode {
     ( $( ($opcode:expr, $konst:ident);)+) => {
         $( pub const $konst: u8 = $opcode; )+
@@ -29,3 +51,18 @@ rdb_opcode! {
     (0xFA, AUX);
 }
 
+rdb_opcode! {
+    (0x00, RDB_TYPE_STRING);
+    (0x01, RDB_TYPE_LIST);
+    (0x02, RDB_TYPE_SET);
+    (0x04, RDB_TYPE_HASH);
+    (0x05, RDB_TYPE_ZSET_2);
+    (0x0F, RDB_TYPE_STREAM_LISTPACKS);
+    (0x13, RDB_TYPE_STREAM_LISTPACKS_2);
+    (0x15, RDB_TYPE_STREAM_LISTPACKS_3);
+}
+
+// lengths that don't fit in 14 bits, followed by a big endian u32 or u64
+pub const RDB_32BITLEN: u8 = 0x80;
+pub const RDB_64BITLEN: u8 = 0x81;
+
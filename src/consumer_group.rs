@@ -0,0 +1,152 @@
+use crate::stream_entry::StreamID;
+use crate::clock::gen_millis;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// An entry delivered to a consumer that wasn't acknowledged yet.
+#[derive(Debug, Clone)]
+pub struct PendingEntry {
+    pub consumer: String,
+    pub delivery_time: u128,
+    pub delivery_count: u64,
+}
+
+impl PendingEntry {
+    pub fn idle(&self) -> u128 {
+        gen_millis().saturating_sub(self.delivery_time)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Consumer {
+    // last time the consumer tried to read or claim anything
+    pub seen_time: u128,
+    // last time it actually got entries, `None` if it never did
+    pub active_time: Option<u128>,
+    pub pending: BTreeSet<StreamID>,
+}
+
+impl Consumer {
+    fn new() -> Self {
+        Self {
+            seen_time: gen_millis(),
+            active_time: None,
+            pending: BTreeSet::new(),
+        }
+    }
+}
+
+/// A consumer group reads the stream from `last_delivered_id` onwards, handing every entry to a
+/// single consumer and keeping it in the pending entries list (PEL) until it is acknowledged.
+#[derive(Debug, Clone)]
+pub struct ConsumerGroup {
+    pub last_delivered_id: StreamID,
+    // entries delivered since the start of the stream, `None` when it isn't known like after
+    // XGROUP SETID to an arbitrary id
+    pub entries_read: Option<u64>,
+    pub pending: BTreeMap<StreamID, PendingEntry>,
+    pub consumers: BTreeMap<String, Consumer>,
+}
+
+impl ConsumerGroup {
+    pub fn new(last_delivered_id: StreamID, entries_read: Option<u64>) -> Self {
+        Self {
+            last_delivered_id,
+            entries_read,
+            pending: BTreeMap::new(),
+            consumers: BTreeMap::new(),
+        }
+    }
+
+    /// Returns false if the consumer already existed.
+    pub fn create_consumer(&mut self, name: &str) -> bool {
+        if self.consumers.contains_key(name) {
+            return false;
+        }
+        self.consumers.insert(name.to_string(), Consumer::new());
+        true
+    }
+
+    /// Marks the consumer as seen, creating it if needed.
+    pub fn touch_consumer(&mut self, name: &str) -> &mut Consumer {
+        let consumer = self.consumers.entry(name.to_string()).or_insert_with(Consumer::new);
+        consumer.seen_time = gen_millis();
+        consumer
+    }
+
+    /// Deletes the consumer along with its pending entries, returning how many it had.
+    pub fn delete_consumer(&mut self, name: &str) -> Option<usize> {
+        let consumer = self.consumers.remove(name)?;
+        for id in consumer.pending.iter() {
+            self.pending.remove(id);
+        }
+        Some(consumer.pending.len())
+    }
+
+    fn activate(&mut self, consumer: &str) {
+        self.touch_consumer(consumer).active_time = Some(gen_millis());
+    }
+
+    /// Hands `id` to `consumer` for the first time, unless NOACK skips the PEL.
+    pub fn deliver(&mut self, id: &StreamID, consumer: &str, noack: bool) {
+        if self.last_delivered_id < *id {
+            self.last_delivered_id = id.clone();
+            if let Some(entries_read) = self.entries_read.as_mut() {
+                *entries_read += 1;
+            }
+        }
+        self.activate(consumer);
+        if noack {
+            return;
+        }
+        // the group may have been moved back with SETID, so the entry can still be pending
+        self.ack(id);
+        self.pending.insert(
+            id.clone(),
+            PendingEntry {
+                consumer: consumer.to_string(),
+                delivery_time: gen_millis(),
+                delivery_count: 1,
+            },
+        );
+        self.touch_consumer(consumer).pending.insert(id.clone());
+    }
+
+    /// Hands a pending entry to its consumer again, when it reads its own history.
+    pub fn redeliver(&mut self, id: &StreamID) {
+        if let Some(entry) = self.pending.get_mut(id) {
+            entry.delivery_time = gen_millis();
+            entry.delivery_count += 1;
+            let consumer = entry.consumer.clone();
+            self.activate(&consumer);
+        }
+    }
+
+    /// Moves a pending entry to `consumer`, creating the entry first if it isn't pending.
+    pub fn claim(&mut self, id: &StreamID, consumer: &str, delivery_time: u128, delivery_count: u64) {
+        self.ack(id);
+        self.pending.insert(
+            id.clone(),
+            PendingEntry {
+                consumer: consumer.to_string(),
+                delivery_time,
+                delivery_count,
+            },
+        );
+        self.touch_consumer(consumer).pending.insert(id.clone());
+        self.activate(consumer);
+    }
+
+    /// Removes `id` from the PEL, returns false if it wasn't pending.
+    pub fn ack(&mut self, id: &StreamID) -> bool {
+        match self.pending.remove(id) {
+            Some(entry) => {
+                if let Some(consumer) = self.consumers.get_mut(&entry.consumer) {
+                    consumer.pending.remove(id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -1,15 +1,24 @@
 use crate::{
-    constants::{COMPRESS_AT_LENGTH, EXPIRETIMEMS},
+    constants::{
+        COMPRESS_AT_LENGTH, EMBSTR_MAX_LENGTH, EXPIRETIMEMS, RDB_32BITLEN, RDB_64BITLEN, RDB_TYPE_HASH,
+        RDB_TYPE_LIST, RDB_TYPE_SET, RDB_TYPE_STREAM_LISTPACKS_3, RDB_TYPE_STRING, RDB_TYPE_ZSET_2,
+    },
+    hash_entry::HashEntry,
+    list_entry::ListEntry,
+    set_entry::SetEntry,
+    stream_entry::StreamEntry,
+    zset_entry::ZSetEntry,
 };
 
 use std::time::SystemTime;
-use tokio::time::{Duration, Instant};
+use tokio::time::Instant;
 
-#[derive(Debug, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
 pub enum ValueType {
     I8Int(i8),
     I16Int(i16),
     I32Int(i32),
+    I64Int(i64),
     IntOrString(String),
     CompressedString {
         real_data_len: usize,
@@ -19,16 +28,14 @@ pub enum ValueType {
 
 impl ValueType {
     pub fn new(data: String) -> Self {
-        if let Ok(as_i8) = data.parse::<i8>() {
-            Self::I8Int(as_i8)
-        } else if let Ok(as_i16) = data.parse::<i16>() {
-            Self::I16Int(as_i16)
-        } else if let Ok(as_i32) = data.parse::<i32>() {
-            Self::I32Int(as_i32)
+        // only the canonical form is kept as an integer, `+1` or `01` must read back as given
+        let int = data.parse::<i64>().ok().filter(|int| int.to_string() == data);
+        if let Some(int) = int {
+            Self::from_int(int)
         } else if data.len() < COMPRESS_AT_LENGTH as usize {
             Self::IntOrString(data)
         } else {
-            match lzf::compress(&data.as_bytes()) {
+            match lzf::compress(data.as_bytes()) {
                 Ok(compressed_data) => Self::CompressedString {
                     real_data_len: data.len(),
                     compressed_data,
@@ -45,27 +52,62 @@ impl ValueType {
             I8Int(x) => x.to_string(),
             I16Int(x) => x.to_string(),
             I32Int(x) => x.to_string(),
+            I64Int(x) => x.to_string(),
             IntOrString(x) => x.to_string(),
             CompressedString {
                 real_data_len,
                 compressed_data,
             } => {
                 // TODO: handle errors better
-                let decompressed = lzf::decompress(&compressed_data, *real_data_len).unwrap();
+                let decompressed = lzf::decompress(compressed_data, *real_data_len).unwrap();
                 String::from(String::from_utf8_lossy(&decompressed))
             }
         }
     }
 
+    pub fn encoding(&self) -> &'static str {
+        use ValueType::*;
+        match self {
+            I8Int(_) | I16Int(_) | I32Int(_) | I64Int(_) => "int",
+            IntOrString(x) if x.len() <= EMBSTR_MAX_LENGTH => "embstr",
+            IntOrString(_) | CompressedString { .. } => "raw",
+        }
+    }
+
     pub fn type_as_string(&self) -> String {
         use ValueType::*;
         match self {
-            I8Int(_) | I16Int(_) | I32Int(_)  => "integer".to_string(),
+            I8Int(_) | I16Int(_) | I32Int(_) | I64Int(_) => "integer".to_string(),
             IntOrString(_) | CompressedString { .. } => "string".to_string(),
         }
     }
 
-    fn number_to_length_encoded(number: u32) -> Vec<u8> {
+    /// Stores `value` with the smallest integer encoding that fits it.
+    pub fn from_int(value: i64) -> Self {
+        if let Ok(as_i8) = i8::try_from(value) {
+            Self::I8Int(as_i8)
+        } else if let Ok(as_i16) = i16::try_from(value) {
+            Self::I16Int(as_i16)
+        } else if let Ok(as_i32) = i32::try_from(value) {
+            Self::I32Int(as_i32)
+        } else {
+            Self::I64Int(value)
+        }
+    }
+
+    /// Returns the value as an integer if it's integer encoded.
+    pub fn as_int(&self) -> Option<i64> {
+        use ValueType::*;
+        match self {
+            I8Int(x) => Some(*x as i64),
+            I16Int(x) => Some(*x as i64),
+            I32Int(x) => Some(*x as i64),
+            I64Int(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn number_to_length_encoded(number: u32) -> Vec<u8> {
         match number {
             0..=63 => {
                 // 0  = 0b0000_0000 => le_bytes [[00]00_0000]
@@ -81,7 +123,6 @@ impl ValueType {
                 //
                 // the parser is using
                 // length = ((bytes[0] & 0x3F) << 8) | bytes[1]
-                let le_bytes = (number as u16).to_le_bytes();
                 let first_byte = ((number >> 8) & 0b00111111) as u8 | 0b01000000;
                 let second_byte = (number & 0xFF) as u8;
                 vec![first_byte, second_byte]
@@ -89,15 +130,34 @@ impl ValueType {
             16384..=u32::MAX => {
                 // 16384    = 0x3FFF
                 // u32::MAX = 0xFFFF
-                // 0b[10][aa_aaaa][next_4_byte] =>
-                // 0b[type=10][len=u32::from_le_bytes(next_4_byte)
-                let mut out: Vec<u8> = vec![0b10000000];
-                out.extend_from_slice(&number.to_le_bytes());
+                // 0b[10][00_0000][next_4_byte] =>
+                // 0b[type=10][len=u32::from_be_bytes(next_4_byte)
+                let mut out: Vec<u8> = vec![RDB_32BITLEN];
+                out.extend_from_slice(&number.to_be_bytes());
                 out
             }
         }
     }
 
+    /// Like `number_to_length_encoded`, with the 64 bit encoding for what doesn't fit in a u32.
+    pub fn u64_to_length_encoded(number: u64) -> Vec<u8> {
+        match u32::try_from(number) {
+            Ok(number) => Self::number_to_length_encoded(number),
+            Err(_) => {
+                let mut out: Vec<u8> = vec![RDB_64BITLEN];
+                out.extend_from_slice(&number.to_be_bytes());
+                out
+            }
+        }
+    }
+
+    /// Raw bytes as a length prefixed string, without looking for integers or compressing them.
+    pub fn bytes_as_rdb(bytes: &[u8]) -> Vec<u8> {
+        let mut out = Self::number_to_length_encoded(bytes.len() as u32);
+        out.extend_from_slice(bytes);
+        out
+    }
+
     fn as_length_encoded_value(&self) -> Vec<u8> {
         use ValueType::*;
         match self {
@@ -124,8 +184,14 @@ impl ValueType {
                 let x = x.to_le_bytes();
                 vec![0xc2, x[0], x[1], x[2], x[3]]
             }
+            I64Int(x) => {
+                // the rdb integer encodings stop at i32, bigger integers are stored as strings
+                let x = x.to_string();
+                let mut out: Vec<u8> = Self::number_to_length_encoded(x.len() as u32);
+                out.extend_from_slice(x.as_bytes());
+                out
+            }
             IntOrString(x) => {
-                let x_len = x.len() as u32;
                 let mut out: Vec<u8> = Self::number_to_length_encoded(x.len() as u32);
                 out.extend_from_slice(x.as_bytes());
                 out
@@ -134,17 +200,10 @@ impl ValueType {
         }
     }
 
-    #[inline]
-    pub fn as_rdb_value_type(&self) -> Vec<u8> {
-        // TODO: for now all values considered of type String
-        // this function is just in case of supporting other types later
-        vec![0]
-    }
-
     pub fn as_rdb(&self) -> Vec<u8> {
         use ValueType::*;
         match self {
-            I8Int { .. } | I16Int { .. } | I32Int { .. } | IntOrString { .. } => {
+            I8Int { .. } | I16Int { .. } | I32Int { .. } | I64Int { .. } | IntOrString { .. } => {
                 self.as_length_encoded_value()
             }
             CompressedString {
@@ -164,25 +223,120 @@ impl ValueType {
                 let real_len = Self::number_to_length_encoded(*real_data_len as u32);
                 out.extend_from_slice(&comp_len);
                 out.extend_from_slice(&real_len);
-                out.extend_from_slice(&compressed_data);
+                out.extend_from_slice(compressed_data);
+                out
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DataValue {
+    String(ValueType),
+    List(ListEntry),
+    Hash(HashEntry),
+    Set(SetEntry),
+    ZSet(ZSetEntry),
+    Stream(StreamEntry),
+}
+
+impl DataValue {
+    pub fn type_as_string(&self) -> String {
+        use DataValue::*;
+        match self {
+            String(_) => "string".to_string(),
+            List(_) => "list".to_string(),
+            Hash(_) => "hash".to_string(),
+            Set(_) => "set".to_string(),
+            ZSet(_) => "zset".to_string(),
+            Stream(_) => "stream".to_string(),
+        }
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        use DataValue::*;
+        match self {
+            String(value) => value.encoding(),
+            List(_) => "quicklist",
+            Hash(_) => "hashtable",
+            Set(set) => set.encoding(),
+            ZSet(_) => "skiplist",
+            Stream(_) => "stream",
+        }
+    }
+
+    #[inline]
+    pub fn as_rdb_value_type(&self) -> u8 {
+        use DataValue::*;
+        match self {
+            String(_) => RDB_TYPE_STRING,
+            List(_) => RDB_TYPE_LIST,
+            Hash(_) => RDB_TYPE_HASH,
+            Set(_) => RDB_TYPE_SET,
+            ZSet(_) => RDB_TYPE_ZSET_2,
+            Stream(_) => RDB_TYPE_STREAM_LISTPACKS_3,
+        }
+    }
+
+    pub fn as_rdb(&self) -> Vec<u8> {
+        use DataValue::*;
+        match self {
+            String(value) => value.as_rdb(),
+            List(list) => {
+                let mut out = ValueType::number_to_length_encoded(list.len() as u32);
+                for item in list.iter() {
+                    out.extend_from_slice(&ValueType::new(item.clone()).as_rdb()[..]);
+                }
+                out
+            }
+            Hash(hash) => {
+                let mut out = ValueType::number_to_length_encoded(hash.len() as u32);
+                for (field, value) in hash.iter() {
+                    out.extend_from_slice(&ValueType::new(field.clone()).as_rdb()[..]);
+                    out.extend_from_slice(&ValueType::new(value.clone()).as_rdb()[..]);
+                }
+                out
+            }
+            Set(set) => {
+                let mut out = ValueType::number_to_length_encoded(set.len() as u32);
+                for member in set.members() {
+                    out.extend_from_slice(&ValueType::new(member).as_rdb()[..]);
+                }
+                out
+            }
+            ZSet(zset) => {
+                let mut out = ValueType::number_to_length_encoded(zset.len() as u32);
+                for (member, score) in zset.iter() {
+                    out.extend_from_slice(&ValueType::new(member.clone()).as_rdb()[..]);
+                    out.extend_from_slice(&score.to_le_bytes());
+                }
                 out
             }
+            Stream(stream) => stream.as_rdb(RDB_TYPE_STREAM_LISTPACKS_3),
         }
     }
 }
 
 #[derive(Debug)]
 pub struct DataEntry {
-    pub value: ValueType,
+    pub value: DataValue,
     pub created_at: Instant,
     pub expired_at_unix_millis: Option<SystemTime>,
 }
 
 impl DataEntry {
-    pub fn new(data: String, expired_millis: Option<u64>) -> Self {
+    pub fn new(data: String, expired_at: Option<SystemTime>) -> Self {
         Self {
-            value: ValueType::new(data),
-            expired_at_unix_millis: expired_millis.map(|ms| SystemTime::now() + Duration::from_millis(ms)),
+            value: DataValue::String(ValueType::new(data)),
+            expired_at_unix_millis: expired_at,
+            created_at: Instant::now(),
+        }
+    }
+
+    pub fn with_value(value: DataValue) -> Self {
+        Self {
+            value,
+            expired_at_unix_millis: None,
             created_at: Instant::now(),
         }
     }
@@ -197,19 +351,16 @@ impl DataEntry {
 
 pub fn key_value_as_rdb(key: &ValueType, value: &DataEntry) -> Vec<u8> {
     // expiry_opcode|None expiry_4_bytes(secs)|expiry_8_bytes(millis)|None
-    // 1 byte value_type (only support string encoded type for now i.e val_type=0)
+    // 1 byte value_type
     // string_encoded_key
     // encoded_value
     let mut out: Vec<u8> = vec![];
     // TODO: should write specified Unix time at which the key will expire, in milliseconds
-    match value.expired_at_unix_millis {
-        Some(expiry) => {
-            out = vec![EXPIRETIMEMS];
-            out.extend_from_slice(&(expiry.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as u64).to_le_bytes());
-        }
-        None => (),
-    };
-    out.push(value.value.as_rdb_value_type()[0]);
+    if let Some(expiry) = value.expired_at_unix_millis {
+        out = vec![EXPIRETIMEMS];
+        out.extend_from_slice(&(expiry.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis() as u64).to_le_bytes());
+    }
+    out.push(value.value.as_rdb_value_type());
     out.extend_from_slice(&key.as_rdb()[..]);
     out.extend_from_slice(&value.value.as_rdb()[..]);
     out
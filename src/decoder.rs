@@ -0,0 +1,216 @@
+use crate::constants::READ_CHUNK_SIZE;
+use crate::parser::{ParseError, Parser};
+use crate::resp::RespType;
+
+use tokio::net::TcpStream;
+
+/// Per connection buffer of the bytes read so far, RESP frames are taken out of it as soon as
+/// they're complete while a frame split across reads is kept until the rest of it arrives.
+pub struct RespDecoder {
+    buffer: Vec<u8>,
+    // start of the bytes not decoded yet, the decoded ones are dropped on the next read
+    start: usize,
+    // how many not decoded bytes are allowed to pile up
+    limit: usize,
+    // how many bytes past `start` the frame being received takes at least, known once the
+    // header of a bulk string it's waiting for was parsed
+    needed: usize,
+}
+
+impl RespDecoder {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            buffer: Vec::new(),
+            start: 0,
+            limit,
+            needed: 0,
+        }
+    }
+
+    pub fn pending(&self) -> &[u8] {
+        &self.buffer[self.start..]
+    }
+
+    /// Appends bytes read from the connection, failing once a frame that's still incomplete
+    /// grows past the limit.
+    pub fn extend(&mut self, bytes: &[u8]) -> Result<(), ParseError> {
+        self.buffer.drain(..self.start);
+        self.start = 0;
+        self.buffer.extend_from_slice(bytes);
+        match self.buffer.len() > self.limit {
+            true => Err(ParseError::QueryBufferLimit),
+            false => Ok(()),
+        }
+    }
+
+    /// The next complete frame along with how many bytes it took, `None` until it's complete.
+    pub fn next_frame(&mut self) -> Result<Option<(RespType, usize)>, ParseError> {
+        let pending = self.pending();
+        if pending.len() < self.needed {
+            return Ok(None);
+        }
+        match Parser::parse_resp(pending) {
+            Ok((frame, rem)) => {
+                let len = pending.len() - rem.len();
+                self.start += len;
+                self.needed = 0;
+                Ok(Some((frame, len)))
+            }
+            Err(ParseError::IncompleteInput) => Ok(None),
+            Err(ParseError::IncompleteBulk(missing)) => {
+                let needed = pending.len().saturating_add(missing);
+                // it would never fit, no point in waiting for it
+                if needed > self.limit {
+                    return Err(ParseError::QueryBufferLimit);
+                }
+                self.needed = needed;
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The RDB file of a full resync, sent as `$<length>\r\n` and the file with no CRLF after it.
+    pub fn next_rdb(&mut self) -> Result<Option<Vec<u8>>, ParseError> {
+        let pending = self.pending();
+        let rest = match pending.split_first() {
+            Some((b'$', rest)) => rest,
+            Some(_) => return Err(ParseError::InvalidInput),
+            None => return Ok(None),
+        };
+        let (len, rest) = match Parser::parse_until_crlf(rest) {
+            Ok(line) => line,
+            Err(ParseError::IncompleteInput) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let len = String::from_utf8_lossy(len)
+            .parse::<usize>()
+            .map_err(|_| ParseError::InvalidInput)?;
+        match rest.get(..len) {
+            Some(rdb) => {
+                let (rdb, taken) = (rdb.to_vec(), pending.len() - rest.len() + len);
+                self.start += taken;
+                Ok(Some(rdb))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Reads whatever the connection has, `Ok(0)` once it's closed.
+    pub async fn read_from(&mut self, stream: &TcpStream) -> anyhow::Result<usize> {
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        loop {
+            stream.readable().await?;
+            match stream.try_read(&mut chunk) {
+                Ok(n) => {
+                    self.extend(&chunk[..n])?;
+                    return Ok(n);
+                }
+                Err(ref e) if e.kind() == tokio::io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resp_array_of_bulks;
+
+    fn new_decoder() -> RespDecoder {
+        RespDecoder::new(1024 * 1024)
+    }
+
+    #[test]
+    fn frame_split_across_reads() {
+        let cmd = resp_array_of_bulks!("SET", "key", "x".repeat(5000));
+        let bytes = cmd.serialize();
+        let mut decoder = new_decoder();
+        for chunk in bytes.chunks(7) {
+            assert_eq!(decoder.next_frame().unwrap(), None);
+            decoder.extend(chunk).unwrap();
+        }
+        assert_eq!(decoder.next_frame().unwrap(), Some((cmd, bytes.len())));
+        assert_eq!(decoder.next_frame().unwrap(), None);
+        assert!(decoder.pending().is_empty());
+    }
+
+    #[test]
+    fn pipelined_frames() {
+        let first = resp_array_of_bulks!("PING");
+        let second = resp_array_of_bulks!("ECHO", "a\r\nb");
+        let mut bytes = first.serialize();
+        bytes.extend(second.serialize());
+        // the second frame is cut right in the middle of its CRLF
+        let (head, tail) = bytes.split_at(bytes.len() - 1);
+        let mut decoder = new_decoder();
+        decoder.extend(head).unwrap();
+        assert_eq!(decoder.next_frame().unwrap(), Some((first, 14)));
+        assert_eq!(decoder.next_frame().unwrap(), None);
+        decoder.extend(tail).unwrap();
+        assert_eq!(decoder.next_frame().unwrap().map(|(frame, _)| frame), Some(second));
+    }
+
+    #[test]
+    fn rejects_malformed_frames() {
+        let mut decoder = new_decoder();
+        decoder.extend(b"$3\r\nabcd\r\n").unwrap();
+        assert_eq!(decoder.next_frame(), Err(ParseError::InvalidInput));
+        let mut decoder = new_decoder();
+        decoder.extend(b"?\r\n").unwrap();
+        assert_eq!(decoder.next_frame(), Err(ParseError::UnkownSymbol));
+    }
+
+    #[test]
+    fn query_buffer_limit() {
+        let mut decoder = RespDecoder::new(16);
+        decoder.extend(b"*1\r\n$4\r\nPING\r\n").unwrap();
+        assert!(decoder.next_frame().unwrap().is_some());
+        // decoded frames don't count towards the limit
+        decoder.extend(b"*1\r\n$4\r\nPING").unwrap();
+        assert_eq!(decoder.extend(b"\r\n*1\r\n"), Err(ParseError::QueryBufferLimit));
+    }
+
+    #[test]
+    fn rejects_bulk_lengths_over_the_limit() {
+        let mut decoder = RespDecoder::new(1024);
+        decoder.extend(b"*2\r\n$4\r\nECHO\r\n$2000\r\nabc").unwrap();
+        assert_eq!(decoder.next_frame(), Err(ParseError::QueryBufferLimit));
+        let mut decoder = new_decoder();
+        decoder.extend(format!("${}\r\n", usize::MAX).as_bytes()).unwrap();
+        assert_eq!(decoder.next_frame(), Err(ParseError::InvalidInput));
+    }
+
+    #[test]
+    fn waits_for_the_whole_bulk() {
+        let mut decoder = new_decoder();
+        decoder.extend(b"*2\r\n$4\r\nECHO\r\n$10\r\nabc").unwrap();
+        assert_eq!(decoder.next_frame().unwrap(), None);
+        assert_eq!(decoder.needed, 31);
+        decoder.extend(b"defghij\r").unwrap();
+        assert_eq!(decoder.next_frame().unwrap(), None);
+        decoder.extend(b"\n").unwrap();
+        let (frame, len) = decoder.next_frame().unwrap().unwrap();
+        assert_eq!((frame, len), (resp_array_of_bulks!("ECHO", "abcdefghij"), 31));
+        assert_eq!(decoder.needed, 0);
+    }
+
+    #[test]
+    fn rdb_then_commands() {
+        let mut decoder = new_decoder();
+        decoder.extend(b"$5\r\nREDIS*1\r\n$4\r").unwrap();
+        assert_eq!(decoder.next_rdb().unwrap(), Some(b"REDIS".to_vec()));
+        assert_eq!(decoder.next_frame().unwrap(), None);
+        decoder.extend(b"\nPING\r\n").unwrap();
+        assert_eq!(decoder.next_frame().unwrap(), Some((resp_array_of_bulks!("PING"), 14)));
+
+        let mut decoder = new_decoder();
+        decoder.extend(b"$10\r\nREDIS").unwrap();
+        assert_eq!(decoder.next_rdb().unwrap(), None);
+        decoder.extend(b"0011").unwrap();
+        assert_eq!(decoder.next_rdb().unwrap(), None);
+        decoder.extend(b"\xff").unwrap();
+        assert_eq!(decoder.next_rdb().unwrap(), Some(b"REDIS0011\xff".to_vec()));
+    }
+}
@@ -0,0 +1,61 @@
+use crate::constants::{ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE, ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP};
+use crate::keyspace_events::publish_keyspace_events;
+use crate::pubsub::AMPubSub;
+use crate::redis::{self, queue_expired, AMConfig, AMSlaves, Databases, RedisDB, TxLock};
+
+use tokio::time::{self, Duration, Instant};
+
+/// Deletes expired keys in the background `hz` times per second, like redis' active expiry.
+/// Each cycle samples keys with a TTL and keeps going while more than
+/// `ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE` percent of the sampled keys were expired, but never
+/// for longer than `active-expire-cpu-percent` of the cycle period, shared by all the databases.
+pub async fn active_expire_cycle(
+    databases: Databases,
+    slaves: AMSlaves,
+    config: AMConfig,
+    pubsub: AMPubSub,
+    tx_lock: TxLock,
+) {
+    loop {
+        let (hz, cpu_percent) = {
+            let config_guard = config.lock().await;
+            (config_guard.hz(), config_guard.active_expire_cpu_percent())
+        };
+        let period = Duration::from_micros(1_000_000 / hz);
+        time::sleep(period).await;
+        let deadline = Instant::now() + period * cpu_percent as u32 / 100;
+        let mut propagated = false;
+        // keys don't expire in the middle of a transaction
+        let tx_guard = tx_lock.read().await;
+        // the DELs are queued before any write can touch the keys again
+        let mut slaves_guard = slaves.lock().await;
+        for (index, db) in databases.iter().enumerate() {
+            let mut dict_guard = db.dict.lock().await;
+            expire_cycle(&mut dict_guard, deadline);
+            propagated |= queue_expired(&mut slaves_guard, index, &mut dict_guard);
+        }
+        drop(slaves_guard);
+        drop(tx_guard);
+        publish_keyspace_events(&databases, &pubsub, &config).await;
+        if propagated {
+            redis::apply_all_pending_updates(slaves.clone()).await;
+        }
+    }
+}
+
+// runs a single cycle, the keys it expires are left in the database's expired keys along with the
+// ones commands expired lazily
+fn expire_cycle(dict_guard: &mut RedisDB, deadline: Instant) {
+    let start = Instant::now();
+    loop {
+        let (checked, expired) = dict_guard.expire_sample(ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP);
+        if checked == 0 || expired * 100 <= checked * ACTIVE_EXPIRE_CYCLE_ACCEPTABLE_STALE {
+            break;
+        }
+        if Instant::now() > deadline {
+            dict_guard.stats.expired_time_cap_reached_count += 1;
+            break;
+        }
+    }
+    dict_guard.stats.expire_cycle_cpu_micros += start.elapsed().as_micros() as u64;
+}
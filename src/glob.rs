@@ -0,0 +1,148 @@
+// Glob-style matching used by KEYS and the MATCH option of the *SCAN commands, following the
+// semantics of redis' `stringmatchlen`:
+// - `*` matches any sequence of bytes, `?` matches a single byte
+// - `[abc]`, `[a-z]` and `[^a]` match a byte in or out of a class, reversed ranges are allowed
+// - `\x` matches `x` literally, both inside and outside classes
+
+/// Matches a single byte against the pattern token at `p`, returns where the next token starts
+/// if it matched.
+fn match_token(pattern: &[u8], p: usize, byte: u8) -> Option<usize> {
+    match pattern[p] {
+        b'?' => Some(p + 1),
+        b'\\' if p + 1 < pattern.len() => (pattern[p + 1] == byte).then_some(p + 2),
+        b'[' => {
+            let mut i = p + 1;
+            let negate = pattern.get(i) == Some(&b'^');
+            if negate {
+                i += 1;
+            }
+            let mut matched = false;
+            // an unterminated class ends with the pattern
+            while i < pattern.len() {
+                match pattern[i] {
+                    b']' => {
+                        i += 1;
+                        break;
+                    }
+                    b'\\' if i + 1 < pattern.len() => {
+                        matched |= pattern[i + 1] == byte;
+                        i += 2;
+                    }
+                    start if i + 2 < pattern.len() && pattern[i + 1] == b'-' => {
+                        let end = pattern[i + 2];
+                        let (low, high) = if start <= end { (start, end) } else { (end, start) };
+                        matched |= low <= byte && byte <= high;
+                        i += 3;
+                    }
+                    literal => {
+                        matched |= literal == byte;
+                        i += 1;
+                    }
+                }
+            }
+            (matched != negate).then_some(i)
+        }
+        literal => (literal == byte).then_some(p + 1),
+    }
+}
+
+/// Returns true if the whole `string` matches the glob `pattern`.
+pub fn glob_match(pattern: &[u8], string: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // where to resume after the last `*` if the current attempt fails: the pattern right after
+    // the star and the string position the star swallows up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while s < string.len() {
+        if p < pattern.len() {
+            if pattern[p] == b'*' {
+                while p < pattern.len() && pattern[p] == b'*' {
+                    p += 1;
+                }
+                if p == pattern.len() {
+                    return true;
+                }
+                backtrack = Some((p, s));
+                continue;
+            }
+            if let Some(next) = match_token(pattern, p, string[s]) {
+                p = next;
+                s += 1;
+                continue;
+            }
+        }
+        match backtrack {
+            Some((star_p, star_s)) => {
+                p = star_p;
+                s = star_s + 1;
+                backtrack = Some((star_p, s));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|&byte| byte == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, string: &str) -> bool {
+        glob_match(pattern.as_bytes(), string.as_bytes())
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*", ""));
+        assert!(matches("h?llo", "hello"));
+        assert!(!matches("h?llo", "hllo"));
+        assert!(matches("h*llo", "hllo"));
+        assert!(matches("**a**", "bab"));
+        assert!(!matches("a*", "ba"));
+    }
+
+    #[test]
+    fn star_backtracking() {
+        assert!(matches("*ab", "aaab"));
+        assert!(matches("a*b*c", "abxbyc"));
+        assert!(matches("*a?c", "abcabc"));
+        assert!(!matches("*ab", "abba"));
+        assert!(!matches("a*b*c", "abxbycd"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("h[ae]llo", "hallo"));
+        assert!(!matches("h[ae]llo", "hillo"));
+        assert!(matches("h[a-c]llo", "hbllo"));
+        // reversed ranges are allowed
+        assert!(matches("h[c-a]llo", "hbllo"));
+        assert!(matches("h[^e]llo", "hallo"));
+        assert!(!matches("h[^e]llo", "hello"));
+        assert!(!matches("h[^a-z]llo", "hello"));
+        // like redis, a `]` right after `-` ends the range rather than the class
+        assert!(matches("[a-]", "_"));
+        assert!(!matches("[a-]", "-"));
+    }
+
+    #[test]
+    fn unterminated_class() {
+        // the class ends with the pattern
+        assert!(matches("h[ae", "ha"));
+        assert!(!matches("h[ae", "hi"));
+        assert!(!matches("h[ae", "hae"));
+        assert!(matches("h[^", "hx"));
+        assert!(!matches("h[^", "h"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(matches("h\\*llo", "h*llo"));
+        assert!(!matches("h\\*llo", "hello"));
+        assert!(matches("\\?", "?"));
+        assert!(!matches("\\?", "a"));
+        assert!(matches("[\\]]", "]"));
+        assert!(matches("[\\^a]", "^"));
+        // a trailing backslash is taken literally
+        assert!(matches("a\\", "a\\"));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::scan::ScanIndex;
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct HashEntry {
+    fields: HashMap<String, String>,
+    // the fields in HSCAN order
+    scan_index: ScanIndex<String>,
+}
+
+impl HashEntry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, String, String> {
+        self.fields.iter()
+    }
+
+    /// The next cursor and about `count` fields from `cursor` on, see `ScanIndex`.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&String, &String)>) {
+        let (cursor, fields) = self.scan_index.scan(cursor, count);
+        let pairs = fields
+            .into_iter()
+            .filter_map(|field| self.fields.get_key_value(field))
+            .collect();
+        (cursor, pairs)
+    }
+
+    pub fn get(&self, field: &str) -> Option<&String> {
+        self.fields.get(field)
+    }
+
+    pub fn contains(&self, field: &str) -> bool {
+        self.fields.contains_key(field)
+    }
+
+    /// Returns true if `field` is a new field in the hash.
+    pub fn set(&mut self, field: String, value: String) -> bool {
+        match self.fields.entry(field) {
+            Entry::Occupied(mut entry) => {
+                entry.insert(value);
+                false
+            }
+            Entry::Vacant(entry) => {
+                self.scan_index.insert(entry.key());
+                entry.insert(value);
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, field: &str) -> bool {
+        match self.fields.remove_entry(field) {
+            Some((field, _value)) => {
+                self.scan_index.remove(&field);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl FromIterator<(String, String)> for HashEntry {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        let mut hash = Self::new();
+        for (field, value) in iter {
+            hash.set(field, value);
+        }
+        hash
+    }
+}
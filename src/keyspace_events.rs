@@ -0,0 +1,184 @@
+use crate::data_entry::ValueType;
+use crate::redis::{AMConfig, Databases};
+use crate::pubsub::AMPubSub;
+
+/// Classes of keyspace events, each one enabled by its letter in `notify-keyspace-events`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventClass {
+    Generic,
+    String,
+    List,
+    Set,
+    Hash,
+    ZSet,
+    Expired,
+    // there is no maxmemory eviction, so nothing raises it yet
+    Evicted,
+    Stream,
+    // read misses aren't reported either
+    KeyMiss,
+    New,
+}
+
+impl EventClass {
+    const ALL: [EventClass; 11] = [
+        Self::Generic,
+        Self::String,
+        Self::List,
+        Self::Set,
+        Self::Hash,
+        Self::ZSet,
+        Self::Expired,
+        Self::Evicted,
+        Self::Stream,
+        Self::KeyMiss,
+        Self::New,
+    ];
+
+    fn flag(&self) -> char {
+        match self {
+            Self::Generic => 'g',
+            Self::String => '$',
+            Self::List => 'l',
+            Self::Set => 's',
+            Self::Hash => 'h',
+            Self::ZSet => 'z',
+            Self::Expired => 'x',
+            Self::Evicted => 'e',
+            Self::Stream => 't',
+            Self::KeyMiss => 'm',
+            Self::New => 'n',
+        }
+    }
+
+    fn bit(&self) -> u16 {
+        1 << (*self as u16)
+    }
+}
+
+/// Parsed `notify-keyspace-events`, `K` publishes to `__keyspace@<db>__:<key>` channels and `E`
+/// to `__keyevent@<db>__:<event>` ones, for the classes given by the other letters. `A` is an
+/// alias for every class but `m` and `n`, like in redis.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct NotifyFlags {
+    pub keyspace: bool,
+    pub keyevent: bool,
+    classes: u16,
+}
+
+impl NotifyFlags {
+    pub fn parse(flags: &str) -> Option<Self> {
+        let mut parsed = Self::default();
+        for flag in flags.chars() {
+            match flag {
+                'K' => parsed.keyspace = true,
+                'E' => parsed.keyevent = true,
+                'A' => {
+                    for class in EventClass::ALL {
+                        if !matches!(class, EventClass::KeyMiss | EventClass::New) {
+                            parsed.classes |= class.bit();
+                        }
+                    }
+                }
+                flag => parsed.classes |= EventClass::ALL.iter().find(|class| class.flag() == flag)?.bit(),
+            }
+        }
+        Some(parsed)
+    }
+
+    pub fn enabled(&self, class: EventClass) -> bool {
+        (self.keyspace || self.keyevent) && self.classes & class.bit() != 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyspaceEvent {
+    pub class: EventClass,
+    pub event: &'static str,
+    pub key: ValueType,
+}
+
+/// Publishes the events the databases queued since the last call, it runs after every command
+/// and expiry cycle so subscribers see them in the order they happened.
+pub async fn publish_keyspace_events(databases: &Databases, pubsub: &AMPubSub, config: &AMConfig) {
+    let (flags, limit) = {
+        let config_guard = config.lock().await;
+        (config_guard.notify_keyspace_events(), config_guard.pubsub_output_buffer_limit())
+    };
+    for (index, db) in databases.iter().enumerate() {
+        let events = db.dict.lock().await.take_events();
+        let events = events
+            .into_iter()
+            .filter(|event| flags.enabled(event.class))
+            .collect::<Vec<_>>();
+        if events.is_empty() {
+            continue;
+        }
+        let mut pubsub_guard = pubsub.lock().await;
+        for event in events {
+            let key = event.key.as_string();
+            if flags.keyspace {
+                let channel = format!("__keyspace@{}__:{}", index, key);
+                pubsub_guard.publish(&channel, event.event, &limit);
+            }
+            if flags.keyevent {
+                let channel = format!("__keyevent@{}__:{}", index, event.event);
+                pubsub_guard.publish(&channel, &key, &limit);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_by_default() {
+        let flags = NotifyFlags::parse("").unwrap();
+        assert_eq!(flags, NotifyFlags::default());
+        assert!(EventClass::ALL.iter().all(|&class| !flags.enabled(class)));
+    }
+
+    #[test]
+    fn classes_need_keyspace_or_keyevent() {
+        let flags = NotifyFlags::parse("g$").unwrap();
+        assert!(!flags.enabled(EventClass::Generic));
+        let flags = NotifyFlags::parse("Kg$").unwrap();
+        assert!(flags.keyspace && !flags.keyevent);
+        assert!(flags.enabled(EventClass::Generic));
+        assert!(flags.enabled(EventClass::String));
+        assert!(!flags.enabled(EventClass::List));
+        let flags = NotifyFlags::parse("Ex").unwrap();
+        assert!(!flags.keyspace && flags.keyevent);
+        assert!(flags.enabled(EventClass::Expired));
+    }
+
+    #[test]
+    fn all_alias_excludes_key_miss_and_new() {
+        let flags = NotifyFlags::parse("KEA").unwrap();
+        for class in EventClass::ALL {
+            let expected = !matches!(class, EventClass::KeyMiss | EventClass::New);
+            assert_eq!(flags.enabled(class), expected, "{:?}", class);
+        }
+        let flags = NotifyFlags::parse("KAmn").unwrap();
+        assert!(EventClass::ALL.iter().all(|&class| flags.enabled(class)));
+    }
+
+    #[test]
+    fn every_class_has_its_letter() {
+        for class in EventClass::ALL {
+            let flags = NotifyFlags::parse(&format!("K{}", class.flag())).unwrap();
+            for other in EventClass::ALL {
+                assert_eq!(flags.enabled(other), other == class, "{:?} {:?}", class, other);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_letters() {
+        for bad in ["KEq", "k", "KE ", "a", "y"] {
+            assert_eq!(NotifyFlags::parse(bad), None, "{:?}", bad);
+        }
+    }
+}
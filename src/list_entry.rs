@@ -0,0 +1,161 @@
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+impl ListEnd {
+    pub fn parse(end: &str) -> Option<Self> {
+        match end.to_lowercase().as_str() {
+            "left" => Some(Self::Left),
+            "right" => Some(Self::Right),
+            _ => None,
+        }
+    }
+
+    /// Keyspace event of pushing at this end.
+    pub fn push_event(&self) -> &'static str {
+        match self {
+            Self::Left => "lpush",
+            Self::Right => "rpush",
+        }
+    }
+
+    /// Keyspace event of popping from this end.
+    pub fn pop_event(&self) -> &'static str {
+        match self {
+            Self::Left => "lpop",
+            Self::Right => "rpop",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ListEntry {
+    items: VecDeque<String>,
+}
+
+impl ListEntry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &String> {
+        self.items.iter()
+    }
+
+    pub fn push(&mut self, end: ListEnd, value: String) -> usize {
+        match end {
+            ListEnd::Left => self.items.push_front(value),
+            ListEnd::Right => self.items.push_back(value),
+        };
+        self.items.len()
+    }
+
+    pub fn pop(&mut self, end: ListEnd) -> Option<String> {
+        match end {
+            ListEnd::Left => self.items.pop_front(),
+            ListEnd::Right => self.items.pop_back(),
+        }
+    }
+
+    // converts a redis index (negative means counting from the tail) into a real position
+    fn position(&self, index: i64) -> Option<usize> {
+        let len = self.items.len() as i64;
+        let index = if index < 0 { len + index } else { index };
+        if index < 0 || index >= len {
+            None
+        } else {
+            Some(index as usize)
+        }
+    }
+
+    // clamps [start, stop] the same way LRANGE and LTRIM do, `None` means an empty range
+    fn clamp_range(&self, start: i64, stop: i64) -> Option<(usize, usize)> {
+        let len = self.items.len() as i64;
+        let start = if start < 0 { (len + start).max(0) } else { start };
+        let stop = if stop < 0 { len + stop } else { stop.min(len - 1) };
+        if start > stop || start >= len {
+            None
+        } else {
+            Some((start as usize, stop as usize))
+        }
+    }
+
+    pub fn range(&self, start: i64, stop: i64) -> Vec<String> {
+        match self.clamp_range(start, stop) {
+            Some((start, stop)) => self.items.range(start..=stop).cloned().collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn index(&self, index: i64) -> Option<&String> {
+        self.position(index).and_then(|pos| self.items.get(pos))
+    }
+
+    pub fn set(&mut self, index: i64, value: String) -> bool {
+        match self.position(index) {
+            Some(pos) => {
+                self.items[pos] = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// count > 0 removes from head to tail, count < 0 from tail to head and count = 0 removes
+    /// every element equal to `value`.
+    pub fn remove(&mut self, count: i64, value: &str) -> usize {
+        let limit = if count == 0 { usize::MAX } else { count.unsigned_abs() as usize };
+        let mut removed = 0;
+        if count >= 0 {
+            let mut i = 0;
+            while i < self.items.len() && removed < limit {
+                if self.items[i] == value {
+                    self.items.remove(i);
+                    removed += 1;
+                } else {
+                    i += 1;
+                }
+            }
+        } else {
+            let mut i = self.items.len();
+            while i > 0 && removed < limit {
+                i -= 1;
+                if self.items[i] == value {
+                    self.items.remove(i);
+                    removed += 1;
+                }
+            }
+        }
+        removed
+    }
+
+    pub fn trim(&mut self, start: i64, stop: i64) {
+        match self.clamp_range(start, stop) {
+            Some((start, stop)) => {
+                self.items.truncate(stop + 1);
+                self.items.drain(..start);
+            }
+            None => self.items.clear(),
+        }
+    }
+}
+
+impl FromIterator<String> for ListEntry {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
+        Self {
+            items: iter.into_iter().collect(),
+        }
+    }
+}
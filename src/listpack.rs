@@ -0,0 +1,262 @@
+use std::fmt;
+
+// total bytes (u32) and number of elements (u16), both little endian
+const HEADER_SIZE: usize = 6;
+const EOF: u8 = 0xFF;
+// past that many elements the header stops counting them
+const UNKNOWN_LEN: u16 = u16::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LpValue<'a> {
+    Int(i64),
+    Str(&'a [u8]),
+}
+
+impl<'a> LpValue<'a> {
+    /// Strings that are printed back exactly the same as an integer are stored as one, like
+    /// redis does, e.g `007` stays a string.
+    pub fn from_string(value: &'a str) -> Self {
+        match value.parse::<i64>() {
+            Ok(int) if int.to_string() == value => Self::Int(int),
+            _ => Self::Str(value.as_bytes()),
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(int) => Some(*int),
+            Self::Str(bytes) => std::str::from_utf8(bytes).ok()?.parse().ok(),
+        }
+    }
+}
+
+impl fmt::Display for LpValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::Int(int) => write!(f, "{}", int),
+            Self::Str(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+        }
+    }
+}
+
+/// A sequence of strings and integers packed in a single buffer, in the same format as redis
+/// listpacks. Every element is its encoding, its data and then its own length (backlen), so the
+/// buffer can be walked in both directions without any pointers.
+#[derive(Clone, PartialEq)]
+pub struct Listpack {
+    buf: Vec<u8>,
+}
+
+impl fmt::Debug for Listpack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_list().entries(self.iter().map(|value| value.to_string())).finish()
+    }
+}
+
+impl Default for Listpack {
+    fn default() -> Self {
+        let mut listpack = Self { buf: vec![0; HEADER_SIZE] };
+        listpack.buf.push(EOF);
+        listpack.set_header(0);
+        listpack
+    }
+}
+
+fn backlen_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+fn encode_backlen(len: usize, out: &mut Vec<u8>) {
+    let bytes = backlen_size(len);
+    // the most significant bits first, every byte but the first one is flagged with 128
+    out.push((len >> (7 * (bytes - 1))) as u8);
+    for i in (0..bytes - 1).rev() {
+        out.push(((len >> (7 * i)) & 127) as u8 | 128);
+    }
+}
+
+fn encode(value: LpValue, out: &mut Vec<u8>) {
+    let start = out.len();
+    match value {
+        LpValue::Int(int @ 0..=127) => out.push(int as u8),
+        LpValue::Int(int @ -4096..=4095) => {
+            let int = (int as u16) & 0x1FFF;
+            out.extend([0xC0 | (int >> 8) as u8, int as u8]);
+        }
+        LpValue::Int(int) if i16::try_from(int).is_ok() => {
+            out.push(0xF1);
+            out.extend((int as i16).to_le_bytes());
+        }
+        LpValue::Int(int @ -8388608..=8388607) => {
+            out.push(0xF2);
+            out.extend(&(int as i32).to_le_bytes()[..3]);
+        }
+        LpValue::Int(int) if i32::try_from(int).is_ok() => {
+            out.push(0xF3);
+            out.extend((int as i32).to_le_bytes());
+        }
+        LpValue::Int(int) => {
+            out.push(0xF4);
+            out.extend(int.to_le_bytes());
+        }
+        LpValue::Str(bytes) => {
+            match bytes.len() {
+                len @ 0..=63 => out.push(0x80 | len as u8),
+                len @ 64..=4095 => out.extend([0xE0 | (len >> 8) as u8, len as u8]),
+                len => {
+                    out.push(0xF0);
+                    out.extend((len as u32).to_le_bytes());
+                }
+            }
+            out.extend(bytes);
+        }
+    }
+    let len = out.len() - start;
+    encode_backlen(len, out);
+}
+
+impl Listpack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes a listpack as stored in an RDB file, `None` if it's malformed.
+    pub fn from_bytes(buf: Vec<u8>) -> Option<Self> {
+        let total_bytes = u32::from_le_bytes(buf.get(..4)?.try_into().ok()?) as usize;
+        if total_bytes != buf.len() || buf.len() <= HEADER_SIZE || buf.last() != Some(&EOF) {
+            return None;
+        }
+        let listpack = Self { buf };
+        // every element has to be readable up to the end
+        let mut pos = HEADER_SIZE;
+        while listpack.buf[pos] != EOF {
+            pos = listpack.get(pos)?.1;
+        }
+        (pos == listpack.buf.len() - 1).then_some(listpack)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Size of the whole listpack in bytes.
+    pub fn bytes(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn len(&self) -> usize {
+        match u16::from_le_bytes([self.buf[4], self.buf[5]]) {
+            UNKNOWN_LEN => self.iter().count(),
+            len => len as usize,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf[HEADER_SIZE] == EOF
+    }
+
+    fn set_header(&mut self, len: usize) {
+        let total_bytes = self.buf.len() as u32;
+        self.buf[..4].copy_from_slice(&total_bytes.to_le_bytes());
+        let len = len.min(UNKNOWN_LEN as usize) as u16;
+        self.buf[4..HEADER_SIZE].copy_from_slice(&len.to_le_bytes());
+    }
+
+    /// Position of the first element.
+    pub fn first(&self) -> usize {
+        HEADER_SIZE
+    }
+
+    /// The element at `pos` along with the position of the next one, `None` at the end.
+    pub fn get(&self, pos: usize) -> Option<(LpValue<'_>, usize)> {
+        let buf = &self.buf;
+        let byte = *buf.get(pos)?;
+        let int = |len: usize| -> Option<i64> {
+            let mut bytes = [0; 8];
+            bytes[..len].copy_from_slice(buf.get(pos + 1..pos + 1 + len)?);
+            // sign extends the narrower integers
+            let shift = 64 - 8 * len as u32;
+            Some(i64::from_le_bytes(bytes).wrapping_shl(shift).wrapping_shr(shift))
+        };
+        let (value, len) = match byte {
+            0x00..=0x7F => (LpValue::Int(byte as i64), 1),
+            0x80..=0xBF => {
+                let len = (byte & 0x3F) as usize;
+                (LpValue::Str(buf.get(pos + 1..pos + 1 + len)?), 1 + len)
+            }
+            0xC0..=0xDF => {
+                let int = ((byte as i64 & 0x1F) << 8) | *buf.get(pos + 1)? as i64;
+                (LpValue::Int(if int >= 1 << 12 { int - (1 << 13) } else { int }), 2)
+            }
+            0xE0..=0xEF => {
+                let len = ((byte as usize & 0x0F) << 8) | *buf.get(pos + 1)? as usize;
+                (LpValue::Str(buf.get(pos + 2..pos + 2 + len)?), 2 + len)
+            }
+            0xF0 => {
+                let len = u32::from_le_bytes(buf.get(pos + 1..pos + 5)?.try_into().ok()?) as usize;
+                (LpValue::Str(buf.get(pos + 5..pos + 5 + len)?), 5 + len)
+            }
+            0xF1 => (LpValue::Int(int(2)?), 3),
+            0xF2 => (LpValue::Int(int(3)?), 4),
+            0xF3 => (LpValue::Int(int(4)?), 5),
+            0xF4 => (LpValue::Int(int(8)?), 9),
+            _ => return None,
+        };
+        let next = pos + len + backlen_size(len);
+        (next < buf.len()).then_some((value, next))
+    }
+
+    /// Position of the element after the one at `pos`.
+    pub fn next(&self, pos: usize) -> Option<usize> {
+        self.get(pos).map(|(_, next)| next)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = LpValue<'_>> {
+        self.iter_from(self.first())
+    }
+
+    /// The elements from the one at `pos` onwards.
+    pub fn iter_from(&self, mut pos: usize) -> impl Iterator<Item = LpValue<'_>> {
+        std::iter::from_fn(move || {
+            let (value, next) = self.get(pos)?;
+            pos = next;
+            Some(value)
+        })
+    }
+
+    pub fn push(&mut self, value: LpValue) {
+        let mut element = Vec::new();
+        encode(value, &mut element);
+        let len = self.len();
+        let end = self.buf.len() - 1;
+        self.buf.splice(end..end, element);
+        self.set_header(len + 1);
+    }
+
+    pub fn push_int(&mut self, int: i64) {
+        self.push(LpValue::Int(int));
+    }
+
+    pub fn push_str(&mut self, value: &str) {
+        self.push(LpValue::from_string(value));
+    }
+
+    /// Replaces the element at `pos`, the elements after it move if the sizes differ.
+    pub fn replace(&mut self, pos: usize, value: LpValue) {
+        let next = match self.next(pos) {
+            Some(next) => next,
+            None => return,
+        };
+        let mut element = Vec::new();
+        encode(value, &mut element);
+        let len = self.len();
+        self.buf.splice(pos..next, element);
+        self.set_header(len);
+    }
+}
@@ -1,21 +1,42 @@
 #![allow(warnings, unused)]
 
+mod blocked_clients;
+mod client;
+mod clock;
 mod cmd;
 mod config;
+mod consumer_group;
 mod constants;
 mod data_entry;
+mod decoder;
+mod expire_cycle;
+mod glob;
+mod hash_entry;
+mod keyspace_events;
+mod list_entry;
+mod listpack;
 mod parser;
+mod pubsub;
 mod rdb;
 mod redis;
+mod redis_db;
 mod resp;
+mod scan;
+mod set_entry;
 mod slave_meta;
 mod stream_entry;
 mod utils;
+mod zset_entry;
 
 use crate::{
-    cmd::{cmd_builder::CmdBuilder, Cmd, CmdType},
+    client::{AMClient, ClientState},
+    cmd::{cmd_builder::CmdBuilder, pubsub::unsubscribe_all, tx::unwatch_all, Cmd, CmdType},
     config::{Config, Role},
-    parser::Parser,
+    constants::READ_CHUNK_SIZE,
+    decoder::RespDecoder,
+    expire_cycle::active_expire_cycle,
+    keyspace_events::publish_keyspace_events,
+    pubsub::{AMPubSub, Mailbox},
     rdb::RDBParser,
     redis::*,
     resp::RespType,
@@ -40,64 +61,145 @@ use tokio::{
 async fn handle_client(
     stream: TcpStream,
     socket_addr: SocketAddr,
-    redis: AMRedisDB,
+    databases: Databases,
     config: AMConfig,
     slaves: AMSlaves,
-    streams: AMStreams,
-    stream_senders: AMStreamSenders,
+    pubsub: AMPubSub,
+    tx_lock: TxLock,
 ) -> anyhow::Result<()> {
     println!("[+] Got Connection: {:?}", socket_addr);
+    let (subscriber, mailbox) = pubsub::channel();
+    let client = Arc::new(Mutex::new(ClientState::new(subscriber)));
+    let result = serve_client(
+        stream,
+        socket_addr,
+        &databases,
+        &client,
+        mailbox,
+        config,
+        slaves,
+        pubsub.clone(),
+        tx_lock,
+    )
+    .await;
+    // the keys it watched and the channels it subscribed to shouldn't keep a reference to a gone
+    // client
+    unwatch_all(&client, &databases).await;
+    unsubscribe_all(&client, &pubsub).await;
+    result
+}
+
+async fn serve_client(
+    stream: TcpStream,
+    socket_addr: SocketAddr,
+    databases: &Databases,
+    client: &AMClient,
+    mut mailbox: Mailbox,
+    config: AMConfig,
+    slaves: AMSlaves,
+    pubsub: AMPubSub,
+    tx_lock: TxLock,
+) -> anyhow::Result<()> {
     let (rx, wr) = stream.into_split();
     let wr = Arc::new(Mutex::new(wr));
+    let mut decoder = RespDecoder::new(config.lock().await.client_query_buffer_limit());
     loop {
-        if let Ok(_) = rx.readable().await {
-            let wr = Arc::clone(&wr);
-            let mut buffer = vec![0; 1024];
-            let n = match rx.try_read(&mut buffer) {
-                Ok(0) => break Ok(()),
-                Ok(n) => n,
-                Err(ref e) if e.kind() == tokio::io::ErrorKind::WouldBlock => continue,
-                Err(e) => return Err(e.into()),
-            };
-            println!("[+] Got {:?}", String::from_utf8_lossy(&buffer[..n]));
-            let mut input = &buffer[..n];
-            loop {
-                let (parsed, rem) = Parser::parse_resp(input)?;
-                let mut cmd = CmdBuilder::from_resp(
-                    parsed,
-                    redis.clone(),
-                    config.clone(),
-                    slaves.clone(),
-                    streams.clone(),
-                    stream_senders.clone(),
-                    Some(socket_addr),
-                    Some(wr.clone())
-                    );
-                let resp = cmd.run().await;
-                if wr.lock().await.writable().await.is_ok() {
-                    match wr.lock().await.try_write(&resp.serialize()) {
-                        Ok(_n) => (),
-                        Err(ref e) if e.kind() == tokio::io::ErrorKind::WouldBlock => continue,
-                        Err(e) => return Err(e.into()),
-                    }
+        tokio::select! {
+            Some(message) = mailbox.messages.recv() => {
+                // a subscriber that doesn't read its messages gets evicted even while this waits
+                tokio::select! {
+                    written = async { wr.lock().await.write_all(&message).await } => written?,
+                    _ = mailbox.eviction.evicted() => break Ok(()),
                 }
-                if rem.is_empty() {
-                    break;
+                mailbox.eviction.written(&message);
+            }
+            _ = mailbox.eviction.evicted() => {
+                println!("[-] Disconnecting {:?}, over its pub/sub output buffer limit", socket_addr);
+                break Ok(());
+            }
+            Ok(_) = rx.readable() => {
+                let mut buffer = vec![0; READ_CHUNK_SIZE];
+                let n = match rx.try_read(&mut buffer) {
+                    Ok(0) => break Ok(()),
+                    Ok(n) => n,
+                    Err(ref e) if e.kind() == tokio::io::ErrorKind::WouldBlock => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                if decoder.extend(&buffer[..n]).is_err() {
+                    println!("[-] Disconnecting {:?}, over its query buffer limit", socket_addr);
+                    break Ok(());
+                }
+                // a command split across reads waits in the decoder for the rest of it
+                loop {
+                    let parsed = match decoder.next_frame() {
+                        Ok(Some((parsed, _len))) => parsed,
+                        Ok(None) => break,
+                        Err(err) => {
+                            let reply = RespType::SimpleError(format!("Protocol error: {}", err));
+                            wr.lock().await.write_all(&reply.serialize()).await?;
+                            return Err(err.into());
+                        }
+                    };
+                    let mut cmd = CmdBuilder::from_resp(
+                        parsed,
+                        databases.clone(),
+                        client.clone(),
+                        config.clone(),
+                        slaves.clone(),
+                        pubsub.clone(),
+                        Some(socket_addr),
+                        Some(wr.clone()),
+                        tx_lock.clone(),
+                        ).await;
+                    let resp = run_cmd(cmd.as_mut(), &databases, &config, &pubsub, &tx_lock).await;
+                    wr.lock().await.write_all(&resp.serialize()).await?;
                 }
-                input = rem;
-            };
-            redis::apply_all_pending_updates(slaves.clone()).await;
+                redis::apply_all_pending_updates(slaves.clone()).await;
+            }
+        }
+    }
+}
+
+// runs a command so it never observes a transaction half way and publishes the keyspace events it
+// caused
+async fn run_cmd(
+    cmd: &mut (dyn Cmd + Send),
+    databases: &Databases,
+    config: &AMConfig,
+    pubsub: &AMPubSub,
+    tx_lock: &TxLock,
+) -> RespType {
+    let resp = match cmd.cmd_type().manages_tx_lock() {
+        true => cmd.run().await,
+        false => {
+            let _tx_guard = tx_lock.read().await;
+            cmd.run().await
+        }
+    };
+    let _tx_guard = tx_lock.read().await;
+    publish_keyspace_events(databases, pubsub, config).await;
+    resp
+}
+
+// waits until the master sent a whole frame, along with how many bytes it took
+async fn read_master_frame(
+    stream: &Arc<Mutex<TcpStream>>,
+    decoder: &mut RespDecoder,
+) -> anyhow::Result<(RespType, usize)> {
+    loop {
+        if let Some(frame) = decoder.next_frame()? {
+            return Ok(frame);
+        }
+        if decoder.read_from(&*stream.lock().await).await? == 0 {
+            bail!("master closed the replication link");
         }
     }
 }
 
 async fn setup_replica(
-    mut redis: &mut AMRedisDB,
-    mut config: &mut AMConfig,
-    slaves: &mut AMSlaves,
-    streams: &mut AMStreams,
-    stream_senders: &mut AMStreamSenders,
-) -> anyhow::Result<Arc<Mutex<TcpStream>>> {
+    databases: &Databases,
+    config: &mut AMConfig,
+) -> anyhow::Result<(Arc<Mutex<TcpStream>>, RespDecoder)> {
     let mut cfg_guard = config.lock().await;
     let stream = match cfg_guard.replica_of.role {
         Role::Slave {
@@ -116,29 +218,16 @@ async fn setup_replica(
         _ => anyhow::bail!("not replica then it can't send handshake"),
     };
     let service_port = cfg_guard.service_port;
+    let mut decoder = RespDecoder::new(cfg_guard.client_query_buffer_limit());
     drop(cfg_guard);
 
-    let read_response = |stream: Arc<Mutex<TcpStream>>| async move {
-        let stream = stream.lock().await;
-        let mut buffer = vec![0; 1024];
-        let n = loop {
-            stream.readable().await?;
-            match stream.try_read(&mut buffer) {
-                Ok(n) => break n,
-                Err(ref e) if e.kind() == tokio::io::ErrorKind::WouldBlock => continue,
-                Err(e) => return Err::<Vec<u8>, std::io::Error>(e.into()),
-            };
-        };
-        Ok(buffer[..n].to_vec())
-    };
     // TODO: if failed, should i quit the handshake ?
-    let validate_response = |actual: Vec<u8>, expexted: RespType| {
-        let (parsed, _rem) = Parser::parse_resp(&actual)?;
-        match parsed == expexted {
+    let validate_response = |actual: RespType, expexted: RespType| {
+        match actual == expexted {
             true => Ok(()),
             false => bail!(format!(
                 "slave replica received `{}` but expected response `{}`",
-                parsed, expexted
+                actual, expexted
             )),
         }
     };
@@ -149,7 +238,7 @@ async fn setup_replica(
         .write_all(&resp_array_of_bulks!("PING").serialize())
         .await
         .context("slave PING can't reach its master")?;
-    let actual = read_response(Arc::clone(&stream)).await?;
+    let (actual, _) = read_master_frame(&stream, &mut decoder).await?;
     let _ = validate_response(actual, RespType::SimpleString("PONG".to_string()));
 
     stream
@@ -158,7 +247,7 @@ async fn setup_replica(
         .write_all(&resp_array_of_bulks!("REPLCONF", "listening-port", service_port).serialize())
         .await
         .context("slave REPLCONF can't reach its master")?;
-    let actual = read_response(Arc::clone(&stream)).await?;
+    let (actual, _) = read_master_frame(&stream, &mut decoder).await?;
     let _ = validate_response(actual, RespType::SimpleString("OK".to_string()));
 
     stream
@@ -167,10 +256,9 @@ async fn setup_replica(
         .write_all(&resp_array_of_bulks!("REPLCONF", "capa", "eof", "capa", "psync2").serialize())
         .await
         .context("slave REPLCONF can't reach its master")?;
-    let actual = read_response(Arc::clone(&stream)).await?;
+    let (actual, _) = read_master_frame(&stream, &mut decoder).await?;
     let _ = validate_response(actual, RespType::SimpleString("OK".to_string()));
 
-    // TODO: add response validation for PSYNC cmd
     stream
         .lock()
         .await
@@ -178,123 +266,64 @@ async fn setup_replica(
         .await
         .context("slave PSYNC can't reach its master")?;
 
-    let input = read_response(Arc::clone(&stream)).await?;
-    println!("[+] PSYNC RESULT: {:?}", String::from_utf8_lossy(&input));
-
-    let (fullsync_resp, rem) = Parser::parse_until_crlf(&input)?;
-    println!(
-        "[+] FULLSYNC RESULT: {:?}",
-        String::from_utf8_lossy(&fullsync_resp)
-    );
-    let mut input = rem;
-
-    if input.is_empty() {
-        // TODO wait until reading rdb
+    let (fullsync_resp, _) = read_master_frame(&stream, &mut decoder).await?;
+    println!("[+] FULLSYNC RESULT: {:?}", fullsync_resp);
+    match fullsync_resp {
+        RespType::SimpleString(ref fullsync) if fullsync.starts_with("FULLRESYNC") => (),
+        _ => bail!("slave replica expected a FULLRESYNC but received `{}`", fullsync_resp),
     }
-    // TODO: it would be better if the parsers for RESP and RDB have similar API
-    // simple and better change, would be if both agree on mutably change `input`
-    let (rdb_header, redis_db) = RDBParser::from_rdb_resp(&mut input)?;
-    *redis = Arc::new(Mutex::new(redis_db));
 
-    let client_socket_addr = stream.lock().await.peer_addr()?;
-    while !input.is_empty() {
-        let input_len_before_parsing = input.len();
-        let (parsed, rem) = Parser::parse_resp(&input)?;
-        let mut cmd = CmdBuilder::from_resp(
-            parsed,
-            redis.clone(),
-            config.clone(),
-            slaves.clone(),
-            streams.clone(),
-            stream_senders.clone(),
-            None,
-            None,
-        );
-        let resp = cmd.run().await;
-        let replica_need_to_respond = matches!(cmd.cmd_type(), CmdType::GETACK);
-        redis::incr_master_repl_offset(
-            config.clone(),
-            (input_len_before_parsing - rem.len()) as u64,
-        )
-        .await;
-        if replica_need_to_respond {
-            if stream.lock().await.writable().await.is_ok() {
-                match stream.lock().await.try_write(&resp.serialize()) {
-                    Ok(_n) => (),
-                    Err(ref e) if e.kind() == tokio::io::ErrorKind::WouldBlock => continue,
-                    Err(e) => return Err(e.into()),
-                }
-            }
+    // the RDB file may take many reads, whatever the master sends after it is left to the
+    // master link
+    let rdb = loop {
+        if let Some(rdb) = decoder.next_rdb()? {
+            break rdb;
         }
-        input = rem;
-    }
+        if decoder.read_from(&*stream.lock().await).await? == 0 {
+            bail!("master closed the replication link during the full resync");
+        }
+    };
+    let (rdb_header, dbs) = RDBParser::from_rdb_file(&mut rdb.as_slice())?;
+    load_databases(databases, dbs).await;
 
     println!("[+] Replica Completed HandShake");
-    Ok(stream)
+    Ok((stream, decoder))
 }
 
+// applies the commands the master streams, one after the other in the order they were sent
 async fn replica_handle_master_connection(
     master_connection: Arc<Mutex<TcpStream>>,
-    mut redis: AMRedisDB,
+    mut decoder: RespDecoder,
+    databases: Databases,
+    master_client: AMClient,
     config: AMConfig,
     slaves: AMSlaves,
-    streams: AMStreams,
-    stream_senders: AMStreamSenders,
+    pubsub: AMPubSub,
+    tx_lock: TxLock,
 ) -> anyhow::Result<()> {
-    let client_socket_addr = master_connection.lock().await.peer_addr()?;
-    let master_connection_guard = master_connection.lock().await;
-
-    let mut buffer = vec![0; 1024];
-    let n = loop {
-        match master_connection_guard.try_read(&mut buffer) {
-            Ok(0) => return Ok(()),
-            Ok(n) => break n,
-            Err(ref e) if e.kind() == tokio::io::ErrorKind::WouldBlock => continue,
-            Err(e) => return Err(e.into()),
-        };
-    };
-    let master_sent_buffer = buffer[..n].to_vec();
-
-    let mut input = master_sent_buffer.as_slice();
-    println!(
-        "[+] Master Sent Buffer: {:?}",
-        String::from_utf8_lossy(&master_sent_buffer)
-    );
     loop {
-        let input_len_before_parsing = input.len();
-        let (parsed, rem) = Parser::parse_resp(input)?;
+        let (parsed, len) = read_master_frame(&master_connection, &mut decoder).await?;
         let mut cmd = CmdBuilder::from_resp(
             parsed,
-            redis.clone(),
+            databases.clone(),
+            master_client.clone(),
             config.clone(),
             slaves.clone(),
-            streams.clone(),
-            stream_senders.clone(),
+            pubsub.clone(),
             None,
             None,
-        );
-        let replica_need_to_respond = matches!(cmd.cmd_type(), CmdType::GETACK);
-        let resp = cmd.run().await;
-        redis::incr_master_repl_offset(
-            config.clone(),
-            (input_len_before_parsing - rem.len()) as u64,
+            tx_lock.clone(),
         )
         .await;
+        let replica_need_to_respond = matches!(cmd.cmd_type(), CmdType::GETACK);
+        let resp = redis::MASTER_LINK
+            .scope((), run_cmd(cmd.as_mut(), &databases, &config, &pubsub, &tx_lock))
+            .await;
+        redis::incr_master_repl_offset(config.clone(), len as u64).await;
         if replica_need_to_respond {
-            if master_connection_guard.writable().await.is_ok() {
-                match master_connection_guard.try_write(&resp.serialize()) {
-                    Ok(_n) => (),
-                    Err(ref e) if e.kind() == tokio::io::ErrorKind::WouldBlock => continue,
-                    Err(e) => return Err(e.into()),
-                }
-            }
-        }
-        if rem.is_empty() {
-            break;
+            master_connection.lock().await.write_all(&resp.serialize()).await?;
         }
-        input = rem;
     }
-    Ok(())
 }
 
 #[tokio::main]
@@ -307,84 +336,63 @@ async fn main() -> anyhow::Result<()> {
         .await
         .unwrap();
 
+    let databases = new_databases(cfg.databases());
     let db_filepath = cfg.get_db_filepath();
-    let mut redis = if db_filepath.exists() {
+    if db_filepath.exists() {
         let mut ibytes = vec![];
         let mut input = BufReader::new(File::open(db_filepath)?);
         let _read_bytes = input.read_to_end(&mut ibytes)?;
         let mut ibytes: &[u8] = &ibytes;
-        match RDBParser::from_rdb_file(&mut ibytes) {
-            Ok((rdb_header, redis_db)) => {
-                println!("{:#?}, {:#?}", rdb_header, redis_db);
-                Arc::new(Mutex::new(redis_db))
-            }
-            _ => Arc::new(Mutex::new(RedisDB::default())),
+        if let Ok((rdb_header, dbs)) = RDBParser::from_rdb_file(&mut ibytes) {
+            println!("{:#?}, {:#?}", rdb_header, dbs);
+            load_databases(&databases, dbs).await;
         }
-    } else {
-        Arc::new(Mutex::new(RedisDB::default()))
-    };
+    }
 
     let mut config = Arc::new(Mutex::new(cfg));
     let mut slaves = Arc::new(Mutex::new(HashMap::default()));
-    let mut streams = Arc::new(Mutex::new(HashMap::default()));
-    let mut stream_senders = Arc::new(Mutex::new(HashMap::default()));
+    let pubsub = AMPubSub::default();
+    let tx_lock = TxLock::default();
 
-    if !is_replica {
-        loop {
-            match listener.accept().await {
-                Ok((stream, socket_addr)) => {
-                    let redis = Arc::clone(&redis);
-                    let config = Arc::clone(&config);
-                    let slaves = Arc::clone(&slaves);
-                    let streams = Arc::clone(&streams);
-                    let stream_senders = Arc::clone(&stream_senders);
-                    tokio::spawn(async move {
-                        handle_client(
-                            stream,
-                            socket_addr,
-                            redis,
-                            config,
-                            slaves,
-                            streams,
-                            stream_senders,
-                        )
-                        .await
-                    });
-                }
-                Err(e) => {
-                    eprintln!("ERROR: {}", e);
-                }
-            };
+    if is_replica {
+        // keys expire when the master says so
+        for db in databases.iter() {
+            db.dict.lock().await.keep_expired = true;
         }
-    } else {
-        let master_connection = setup_replica(
-            &mut redis,
-            &mut config,
-            &mut slaves,
-            &mut streams,
-            &mut stream_senders,
-        )
-        .await?;
-        println!("{:?}", redis.clone().lock().await);
-        loop {
-            let redis = Arc::clone(&redis);
-            let config = Arc::clone(&config);
-            let slaves = Arc::clone(&slaves);
-            let streams = Arc::clone(&streams);
-            let stream_senders = Arc::clone(&stream_senders);
-            tokio::select! {
-                Ok((stream, socket_addr)) = listener.accept() => {
-                    tokio::spawn(async move { handle_client(stream, socket_addr, redis, config, slaves, streams, stream_senders).await });
-                }
-
-                Ok(_) = async {
-                    master_connection.lock().await.readable().await
-                } => {
-                    let master_connection = Arc::clone(&master_connection);
-                    // XXX that spawns a lot of threads
-                    tokio::spawn(async move { replica_handle_master_connection(master_connection, redis, config, slaves, streams, stream_senders).await });
-                }
+        // the master link keeps its own selected database across the commands it streams, it
+        // never subscribes so nothing reads its messages
+        let master_client = Arc::new(Mutex::new(ClientState::new(pubsub::channel().0)));
+        let (master_connection, decoder) = setup_replica(&databases, &mut config).await?;
+        let databases = Arc::clone(&databases);
+        let config = Arc::clone(&config);
+        let slaves = Arc::clone(&slaves);
+        let pubsub = Arc::clone(&pubsub);
+        let tx_lock = Arc::clone(&tx_lock);
+        tokio::spawn(async move {
+            let result = replica_handle_master_connection(master_connection, decoder, databases, master_client, config, slaves, pubsub, tx_lock).await;
+            if let Err(e) = result {
+                eprintln!("ERROR: {}", e);
             }
-        }
+        });
+    }
+    if !is_replica {
+        tokio::spawn(active_expire_cycle(databases.clone(), slaves.clone(), config.clone(), pubsub.clone(), tx_lock.clone()));
+    }
+    loop {
+        match listener.accept().await {
+            Ok((stream, socket_addr)) => {
+                let databases = Arc::clone(&databases);
+                let config = Arc::clone(&config);
+                let slaves = Arc::clone(&slaves);
+                let pubsub = Arc::clone(&pubsub);
+                let tx_lock = Arc::clone(&tx_lock);
+                tokio::spawn(async move {
+                    handle_client(stream, socket_addr, databases, config, slaves, pubsub, tx_lock).await
+                });
+            }
+            Err(e) => {
+                eprintln!("ERROR: {}", e);
+            }
+        };
     }
 }
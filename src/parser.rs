@@ -9,10 +9,13 @@ pub enum ParseError {
     InvalidInput,
     #[error("ERROR: Incomplete Input")]
     IncompleteInput,
-    #[error("ERROR: CRLF Not Found")]
-    CRLFNotFound,
+    // the input ends within the data of a bulk string, that many bytes short of it
+    #[error("ERROR: Incomplete Input")]
+    IncompleteBulk(usize),
     #[error("ERROR: Unkonwn Symbol")]
     UnkownSymbol,
+    #[error("ERROR: Query Buffer Limit Reached")]
+    QueryBufferLimit,
 }
 
 type ParseResult<'a> = std::result::Result<(RespType, &'a [u8]), ParseError>;
@@ -22,7 +25,7 @@ pub struct Parser {}
 
 impl Parser {
     pub fn parse_resp<'a>(input: &'a [u8]) -> ParseResult<'a> {
-        if input.len() == 0 || input[0] == 0 {
+        if input.is_empty() {
             return Err(ParseError::IncompleteInput);
         }
         let symbol = String::from_utf8_lossy(&input[0..1]);
@@ -56,18 +59,19 @@ impl Parser {
         let (data_len, remaining) = Self::parse_until_crlf(input)?;
         let data_len = String::from(String::from_utf8_lossy(data_len));
         if data_len == "-1" {
-            return Ok((RespType::Null, "".as_bytes()));
+            return Ok((RespType::Null, remaining));
         }
         match data_len.parse::<usize>() {
+            // the data is taken by its length, it may hold CRLF itself
             Ok(data_len) => {
-                let (data, remaining) = Self::parse_until_crlf(remaining)?;
-                if data.len().lt(&data_len) {
-                    Err(ParseError::IncompleteInput)
-                } else if data.len().gt(&data_len) {
-                    Err(ParseError::InvalidInput)
-                } else {
-                    let data = String::from(String::from_utf8_lossy(data));
-                    Ok((RespType::BulkString(data), remaining))
+                let end = data_len.checked_add(2).ok_or(ParseError::InvalidInput)?;
+                match remaining.get(data_len..end) {
+                    None => Err(ParseError::IncompleteBulk(end - remaining.len())),
+                    Some(&[CR, LF]) => {
+                        let data = String::from(String::from_utf8_lossy(&remaining[..data_len]));
+                        Ok((RespType::BulkString(data), &remaining[end..]))
+                    }
+                    Some(_) => Err(ParseError::InvalidInput),
                 }
             }
             Err(_) => Err(ParseError::InvalidInput),
@@ -87,7 +91,7 @@ impl Parser {
         let (data_len, mut remaining) = Self::parse_until_crlf(input)?;
         let data_len = String::from(String::from_utf8_lossy(data_len));
         if data_len == "-1" {
-            return Ok((RespType::Null, "".as_bytes()));
+            return Ok((RespType::Null, remaining));
         }
         match data_len.parse::<usize>() {
             Ok(data_len) => {
@@ -103,18 +107,11 @@ impl Parser {
         }
     }
 
+    // a line without its CRLF yet is incomplete, the rest of it may still be on its way
     pub fn parse_until_crlf<'a>(input: &'a [u8]) -> ParseCRLFResult<'a> {
-        if input.len() == 0 {
-            return Ok((&[0], &[0]));
-        }
-        for i in 0..input.len() - 1 {
-            if input[i] == 0 {
-                return Ok((&[0], &[0]));
-            }
-            if input[i] == CR && input[i + 1] == LF {
-                return Ok((&input[0..i], &input[i + 2..]));
-            }
+        match input.windows(2).position(|window| window == [CR, LF]) {
+            Some(i) => Ok((&input[..i], &input[i + 2..])),
+            None => Err(ParseError::IncompleteInput),
         }
-        Err(ParseError::CRLFNotFound)
     }
 }
@@ -0,0 +1,214 @@
+use crate::glob::glob_match;
+use crate::resp::RespType;
+use crate::clock::gen_millis;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex, Notify};
+
+pub type AMPubSub = Arc<Mutex<PubSub>>;
+
+/// Limits on the messages a subscriber may have queued but not yet written to its socket, going
+/// over the hard limit, or over the soft one for `soft_seconds`, disconnects the subscriber.
+/// A zero limit is disabled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputBufferLimit {
+    pub hard: usize,
+    pub soft: usize,
+    pub soft_seconds: u64,
+}
+
+/// The publishing end of a connection's messages.
+#[derive(Clone)]
+pub struct Subscriber {
+    sender: mpsc::UnboundedSender<Vec<u8>>,
+    pending: Arc<AtomicUsize>,
+    // when the pending messages went over the soft limit, 0 while they are under it
+    over_soft_since: Arc<AtomicU64>,
+    evicted: Arc<Notify>,
+}
+
+/// The connection end, it writes the messages out and gets told when it was too slow to.
+pub struct Mailbox {
+    pub messages: mpsc::UnboundedReceiver<Vec<u8>>,
+    pub eviction: Eviction,
+}
+
+pub struct Eviction {
+    pending: Arc<AtomicUsize>,
+    evicted: Arc<Notify>,
+}
+
+pub fn channel() -> (Subscriber, Mailbox) {
+    let (sender, messages) = mpsc::unbounded_channel();
+    let pending = Arc::new(AtomicUsize::new(0));
+    let evicted = Arc::new(Notify::new());
+    let subscriber = Subscriber {
+        sender,
+        pending: pending.clone(),
+        over_soft_since: Arc::default(),
+        evicted: evicted.clone(),
+    };
+    let mailbox = Mailbox {
+        messages,
+        eviction: Eviction { pending, evicted },
+    };
+    (subscriber, mailbox)
+}
+
+impl Subscriber {
+    // queues the message, returning false once the subscriber is over its limit and has to go
+    fn deliver(&self, message: &[u8], limit: &OutputBufferLimit) -> bool {
+        let pending = self.pending.fetch_add(message.len(), Ordering::SeqCst) + message.len();
+        let over_hard = limit.hard > 0 && pending > limit.hard;
+        let over_soft = limit.soft > 0 && pending > limit.soft && {
+            let now = gen_millis() as u64;
+            let since = match self.over_soft_since.load(Ordering::SeqCst) {
+                0 => {
+                    self.over_soft_since.store(now, Ordering::SeqCst);
+                    now
+                }
+                since => since,
+            };
+            now - since >= limit.soft_seconds * 1000
+        };
+        if limit.soft == 0 || pending <= limit.soft {
+            self.over_soft_since.store(0, Ordering::SeqCst);
+        }
+        if over_hard || over_soft {
+            self.evicted.notify_one();
+            return false;
+        }
+        self.sender.send(message.to_vec()).is_ok()
+    }
+}
+
+impl Eviction {
+    /// Called once a message got written to the socket.
+    pub fn written(&self, message: &[u8]) {
+        self.pending.fetch_sub(message.len(), Ordering::SeqCst);
+    }
+
+    /// Resolves once a publisher found this connection over its output buffer limit.
+    pub async fn evicted(&self) {
+        self.evicted.notified().await
+    }
+}
+
+/// Channel and pattern subscriptions of every connection, keyed by client id.
+#[derive(Default)]
+pub struct PubSub {
+    channels: HashMap<String, HashMap<u64, Subscriber>>,
+    patterns: HashMap<String, HashMap<u64, Subscriber>>,
+}
+
+impl PubSub {
+    pub fn subscribe(&mut self, channel: &str, client_id: u64, subscriber: &Subscriber) {
+        Self::add(&mut self.channels, channel, client_id, subscriber)
+    }
+
+    pub fn unsubscribe(&mut self, channel: &str, client_id: u64) {
+        Self::remove(&mut self.channels, channel, client_id)
+    }
+
+    pub fn psubscribe(&mut self, pattern: &str, client_id: u64, subscriber: &Subscriber) {
+        Self::add(&mut self.patterns, pattern, client_id, subscriber)
+    }
+
+    pub fn punsubscribe(&mut self, pattern: &str, client_id: u64) {
+        Self::remove(&mut self.patterns, pattern, client_id)
+    }
+
+    fn add(
+        subscriptions: &mut HashMap<String, HashMap<u64, Subscriber>>,
+        name: &str,
+        client_id: u64,
+        subscriber: &Subscriber,
+    ) {
+        subscriptions
+            .entry(name.to_string())
+            .or_default()
+            .insert(client_id, subscriber.clone());
+    }
+
+    fn remove(subscriptions: &mut HashMap<String, HashMap<u64, Subscriber>>, name: &str, client_id: u64) {
+        if let Some(subscribers) = subscriptions.get_mut(name) {
+            subscribers.remove(&client_id);
+            if subscribers.is_empty() {
+                subscriptions.remove(name);
+            }
+        }
+    }
+
+    /// Sends `message` to the subscribers of `channel` and of the patterns matching it, returning
+    /// how many received it. Subscribers over `limit` are dropped from every subscription.
+    pub fn publish(&mut self, channel: &str, message: &str, limit: &OutputBufferLimit) -> usize {
+        use RespType::{Array, BulkString};
+        let mut receivers = 0;
+        let mut evicted = Vec::new();
+        if let Some(subscribers) = self.channels.get(channel) {
+            let message = Array(vec![
+                BulkString("message".to_string()),
+                BulkString(channel.to_string()),
+                BulkString(message.to_string()),
+            ])
+            .serialize();
+            for (client_id, subscriber) in subscribers.iter() {
+                match subscriber.deliver(&message, limit) {
+                    true => receivers += 1,
+                    false => evicted.push(*client_id),
+                }
+            }
+        }
+        for (pattern, subscribers) in self.patterns.iter() {
+            if !glob_match(pattern.as_bytes(), channel.as_bytes()) {
+                continue;
+            }
+            let message = Array(vec![
+                BulkString("pmessage".to_string()),
+                BulkString(pattern.clone()),
+                BulkString(channel.to_string()),
+                BulkString(message.to_string()),
+            ])
+            .serialize();
+            for (client_id, subscriber) in subscribers.iter() {
+                match subscriber.deliver(&message, limit) {
+                    true => receivers += 1,
+                    false => evicted.push(*client_id),
+                }
+            }
+        }
+        for client_id in evicted {
+            self.remove_client(client_id);
+        }
+        receivers
+    }
+
+    fn remove_client(&mut self, client_id: u64) {
+        for subscriptions in [&mut self.channels, &mut self.patterns] {
+            subscriptions.retain(|_, subscribers| {
+                subscribers.remove(&client_id);
+                !subscribers.is_empty()
+            });
+        }
+    }
+
+    /// Channels with at least one subscriber, optionally only the ones matching `pattern`.
+    pub fn active_channels(&self, pattern: Option<&str>) -> Vec<String> {
+        self.channels
+            .keys()
+            .filter(|channel| pattern.map_or(true, |pattern| glob_match(pattern.as_bytes(), channel.as_bytes())))
+            .cloned()
+            .collect()
+    }
+
+    pub fn numsub(&self, channel: &str) -> usize {
+        self.channels.get(channel).map_or(0, |subscribers| subscribers.len())
+    }
+
+    /// Number of distinct patterns subscribed to.
+    pub fn numpat(&self) -> usize {
+        self.patterns.len()
+    }
+}
@@ -1,8 +1,15 @@
 // https://rdb.fnordig.de/file_format.html
 
-use crate::data_entry::{ValueType, DataEntry};
+use crate::consumer_group::{Consumer, ConsumerGroup, PendingEntry};
+use crate::data_entry::{ValueType, DataEntry, DataValue};
+use crate::hash_entry::HashEntry;
+use crate::list_entry::ListEntry;
+use crate::listpack::Listpack;
+use crate::set_entry::SetEntry;
+use crate::stream_entry::{StreamEntry, StreamID};
+use crate::zset_entry::ZSetEntry;
 use crate::redis::RedisDB;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::time::SystemTime;
 use tokio::time::{Duration, Instant};
 use crate::utils::take_upto;
@@ -30,6 +37,8 @@ pub enum RDBParseError {
     InvalidTimeMS,
     #[error("ERROR: invalid given value type or not implemented yet")]
     InvalidValType,
+    #[error("ERROR: invalid encoded stream")]
+    InvalidStream,
 }
 
 #[derive(Debug)]
@@ -53,7 +62,6 @@ impl RDBHeader {
         let mut out = Vec::new();
         out.extend_from_slice(&self.magic.as_bytes());
         out.extend_from_slice(format!("{:04}", &self.rdb_version).as_bytes());
-        out.extend_from_slice(&[SELECTDB, 0x0]);
         for (key, value) in self.aux_settings.iter() {
             out.push(AUX);
             out.extend_from_slice(&key.as_rdb()[..]);
@@ -68,16 +76,11 @@ type Result<T> = std::result::Result<T, RDBParseError>;
 pub struct RDBParser {}
 
 impl RDBParser {
-    pub fn from_rdb_file(data: &mut &[u8]) -> Result<(RDBHeader, RedisDB)> {
+    pub fn from_rdb_file(data: &mut &[u8]) -> Result<(RDBHeader, HashMap<usize, RedisDB>)> {
         Self::from_rdb(data, data.len())
     }
 
-    pub fn from_rdb_resp(data: &mut &[u8]) -> Result<(RDBHeader, RedisDB)> {
-        let file_length = Self::parse_rdb_file_length(data)?;
-        Self::from_rdb(data, file_length)
-    }
-
-    fn from_rdb(data: &mut &[u8], file_length: usize) -> Result<(RDBHeader, RedisDB)> {
+    fn from_rdb(data: &mut &[u8], file_length: usize) -> Result<(RDBHeader, HashMap<usize, RedisDB>)> {
         let data_len_should_remain = data.len() - file_length;
         println!("[+] Data Len Start: {:?} /  Parsed RDB file length: {:?} / Should rem: {:?}", data.len(), file_length, data_len_should_remain);
 
@@ -85,10 +88,13 @@ impl RDBParser {
         let rdb_version = Self::parse_version(data)? as u8;
 
         let mut aux_settings: HashMap<ValueType, ValueType> = HashMap::new();
-        let mut db: HashMap<ValueType, DataEntry> = HashMap::new();
+        let mut dbs: HashMap<usize, RedisDB> = HashMap::new();
+        // keys before any SELECTDB belong to the first database
+        let mut db_index = 0;
 
         while data.len() >= data_len_should_remain {
-            let (opcode, rest) = data.split_first_chunk::<1>().unwrap();
+            // a file cut before its EOF opcode
+            let (opcode, rest) = data.split_first_chunk::<1>().ok_or(RDBParseError::InvalidFileLength)?;
             let opcode = opcode[0];
             match opcode {
                 EOF => {
@@ -103,9 +109,7 @@ impl RDBParser {
                 }
                 SELECTDB => {
                     *data = rest;
-                    // TODO: for now discard this selector and support just one DB. later use the
-                    // given selector to select a DB
-                    let _db_selector = Self::parse_length_encoded_data(data)?;
+                    db_index = Self::parse_integer(data)? as usize;
                 }
                 RESIZEDB => {
                     *data = rest;
@@ -124,16 +128,13 @@ impl RDBParser {
                 EXPIRETIME => {
                     *data = rest;
                     let expiry = Duration::from_secs(Self::parse_time_secs(data)? as u64);
-                    // TODO: for now discard the value type and just support only string encoded
-                    // for simplicity. later use value_type and optimize memory by adjusting the
-                    // value type for each key
-                    let _val_type = Self::parse_value_type(data)?;
+                    let val_type = Self::parse_value_type(data)?;
                     let key = Self::parse_length_encoded_data(data)?;
-                    let value = Self::parse_length_encoded_data(data)?;
-                    db.insert(
+                    let value = Self::parse_value(val_type, data)?;
+                    dbs.entry(db_index).or_default().insert(
                         ValueType::new(key),
                         DataEntry {
-                            value: ValueType::new(value),
+                            value,
                             created_at: Instant::now(), // XXX
                             expired_at_unix_millis: Some(SystemTime::UNIX_EPOCH + expiry),
                         },
@@ -142,32 +143,26 @@ impl RDBParser {
                 EXPIRETIMEMS => {
                     *data = rest;
                     let expiry = Duration::from_millis(Self::parse_time_millis(data)?);
-                    // TODO: for now discard the value type and just support only string encoded
-                    // for simplicity. later use value_type and optimize memory by adjusting the
-                    // value type for each key
-                    let _val_type = Self::parse_value_type(data)?;
+                    let val_type = Self::parse_value_type(data)?;
                     let key = Self::parse_length_encoded_data(data)?;
-                    let value = Self::parse_length_encoded_data(data)?;
-                    db.insert(
+                    let value = Self::parse_value(val_type, data)?;
+                    dbs.entry(db_index).or_default().insert(
                         ValueType::new(key),
                         DataEntry {
-                            value: ValueType::new(value),
+                            value,
                             created_at: Instant::now(), // XXX
                             expired_at_unix_millis: Some(SystemTime::UNIX_EPOCH + expiry),
                         },
                     );
                 }
                 _ => {
-                    // TODO: for now discard the value type and just support only string encoded
-                    // for simplicity. later use value_type and optimize memory by adjusting the
-                    // value type for each key
-                    let _val_type = Self::parse_value_type(data)?;
+                    let val_type = Self::parse_value_type(data)?;
                     let key = Self::parse_length_encoded_data(data)?;
-                    let value = Self::parse_length_encoded_data(data)?;
-                    db.insert(
+                    let value = Self::parse_value(val_type, data)?;
+                    dbs.entry(db_index).or_default().insert(
                         ValueType::new(key),
                         DataEntry {
-                            value: ValueType::new(value),
+                            value,
                             created_at: Instant::now(), // XXX
                             expired_at_unix_millis: None,
                         },
@@ -175,33 +170,7 @@ impl RDBParser {
                 }
             };
         }
-        Ok((RDBHeader { magic, rdb_version, aux_settings, }, db))
-    }
-
-    fn parse_rdb_file_length(data: &mut &[u8]) -> Result<usize> {
-        use RDBParseError::InvalidFileLength as err;
-        let mark_str = String::from_utf8_lossy(take_upto::<1>(data).ok_or_else(|| err)?);
-        if !mark_str.eq("$") {
-            return Err(err);
-        }
-        if data.len() == 0 {
-            return Err(err);
-        }
-        for i in 0..data.len() - 1 {
-            if data[i] == 0 {
-                return Err(err);
-            }
-            if data[i] == CR && data[i + 1] == LF {
-                return match String::from_utf8_lossy(&data[0..i]).parse::<usize>() {
-                    Ok(number) => {
-                        *data = &data[i + 2 ..];
-                        Ok(number)
-                    }
-                    Err(_) => Err(err),
-                };
-            }
-        }
-        return Err(err);
+        Ok((RDBHeader { magic, rdb_version, aux_settings, }, dbs))
     }
 
     fn parse_magic(data: &mut &[u8]) -> Result<String> {
@@ -232,11 +201,13 @@ impl RDBParser {
                 let next_byte = take_upto::<1>(data).ok_or_else(|| RDBParseError::InvalidLen)?[0];
                 Ok(IntOrString(i16::from_le_bytes([next_byte, len_type & 0b00111111]) as i32))
             }
-            2 => {
+            2 if len_type == RDB_32BITLEN => {
                 let next_four_bytes =
                     take_upto::<4>(data).ok_or_else(|| RDBParseError::InvalidLen)?;
-                Ok(IntOrString(i32::from_le_bytes(*next_four_bytes)))
+                Ok(IntOrString(i32::from_be_bytes(*next_four_bytes)))
             }
+            // 64 bit lengths are only read by `parse_len_u64`
+            2 => Err(RDBParseError::InvalidLen),
             3 => match len_type & 0b00111111 {
                 0 => Ok(I8Int),
                 1 => Ok(I16Int),
@@ -260,6 +231,156 @@ impl RDBParser {
         // }
     }
 
+    fn parse_value(val_type: u8, data: &mut &[u8]) -> Result<DataValue> {
+        match val_type {
+            RDB_TYPE_STRING => Ok(DataValue::String(ValueType::new(
+                Self::parse_length_encoded_data(data)?,
+            ))),
+            RDB_TYPE_LIST => {
+                let len = Self::parse_integer(data)?;
+                let list = (0..len)
+                    .map(|_| Self::parse_length_encoded_data(data))
+                    .collect::<Result<ListEntry>>()?;
+                Ok(DataValue::List(list))
+            }
+            RDB_TYPE_SET => {
+                let len = Self::parse_integer(data)?;
+                let set = (0..len)
+                    .map(|_| Self::parse_length_encoded_data(data))
+                    .collect::<Result<SetEntry>>()?;
+                Ok(DataValue::Set(set))
+            }
+            RDB_TYPE_HASH => {
+                let len = Self::parse_integer(data)?;
+                let hash = (0..len)
+                    .map(|_| {
+                        let field = Self::parse_length_encoded_data(data)?;
+                        let value = Self::parse_length_encoded_data(data)?;
+                        Ok((field, value))
+                    })
+                    .collect::<Result<HashEntry>>()?;
+                Ok(DataValue::Hash(hash))
+            }
+            RDB_TYPE_ZSET_2 => {
+                let len = Self::parse_integer(data)?;
+                let zset = (0..len)
+                    .map(|_| {
+                        let member = Self::parse_length_encoded_data(data)?;
+                        let score = take_upto::<8>(data).ok_or_else(|| RDBParseError::InvalidValType)?;
+                        Ok((member, f64::from_le_bytes(*score)))
+                    })
+                    .collect::<Result<ZSetEntry>>()?;
+                Ok(DataValue::ZSet(zset))
+            }
+            RDB_TYPE_STREAM_LISTPACKS | RDB_TYPE_STREAM_LISTPACKS_2 | RDB_TYPE_STREAM_LISTPACKS_3 => {
+                Ok(DataValue::Stream(Self::parse_stream(val_type, data)?))
+            }
+            _ => Err(RDBParseError::InvalidValType),
+        }
+    }
+
+    // the layout `StreamEntry::as_rdb` writes, the fields missing from older types get defaults
+    fn parse_stream(val_type: u8, data: &mut &[u8]) -> Result<StreamEntry> {
+        use RDBParseError::InvalidStream as err;
+        let mut nodes = BTreeMap::new();
+        for _ in 0..Self::parse_len_u64(data)? {
+            let master_id = Self::parse_length_encoded_bytes(data)?;
+            let master_id = StreamID::from_be_bytes(master_id.as_slice().try_into().map_err(|_| err)?);
+            let node = Listpack::from_bytes(Self::parse_length_encoded_bytes(data)?).ok_or(err)?;
+            if node.is_empty() {
+                return Err(err);
+            }
+            nodes.insert(master_id, node);
+        }
+        // the entries are counted again from the nodes
+        let _length = Self::parse_len_u64(data)?;
+        let last_id = Self::parse_stream_id(data)?;
+        let (max_deleted_entry_id, entries_added) = match val_type {
+            RDB_TYPE_STREAM_LISTPACKS => (StreamID::MIN, None),
+            _ => {
+                let _first_id = Self::parse_stream_id(data)?;
+                let max_deleted_entry_id = Self::parse_stream_id(data)?;
+                (max_deleted_entry_id, Some(Self::parse_len_u64(data)?))
+            }
+        };
+        let mut groups = BTreeMap::new();
+        for _ in 0..Self::parse_len_u64(data)? {
+            let name = Self::parse_length_encoded_data(data)?;
+            let last_delivered_id = Self::parse_stream_id(data)?;
+            let entries_read = match val_type {
+                RDB_TYPE_STREAM_LISTPACKS => None,
+                _ => Some(Self::parse_len_u64(data)?).filter(|entries_read| *entries_read != u64::MAX),
+            };
+            let mut group = ConsumerGroup::new(last_delivered_id, entries_read);
+            // delivery time and count of every pending entry, until a consumer claims it
+            let mut pending = BTreeMap::new();
+            for _ in 0..Self::parse_len_u64(data)? {
+                let id = Self::parse_raw_stream_id(data)?;
+                let delivery_time = Self::parse_time_millis(data)? as u128;
+                pending.insert(id, (delivery_time, Self::parse_len_u64(data)?));
+            }
+            for _ in 0..Self::parse_len_u64(data)? {
+                let consumer_name = Self::parse_length_encoded_data(data)?;
+                let seen_time = Self::parse_time_millis(data)? as u128;
+                let active_time = match val_type {
+                    RDB_TYPE_STREAM_LISTPACKS_3 => match Self::parse_time_millis(data)? as i64 {
+                        -1 => None,
+                        active_time => Some(active_time as u128),
+                    },
+                    _ => Some(seen_time),
+                };
+                let mut consumer = Consumer {
+                    seen_time,
+                    active_time,
+                    pending: BTreeSet::new(),
+                };
+                for _ in 0..Self::parse_len_u64(data)? {
+                    let id = Self::parse_raw_stream_id(data)?;
+                    let (delivery_time, delivery_count) = pending.remove(&id).ok_or(err)?;
+                    group.pending.insert(
+                        id.clone(),
+                        PendingEntry {
+                            consumer: consumer_name.clone(),
+                            delivery_time,
+                            delivery_count,
+                        },
+                    );
+                    consumer.pending.insert(id);
+                }
+                group.consumers.insert(consumer_name, consumer);
+            }
+            // every pending entry belongs to a consumer
+            if !pending.is_empty() {
+                return Err(err);
+            }
+            groups.insert(name, group);
+        }
+        Ok(StreamEntry::from_rdb(nodes, last_id, max_deleted_entry_id, entries_added, groups))
+    }
+
+    fn parse_stream_id(data: &mut &[u8]) -> Result<StreamID> {
+        let millis = Self::parse_len_u64(data)? as u128;
+        Ok(StreamID { millis, seq: Self::parse_len_u64(data)? })
+    }
+
+    // ids in PELs aren't length prefixed
+    fn parse_raw_stream_id(data: &mut &[u8]) -> Result<StreamID> {
+        let bytes = take_upto::<16>(data).ok_or(RDBParseError::InvalidStream)?;
+        Ok(StreamID::from_be_bytes(bytes))
+    }
+
+    // stream ids and counters take the whole u64 range, which needs the 64 bit length encoding
+    fn parse_len_u64(data: &mut &[u8]) -> Result<u64> {
+        match data.first() {
+            Some(&RDB_64BITLEN) => {
+                *data = &data[1..];
+                let bytes = take_upto::<8>(data).ok_or(RDBParseError::InvalidLen)?;
+                Ok(u64::from_be_bytes(*bytes))
+            }
+            _ => Ok(Self::parse_integer(data)? as u32 as u64),
+        }
+    }
+
     fn parse_integer(data: &mut &[u8]) -> Result<i32> {
         use RDBParseError::InvalidInt as err;
         use RDBParsedLen::*;
@@ -282,20 +403,25 @@ impl RDBParser {
     }
 
     fn parse_length_encoded_data(data: &mut &[u8]) -> Result<String> {
+        Ok(String::from(String::from_utf8_lossy(&Self::parse_length_encoded_bytes(data)?)))
+    }
+
+    // binary strings like listpacks
+    fn parse_length_encoded_bytes(data: &mut &[u8]) -> Result<Vec<u8>> {
         use RDBParseError::*;
         use RDBParsedLen::*;
         match Self::parse_length(data)? {
             I8Int => {
                 let ibytes = take_upto::<1>(data).ok_or_else(|| InvalidInt)?;
-                Ok((i8::from_le_bytes(*ibytes)).to_string())
+                Ok((i8::from_le_bytes(*ibytes)).to_string().into_bytes())
             }
             I16Int => {
                 let ibytes = take_upto::<2>(data).ok_or_else(|| InvalidInt)?;
-                Ok((i16::from_le_bytes(*ibytes)).to_string())
+                Ok((i16::from_le_bytes(*ibytes)).to_string().into_bytes())
             }
             I32Int => {
                 let ibytes = take_upto::<4>(data).ok_or_else(|| InvalidInt)?;
-                Ok(i32::from_le_bytes(*ibytes).to_string())
+                Ok(i32::from_le_bytes(*ibytes).to_string().into_bytes())
             }
             CompressedString => {
                 let comp_len = Self::parse_integer(data)? as usize;
@@ -307,9 +433,7 @@ impl RDBParser {
                 } else {
                     return Err(InvalidCompStr);
                 };
-                let decompressed =
-                    lzf::decompress(&ibytes, real_len).map_err(|_| InvalidCompStr)?;
-                Ok(String::from(String::from_utf8_lossy(&decompressed)))
+                lzf::decompress(&ibytes, real_len).map_err(|_| InvalidCompStr)
             }
             IntOrString(ilen) => {
                 let ibytes = if data.len() >= ilen as usize {
@@ -319,7 +443,7 @@ impl RDBParser {
                 } else {
                     return Err(InvalidUnCompStr);
                 };
-                Ok(String::from(String::from_utf8_lossy(ibytes)))
+                Ok(ibytes.to_vec())
             }
         }
     }
@@ -336,3 +460,144 @@ impl RDBParser {
         Ok(u64::from_le_bytes(*ibytes))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_entry::key_value_as_rdb;
+
+    // a stream spanning several nodes, with deleted entries, both field layouts and groups
+    // holding pending entries
+    fn sample_stream() -> StreamEntry {
+        let mut stream = StreamEntry::new();
+        for i in 1..=250u64 {
+            let mut data = BTreeMap::new();
+            data.insert("sensor".to_string(), format!("{}", i % 7));
+            if i % 3 == 0 {
+                data.insert("name".to_string(), format!("reading-{}", i));
+            }
+            stream.append_stream(&format!("{}-{}", 1000 + i / 2, i % 2), data).unwrap();
+        }
+        for i in [2u64, 5, 101, 102] {
+            stream.delete(&StreamID { millis: 1000 + i as u128 / 2, seq: i % 2 });
+        }
+        let mut group = ConsumerGroup::new(StreamID::MIN, Some(0));
+        group.deliver(&StreamID { millis: 1001, seq: 1 }, "alice", false);
+        group.deliver(&StreamID { millis: 1002, seq: 0 }, "bob", false);
+        group.deliver(&StreamID { millis: 1003, seq: 0 }, "alice", false);
+        group.redeliver(&StreamID { millis: 1003, seq: 0 });
+        group.create_consumer("idle");
+        stream.groups.insert("readers".to_string(), group);
+        stream.groups.insert("unknown".to_string(), ConsumerGroup::new(StreamID { millis: 1010, seq: 0 }, None));
+        stream
+    }
+
+    fn parse_stream(rdb_type: u8, stream: &StreamEntry) -> StreamEntry {
+        let encoded = stream.as_rdb(rdb_type);
+        let mut data = &encoded[..];
+        let parsed = match RDBParser::parse_value(rdb_type, &mut data) {
+            Ok(DataValue::Stream(parsed)) => parsed,
+            other => panic!("not a stream: {:?}", other.err()),
+        };
+        assert!(data.is_empty());
+        parsed
+    }
+
+    fn entries(stream: &StreamEntry) -> crate::resp::RespType {
+        stream.query_xrange(&StreamID::MIN, &StreamID::MAX, None, false)
+    }
+
+    #[test]
+    fn stream_round_trip() {
+        let stream = sample_stream();
+        let parsed = parse_stream(RDB_TYPE_STREAM_LISTPACKS_3, &stream);
+        assert_eq!(entries(&parsed), entries(&stream));
+        assert_eq!(parsed.len(), stream.len());
+        assert_eq!(parsed.last_id(), stream.last_id());
+        assert_eq!(parsed.max_deleted_entry_id(), stream.max_deleted_entry_id());
+        assert_eq!(parsed.entries_added(), stream.entries_added());
+        assert_eq!(format!("{:?}", parsed.groups), format!("{:?}", stream.groups));
+    }
+
+    #[test]
+    fn older_stream_types_fill_in_defaults() {
+        let mut stream = sample_stream();
+        let parsed = parse_stream(RDB_TYPE_STREAM_LISTPACKS_2, &stream);
+        assert_eq!(entries(&parsed), entries(&stream));
+        assert_eq!(parsed.entries_added(), stream.entries_added());
+        // the active time wasn't stored yet
+        for group in stream.groups.values_mut() {
+            for consumer in group.consumers.values_mut() {
+                consumer.active_time = Some(consumer.seen_time);
+            }
+        }
+        assert_eq!(format!("{:?}", parsed.groups), format!("{:?}", stream.groups));
+
+        let parsed = parse_stream(RDB_TYPE_STREAM_LISTPACKS, &stream);
+        assert_eq!(entries(&parsed), entries(&stream));
+        assert_eq!(parsed.last_id(), stream.last_id());
+        assert_eq!(parsed.max_deleted_entry_id(), &StreamID::MIN);
+        assert_eq!(parsed.entries_added(), stream.len() as u64);
+        for group in stream.groups.values_mut() {
+            group.entries_read = None;
+        }
+        assert_eq!(format!("{:?}", parsed.groups), format!("{:?}", stream.groups));
+    }
+
+    #[test]
+    fn empty_stream_round_trip() {
+        let mut stream = StreamEntry::new();
+        stream.groups.insert("g".to_string(), ConsumerGroup::new(StreamID::MIN, Some(0)));
+        let parsed = parse_stream(RDB_TYPE_STREAM_LISTPACKS_3, &stream);
+        assert_eq!(parsed.len(), 0);
+        assert_eq!(format!("{:?}", parsed.groups), format!("{:?}", stream.groups));
+    }
+
+    #[test]
+    fn large_ids_round_trip() {
+        let mut stream = StreamEntry::new();
+        let mut data = BTreeMap::new();
+        data.insert("f".to_string(), "v".to_string());
+        stream.append_stream(&format!("{}-{}", u64::MAX, u64::MAX - 1), data).unwrap();
+        let parsed = parse_stream(RDB_TYPE_STREAM_LISTPACKS_3, &stream);
+        assert_eq!(parsed.last_id(), stream.last_id());
+        assert_eq!(entries(&parsed), entries(&stream));
+    }
+
+    #[test]
+    fn rejects_truncated_streams() {
+        let encoded = sample_stream().as_rdb(RDB_TYPE_STREAM_LISTPACKS_3);
+        for len in [0, 1, 20, encoded.len() / 2, encoded.len() - 1] {
+            let mut data = &encoded[..len];
+            assert!(RDBParser::parse_value(RDB_TYPE_STREAM_LISTPACKS_3, &mut data).is_err());
+        }
+    }
+
+    #[test]
+    fn stream_in_rdb_file() {
+        let header = RDBHeader {
+            magic: "REDIS".to_string(),
+            rdb_version: 11,
+            aux_settings: HashMap::new(),
+        };
+        let key = ValueType::new("events".to_string());
+        let entry = DataEntry {
+            value: DataValue::Stream(sample_stream()),
+            created_at: Instant::now(),
+            expired_at_unix_millis: None,
+        };
+        let mut file = header.as_rdb();
+        file.extend([SELECTDB, 0]);
+        file.extend(key_value_as_rdb(&key, &entry));
+        file.push(EOF);
+        file.extend([0; 8]);
+
+        let (_, dbs) = RDBParser::from_rdb_file(&mut &file[..]).unwrap();
+        let parsed = match dbs.get(&0).and_then(|db| db.get(&key)).map(|entry| &entry.value) {
+            Some(DataValue::Stream(parsed)) => parsed,
+            _ => panic!("stream wasn't loaded"),
+        };
+        assert_eq!(entries(parsed), entries(&sample_stream()));
+        assert_eq!(parsed.groups.len(), 2);
+    }
+}
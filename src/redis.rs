@@ -1,6 +1,10 @@
 use crate::{
+    constants::SELECTDB,
+    resp_array_of_bulks,
+    blocked_clients::AMBlockedClients,
     config::Config,
-    data_entry::{key_value_as_rdb, DataEntry, ValueType},
+    cmd::CmdError,
+    data_entry::{key_value_as_rdb, DataEntry, DataValue, ValueType},
     resp::RespType,
     slave_meta::{SlaveMeta, UpdateState},
     stream_entry::StreamEntry,
@@ -13,18 +17,78 @@ use std::{
 };
 use tokio::sync::{
     broadcast::{self, Receiver, Sender},
-    Mutex,
+    Mutex, RwLock,
 };
 
-pub type RedisDB = HashMap<ValueType, DataEntry>;
-pub type StreamDB = HashMap<ValueType, StreamEntry>;
+pub use crate::redis_db::RedisDB;
 
-// TODO: what if entered dict has same key as streams?
 pub type AMConfig = Arc<Mutex<Config>>;
 pub type AMRedisDB = Arc<Mutex<RedisDB>>;
-pub type AMStreams = Arc<Mutex<StreamDB>>;
-pub type AMSlaves = Arc<Mutex<HashMap<SocketAddr, SlaveMeta>>>;
+pub type Slaves = HashMap<SocketAddr, SlaveMeta>;
+pub type AMSlaves = Arc<Mutex<Slaves>>;
 pub type AMStreamSenders = Arc<Mutex<HashMap<String, Sender<RespType>>>>;
+pub type Databases = Arc<Vec<Database>>;
+/// Commands run holding it for reading, EXEC holds it for writing so no other command runs in
+/// the middle of a transaction.
+pub type TxLock = Arc<RwLock<()>>;
+
+/// A logical database selected with SELECT, keys of different databases never meet.
+#[derive(Clone, Default)]
+pub struct Database {
+    pub dict: AMRedisDB,
+    pub stream_senders: AMStreamSenders,
+    pub blocked_clients: AMBlockedClients,
+}
+
+tokio::task_local! {
+    /// Set while a replica runs what its master streams, the only one that still sees the keys
+    /// that expired but weren't deleted by the master yet.
+    pub static MASTER_LINK: ();
+}
+
+pub fn new_databases(count: usize) -> Databases {
+    Arc::new((0..count).map(|_| Database::default()).collect())
+}
+
+/// Replaces the keys of every database with the ones loaded from an RDB file, databases missing
+/// from the file end up empty.
+pub async fn load_databases(databases: &Databases, mut loaded: HashMap<usize, RedisDB>) {
+    for (index, db) in databases.iter().enumerate() {
+        let mut dict = loaded.remove(&index).unwrap_or_default();
+        // loading isn't a change anyone gets notified about
+        dict.take_events();
+        let mut dict_guard = db.dict.lock().await;
+        dict.keep_expired = dict_guard.keep_expired;
+        *dict_guard = dict;
+    }
+    for index in loaded.keys() {
+        eprintln!("ERROR: RDB selects database {} but there are only {}", index, databases.len());
+    }
+}
+
+/// Looks up `key`, lazily removing it first if its TTL already passed. Replicas report it missing
+/// instead, leaving it for the DEL of their master so both apply the same writes to it.
+pub fn get_alive_entry<'a>(dict: &'a mut RedisDB, key: &ValueType) -> Option<&'a mut DataEntry> {
+    if dict.get(key).is_some_and(|entry| entry.is_expired()) {
+        match dict.keep_expired {
+            false => {
+                dict.expire(key);
+            }
+            true if MASTER_LINK.try_with(|_| ()).is_err() => return None,
+            true => (),
+        }
+    }
+    dict.get_mut(key)
+}
+
+/// Looks up the stream stored at `key`, `Ok(None)` if there is no such key.
+pub fn get_alive_stream<'a>(dict: &'a mut RedisDB, key: &ValueType) -> Result<Option<&'a mut StreamEntry>, CmdError> {
+    match get_alive_entry(dict, key) {
+        Some(DataEntry { value: DataValue::Stream(stream), .. }) => Ok(Some(stream)),
+        Some(_) => Err(CmdError::WrongType),
+        None => Ok(None),
+    }
+}
 
 pub async fn incr_master_repl_offset(cfg: AMConfig, value: u64) {
     cfg.lock().await.replica_of.master_repl_offset += value;
@@ -35,7 +99,6 @@ pub async fn get_stream_reciver(
     key: &String,
 ) -> Receiver<RespType> {
     let mut stream_senders_guard = stream_senders.lock().await;
-    println!("{:?}", stream_senders_guard.len());
     match stream_senders_guard.get(key) {
         Some(sender) => sender.subscribe(),
         _ => {
@@ -47,15 +110,53 @@ pub async fn get_stream_reciver(
 }
 
 pub async fn add_pending_update_resp(slaves: AMSlaves, resp: &RespType) {
-    for (_socket_addr, slave_meta) in slaves.lock().await.iter_mut() {
+    queue_update(&mut *slaves.lock().await, resp);
+}
+
+// Writes hold the lock of `slaves` from the moment they change anything until they queued what
+// they changed, so the replicas get the updates in the order they were applied. These queue
+// through a guard the caller already holds.
+
+pub fn queue_update(slaves: &mut Slaves, resp: &RespType) {
+    for (_socket_addr, slave_meta) in slaves.iter_mut() {
         slave_meta.append_update(&resp.serialize());
     }
 }
 
-pub async fn db_as_rdb(dict: AMRedisDB) -> Vec<u8> {
+/// Like `queue_update` but for commands that touch the keys of database `db`, the replicas get a
+/// SELECT first whenever their stream was in another database.
+pub fn queue_db_update(slaves: &mut Slaves, db: usize, resp: &RespType) {
+    for (_socket_addr, slave_meta) in slaves.iter_mut() {
+        if slave_meta.selected_db != Some(db) {
+            slave_meta.append_update(&resp_array_of_bulks!("SELECT", db.to_string()).serialize());
+            slave_meta.selected_db = Some(db);
+        }
+        slave_meta.append_update(&resp.serialize());
+    }
+}
+
+/// Queues a DEL for every key of database `db` that expired since the last call, returning
+/// whether there was any.
+pub fn queue_expired(slaves: &mut Slaves, db: usize, dict: &mut RedisDB) -> bool {
+    let expired = dict.take_expired();
+    for key in expired.iter() {
+        queue_db_update(slaves, db, &resp_array_of_bulks!("DEL", key.as_string()));
+    }
+    !expired.is_empty()
+}
+
+pub async fn db_as_rdb(databases: &Databases) -> Vec<u8> {
     let mut out: Vec<u8> = vec![];
-    for (key, value) in dict.lock().await.iter() {
-        out.extend_from_slice(&key_value_as_rdb(&key, &value)[..]);
+    for (index, db) in databases.iter().enumerate() {
+        let dict_guard = db.dict.lock().await;
+        if dict_guard.is_empty() {
+            continue;
+        }
+        out.push(SELECTDB);
+        out.extend_from_slice(&ValueType::number_to_length_encoded(index as u32)[..]);
+        for (key, value) in dict_guard.iter() {
+            out.extend_from_slice(&key_value_as_rdb(&key, &value)[..]);
+        }
     }
     out
 }
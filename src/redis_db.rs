@@ -0,0 +1,264 @@
+use crate::client::WatchFlag;
+use crate::data_entry::{DataEntry, ValueType};
+use crate::keyspace_events::{EventClass, KeyspaceEvent};
+use crate::scan::ScanIndex;
+
+use rand::{thread_rng, Rng};
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+/// Expiry counters reported by the stats section of INFO.
+#[derive(Debug, Default, Clone)]
+pub struct ExpireStats {
+    pub expired_keys: u64,
+    pub expired_time_cap_reached_count: u64,
+    pub expire_cycle_cpu_micros: u64,
+}
+
+impl fmt::Display for ExpireStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "# Stats\nexpired_keys:{}\nexpired_time_cap_reached_count:{}\nexpire_cycle_cpu_milliseconds:{}",
+            self.expired_keys,
+            self.expired_time_cap_reached_count,
+            self.expire_cycle_cpu_micros / 1000,
+        )
+    }
+}
+
+/// Keys of a database. Besides the entries it keeps every key that was given a TTL in a vector
+/// (like the expires dict of redis), so the active expiry cycle can sample them at random without
+/// walking the whole keyspace. The vector may hold stale keys that got deleted or persisted, they
+/// are dropped once sampled.
+///
+/// It also knows which clients WATCH its keys, every change to a key (including its expiry)
+/// must `touch` it so their transactions fail. `insert`, `remove` and `flush` do it on their own,
+/// whoever changes an entry through `get_mut` must call `touch` as well.
+///
+/// Keyspace events are queued the same way, `insert` and expiries `notify` on their own while
+/// commands notify the events named after them.
+#[derive(Debug, Default)]
+pub struct RedisDB {
+    entries: HashMap<ValueType, DataEntry>,
+    // the keys in SCAN order
+    scan_index: ScanIndex<ValueType>,
+    volatile: Vec<ValueType>,
+    volatile_index: HashMap<ValueType, usize>,
+    // expired keys the replicas weren't told about yet
+    expired: Vec<ValueType>,
+    watchers: HashMap<ValueType, Vec<WatchFlag>>,
+    // keyspace events not published yet
+    events: Vec<KeyspaceEvent>,
+    pub stats: ExpireStats,
+    // set on replicas, their keys only go away once the master sends the DEL of their expiry
+    pub keep_expired: bool,
+}
+
+impl RedisDB {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &ValueType) -> Option<&DataEntry> {
+        self.entries.get(key)
+    }
+
+    /// Whoever changes the returned entry must call `touch`, and `track_expiry` as well if it
+    /// gets a TTL.
+    pub fn get_mut(&mut self, key: &ValueType) -> Option<&mut DataEntry> {
+        self.entries.get_mut(key)
+    }
+
+    pub fn contains_key(&self, key: &ValueType) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, ValueType, DataEntry> {
+        self.entries.iter()
+    }
+
+    /// The next cursor and the entries of about `count` keys from `cursor` on, see `ScanIndex`.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&ValueType, &DataEntry)>) {
+        let (cursor, keys) = self.scan_index.scan(cursor, count);
+        let entries = keys
+            .into_iter()
+            .filter_map(|key| self.entries.get_key_value(key))
+            .collect();
+        (cursor, entries)
+    }
+
+    pub fn insert(&mut self, key: ValueType, entry: DataEntry) -> Option<DataEntry> {
+        if entry.expired_at_unix_millis.is_some() {
+            self.track_expiry(&key);
+        }
+        self.touch(&key);
+        if !self.entries.contains_key(&key) {
+            self.notify(EventClass::New, "new", &key);
+            self.scan_index.insert(&key);
+        }
+        self.entries.insert(key, entry)
+    }
+
+    pub fn remove(&mut self, key: &ValueType) -> Option<DataEntry> {
+        self.untrack_expiry(key);
+        let entry = self.entries.remove(key)?;
+        self.scan_index.remove(key);
+        self.touch(key);
+        Some(entry)
+    }
+
+    /// Marks the transactions of every client watching `key` as failed.
+    pub fn touch(&mut self, key: &ValueType) {
+        // once failed the transaction stays failed, so there is no need to keep watching
+        for flag in self.watchers.remove(key).into_iter().flatten() {
+            flag.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Queues a keyspace event about `key`, published once the running command is done.
+    pub fn notify(&mut self, class: EventClass, event: &'static str, key: &ValueType) {
+        self.events.push(KeyspaceEvent {
+            class,
+            event,
+            key: key.clone(),
+        });
+    }
+
+    /// Keyspace events since the last call.
+    pub fn take_events(&mut self) -> Vec<KeyspaceEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn watch(&mut self, key: ValueType, flag: &WatchFlag) {
+        let flags = self.watchers.entry(key).or_default();
+        if !flags.iter().any(|watching| Arc::ptr_eq(watching, flag)) {
+            flags.push(flag.clone());
+        }
+    }
+
+    pub fn unwatch(&mut self, key: &ValueType, flag: &WatchFlag) {
+        if let Some(flags) = self.watchers.get_mut(key) {
+            flags.retain(|watching| !Arc::ptr_eq(watching, flag));
+            if flags.is_empty() {
+                self.watchers.remove(key);
+            }
+        }
+    }
+
+    /// Swaps the keys of two databases, the clients watching them stay with their database.
+    pub fn swap_keys(&mut self, other: &mut RedisDB) {
+        std::mem::swap(&mut self.entries, &mut other.entries);
+        std::mem::swap(&mut self.scan_index, &mut other.scan_index);
+        std::mem::swap(&mut self.volatile, &mut other.volatile);
+        std::mem::swap(&mut self.volatile_index, &mut other.volatile_index);
+        std::mem::swap(&mut self.expired, &mut other.expired);
+        for db in [&mut *self, &mut *other] {
+            let watched_keys = db.watchers.keys().cloned().collect::<Vec<_>>();
+            for key in watched_keys {
+                db.touch(&key);
+            }
+        }
+    }
+
+    /// Removes every key at once, returning them so the caller decides where they get dropped.
+    /// Keys that expired before are still queued for the replicas.
+    pub fn flush(&mut self) -> HashMap<ValueType, DataEntry> {
+        self.scan_index.clear();
+        self.volatile.clear();
+        self.volatile_index.clear();
+        let watched_keys = self
+            .watchers
+            .keys()
+            .filter(|key| self.entries.contains_key(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in watched_keys {
+            self.touch(&key);
+        }
+        std::mem::take(&mut self.entries)
+    }
+
+    /// Number of keys with a TTL.
+    pub fn expires(&self) -> usize {
+        self.entries
+            .values()
+            .filter(|entry| entry.expired_at_unix_millis.is_some())
+            .count()
+    }
+
+    /// Makes the active expiry cycle aware of the TTL `key` was just given.
+    pub fn track_expiry(&mut self, key: &ValueType) {
+        if !self.volatile_index.contains_key(key) {
+            self.volatile_index.insert(key.clone(), self.volatile.len());
+            self.volatile.push(key.clone());
+        }
+    }
+
+    fn untrack_expiry(&mut self, key: &ValueType) {
+        if let Some(pos) = self.volatile_index.remove(key) {
+            self.volatile.swap_remove(pos);
+            if let Some(moved) = self.volatile.get(pos) {
+                self.volatile_index.insert(moved.clone(), pos);
+            }
+        }
+    }
+
+    /// Removes `key` because its TTL passed, it's counted in the stats and queued for the replicas.
+    pub fn expire(&mut self, key: &ValueType) -> Option<DataEntry> {
+        let entry = self.remove(key)?;
+        self.record_expired(key.clone());
+        Some(entry)
+    }
+
+    pub fn record_expired(&mut self, key: ValueType) {
+        self.touch(&key);
+        self.notify(EventClass::Expired, "expired", &key);
+        self.stats.expired_keys += 1;
+        self.expired.push(key);
+    }
+
+    /// Expired keys since the last call, each of them should reach the replicas as a DEL.
+    pub fn take_expired(&mut self) -> Vec<ValueType> {
+        std::mem::take(&mut self.expired)
+    }
+
+    pub fn purge_expired(&mut self) {
+        let expired_keys = self
+            .entries
+            .iter()
+            .filter(|(_key, entry)| entry.is_expired())
+            .map(|(key, _entry)| key.clone())
+            .collect::<Vec<_>>();
+        for key in expired_keys.iter() {
+            self.expire(key);
+        }
+    }
+
+    /// Checks up to `samples` random keys with a TTL and expires the ones whose TTL passed.
+    /// Returns how many keys were checked and how many of them expired.
+    pub fn expire_sample(&mut self, samples: usize) -> (usize, usize) {
+        let mut rng = thread_rng();
+        let (mut checked, mut expired) = (0, 0);
+        while checked < samples && !self.volatile.is_empty() {
+            let key = self.volatile[rng.gen_range(0..self.volatile.len())].clone();
+            match self.entries.get(&key) {
+                Some(entry) if entry.is_expired() => {
+                    self.expire(&key);
+                    expired += 1;
+                }
+                Some(entry) if entry.expired_at_unix_millis.is_some() => (),
+                // deleted or persisted since it got a TTL
+                _ => self.untrack_expiry(&key),
+            }
+            checked += 1;
+        }
+        (checked, expired)
+    }
+}
@@ -22,7 +22,13 @@ impl RespType {
         use RespType::*;
         match self {
             SimpleString(s) => format!("+{}\r\n", s).as_bytes().to_vec(),
-            SimpleError(err) => format!("-ERR {}\r\n", err).as_bytes().to_vec(),
+            SimpleError(err) => match err.split_once(' ') {
+                // errors that already carry their own code e.g `WRONGTYPE ...` are sent as is
+                Some((code, _)) if code.chars().all(|c| c.is_ascii_uppercase()) => {
+                    format!("-{}\r\n", err).as_bytes().to_vec()
+                }
+                _ => format!("-ERR {}\r\n", err).as_bytes().to_vec(),
+            },
             Integer(num) => format!(":{}\r\n", num).as_bytes().to_vec(),
             BulkString(s) => format!("${}\r\n{}\r\n", s.len(), s).as_bytes().to_vec(),
             Array(values) => {
@@ -33,7 +39,7 @@ impl RespType {
                 ret
             }
             WildCard(s) => s.to_vec(),
-            Null => format!("$-1\r\n").as_bytes().to_vec(),
+            Null => b"$-1\r\n".to_vec(),
         }
     }
 }
@@ -0,0 +1,115 @@
+// Cursor based iteration used by the *SCAN commands.
+//
+// Every element has a fixed position, which is the hash of its name, and the cursor is just the
+// smallest position the next call should return. Positions never change while the collection
+// grows or shrinks, so every element that is present for the whole iteration gets returned
+// exactly once, no matter how many elements were added or removed in between calls.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+pub fn scan_position<T: Hash + ?Sized>(item: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The names of a collection ordered by their position, so a call resumes right at its cursor
+/// instead of going through the whole collection. Its owner updates it on every insert and removal.
+#[derive(Debug, Clone)]
+pub struct ScanIndex<K> {
+    // names sharing a position, which is rare, share a slot
+    positions: BTreeMap<u64, Vec<K>>,
+}
+
+impl<K> Default for ScanIndex<K> {
+    fn default() -> Self {
+        Self {
+            positions: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> ScanIndex<K> {
+    pub fn insert(&mut self, name: &K) {
+        let slot = self.positions.entry(scan_position(name)).or_default();
+        if !slot.contains(name) {
+            slot.push(name.clone());
+        }
+    }
+
+    pub fn remove(&mut self, name: &K) {
+        let position = scan_position(name);
+        if let Some(slot) = self.positions.get_mut(&position) {
+            slot.retain(|other| other != name);
+            if slot.is_empty() {
+                self.positions.remove(&position);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+    }
+
+    /// Returns the next cursor (0 when the iteration is over) and roughly `count` names positioned
+    /// at or after `cursor`. It may return a few more than `count` when several names share a
+    /// position.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<&K>) {
+        let count = count.max(1);
+        let mut names = Vec::with_capacity(count);
+        let mut slots = self.positions.range(cursor..);
+        for (_position, slot) in slots.by_ref() {
+            names.extend(slot.iter());
+            if names.len() >= count {
+                break;
+            }
+        }
+        let cursor = slots.next().map_or(0, |(position, _slot)| *position);
+        (cursor, names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn full_iteration_in_steps() {
+        let mut index = ScanIndex::default();
+        for n in 0..100 {
+            index.insert(&n.to_string());
+        }
+        index.insert(&"7".to_string());
+        let (mut cursor, mut seen, mut calls) = (0, HashSet::new(), 0);
+        loop {
+            let (next, names) = index.scan(cursor, 10);
+            assert!(names.len() >= 10 || next == 0);
+            let names = names.into_iter().cloned().collect::<Vec<_>>();
+            for name in names.iter() {
+                assert!(seen.insert(name.clone()), "{} returned twice", name);
+            }
+            // removing elements already returned doesn't make the iteration skip any other
+            index.remove(&names[0]);
+            calls += 1;
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        assert_eq!(seen.len(), 100);
+        assert_eq!(calls, 10);
+    }
+
+    #[test]
+    fn empty_and_removed() {
+        let mut index = ScanIndex::<String>::default();
+        assert_eq!(index.scan(0, 10), (0, vec![]));
+        index.insert(&"a".to_string());
+        index.remove(&"a".to_string());
+        index.remove(&"b".to_string());
+        assert_eq!(index.scan(0, 10), (0, vec![]));
+    }
+}
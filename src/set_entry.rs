@@ -0,0 +1,117 @@
+use crate::constants::SET_MAX_INTSET_ENTRIES;
+
+use std::collections::HashSet;
+
+/// Small sets made only of integers are kept as a sorted vector of integers (like redis intset),
+/// they get converted into a hash table once a non integer member is added or they grow past
+/// `SET_MAX_INTSET_ENTRIES`.
+#[derive(Debug)]
+pub enum SetEntry {
+    IntSet(Vec<i64>),
+    HashTable(HashSet<String>),
+}
+
+impl Default for SetEntry {
+    fn default() -> Self {
+        Self::IntSet(Vec::new())
+    }
+}
+
+// only members that are printed back exactly the same are stored as integers e.g `007` isn't
+fn as_int(member: &str) -> Option<i64> {
+    member
+        .parse::<i64>()
+        .ok()
+        .filter(|int| int.to_string() == member)
+}
+
+impl SetEntry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            Self::IntSet(_) => "intset",
+            Self::HashTable(_) => "hashtable",
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::IntSet(ints) => ints.len(),
+            Self::HashTable(members) => members.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, member: &str) -> bool {
+        match self {
+            Self::IntSet(ints) => as_int(member).is_some_and(|int| ints.binary_search(&int).is_ok()),
+            Self::HashTable(members) => members.contains(member),
+        }
+    }
+
+    pub fn members(&self) -> Vec<String> {
+        match self {
+            Self::IntSet(ints) => ints.iter().map(|int| int.to_string()).collect(),
+            Self::HashTable(members) => members.iter().cloned().collect(),
+        }
+    }
+
+    /// Returns true if `member` wasn't already in the set.
+    pub fn add(&mut self, member: String) -> bool {
+        if let Self::IntSet(ints) = self {
+            if let Some(int) = as_int(&member) {
+                return match ints.binary_search(&int) {
+                    Ok(_) => false,
+                    Err(pos) if ints.len() < SET_MAX_INTSET_ENTRIES => {
+                        ints.insert(pos, int);
+                        true
+                    }
+                    Err(_) => {
+                        self.convert_to_hash_table();
+                        self.add(member)
+                    }
+                };
+            }
+            self.convert_to_hash_table();
+        }
+        match self {
+            Self::HashTable(members) => members.insert(member),
+            Self::IntSet(_) => unreachable!(),
+        }
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        match self {
+            Self::IntSet(ints) => match as_int(member).map(|int| ints.binary_search(&int)) {
+                Some(Ok(pos)) => {
+                    ints.remove(pos);
+                    true
+                }
+                _ => false,
+            },
+            Self::HashTable(members) => members.remove(member),
+        }
+    }
+
+    fn convert_to_hash_table(&mut self) {
+        if let Self::IntSet(ints) = self {
+            *self = Self::HashTable(ints.iter().map(|int| int.to_string()).collect());
+        }
+    }
+}
+
+impl FromIterator<String> for SetEntry {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for member in iter {
+            set.add(member);
+        }
+        set
+    }
+}
@@ -26,6 +26,8 @@ pub struct SlaveMeta {
     pub wr: WriteStream,
     pub socket_addr: SocketAddr,
     pub pending_updates: Vec<u8>,
+    // database the replica applies the pending updates to, unknown right after the full resync
+    pub selected_db: Option<usize>,
 }
 
 impl SlaveMeta {
@@ -1,75 +1,132 @@
-use anyhow::Context;
-use std::collections::{BTreeMap, HashMap, VecDeque};
-use std::ops::Bound::Included;
-use crate::RespType;
-use crate::utils;
+use std::collections::BTreeMap;
+use std::ops::Bound::{Excluded, Unbounded};
+use crate::resp::RespType;
+use crate::clock::gen_millis;
+use crate::cmd::CmdError;
+use crate::consumer_group::ConsumerGroup;
+use crate::constants::{
+    RDB_TYPE_STREAM_LISTPACKS_2, RDB_TYPE_STREAM_LISTPACKS_3, STREAM_NODE_MAX_BYTES, STREAM_NODE_MAX_ENTRIES,
+};
+use crate::data_entry::ValueType;
+use crate::listpack::{Listpack, LpValue};
 use std::fmt;
 
-#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+// ordered by millis first and then by seq
+#[derive(Debug, Clone, Eq, Hash, PartialEq, PartialOrd, Ord)]
 pub struct StreamID {
     pub millis: u128,
     pub seq: u64,
 }
 
-const INVALID_SEQ: u64 = u64::MAX;
-
 impl StreamID {
-    pub fn to_xrange(id: String) -> Self {
-        match id.split("-").collect::<Vec<_>>().as_slice() {
-            &[mt @ "", sn @ ""] => {
-                // id was equal to `-`
-                Self { millis: 0, seq: 0, }
-            }
-            &[mt, sn] => {
-                let millis = mt.parse::<u128>().expect("Invalid Stream ID <millis:u128>");
-                let seq = sn.parse::<u64>().expect("Invalid Stream ID <seq:u64>");
-                Self { millis, seq }
-            }
-            &[elem @ "+"] => Self { millis: u128::MAX, seq: u64::MAX, },
-            &[mt] => {
-                let millis = mt.parse::<u128>().expect("Invalid Stream ID <millis:u128>");
-                Self { millis, seq: 0, }
-            }
-            _ => panic!("Invalid Stream ID <millis:u128>:<seq:u64>"),
+    pub const MIN: Self = Self { millis: u128::MIN, seq: u64::MIN };
+    pub const MAX: Self = Self { millis: u128::MAX, seq: u64::MAX };
+
+    /// Parses a complete `<millis>-<seq>` id, a missing seq defaults to 0.
+    pub fn parse(id: &str) -> Result<Self, CmdError> {
+        let (millis, seq) = id.split_once('-').unwrap_or((id, "0"));
+        Ok(Self {
+            millis: parse_millis(millis)?,
+            seq: parse_part(seq)?,
+        })
+    }
+
+    /// Parses an XRANGE bound, `-` and `+` stand for the smallest and greatest ids, a missing seq
+    /// takes in the whole millisecond and a `(` prefix excludes the id itself.
+    pub fn to_xrange(id: &str, start: bool) -> Result<Self, CmdError> {
+        let invalid = match start {
+            true => CmdError::InvalidRangeStart,
+            false => CmdError::InvalidRangeEnd,
+        };
+        let (exclusive, id) = match id.strip_prefix('(') {
+            Some(id) => (true, id),
+            None => (false, id),
+        };
+        let bound = match id {
+            "-" | "+" if exclusive => return Err(invalid),
+            "-" => Self::MIN,
+            "+" => Self::MAX,
+            id if id.contains('-') => Self::parse(id)?,
+            millis => Self {
+                millis: parse_millis(millis)?,
+                seq: if start { u64::MIN } else { u64::MAX },
+            },
+        };
+        match (exclusive, start) {
+            (false, _) => Ok(bound),
+            (true, true) => bound.next().ok_or(invalid),
+            (true, false) => bound.prev().ok_or(invalid),
         }
     }
 
-    pub fn to_xread(id: String, last_stream_id: &StreamID) -> Self {
-        match id.split("-").collect::<Vec<_>>().as_slice() {
-            &[mt, sn] => {
-                let millis = mt.parse::<u128>().expect("Invalid Stream ID <millis:u128>");
-                let mut seq = sn.parse::<u64>().expect("Invalid Stream ID <seq:u64>");
-                Self { millis, seq }
-            }
-            &[mt @ "$"] => {
-                last_stream_id.clone()
-            }
-            &[mt] => {
-                let millis = mt.parse::<u128>().expect("Invalid Stream ID <millis:u128>");
-                Self { millis, seq: 0 }
-            }
-            _ => panic!("Invalid Stream ID <millis:u128>:<seq:u64>"),
+    fn next(&self) -> Option<Self> {
+        match self.seq.checked_add(1) {
+            Some(seq) => Some(Self { millis: self.millis, seq }),
+            None => Some(Self { millis: self.millis.checked_add(1)?, seq: 0 }),
         }
     }
 
-    pub fn to_xadd(id: String) -> Self {
-        match id.split("-").collect::<Vec<_>>().as_slice() {
-            &[mt, sn @ "*"] => {
-                let millis = mt.parse::<u128>().expect("Invalid Stream ID <millis:u128>");
-                Self { millis, seq: INVALID_SEQ, }
-            }
-            &[mt, sn] => {
-                let millis = mt.parse::<u128>().expect("Invalid Stream ID <millis:u128>");
-                let seq = sn.parse::<u64>().expect("Invalid Stream ID <seq:u64>");
-                Self { millis, seq }
-            }
-            &[star @ "*"] => {
-                let millis = utils::gen_millis();
-                Self { millis, seq: INVALID_SEQ, }
-            }
-            _ => panic!("Invalid Stream ID <millis:u128>:<seq:u64>"),
+    fn prev(&self) -> Option<Self> {
+        match self.seq.checked_sub(1) {
+            Some(seq) => Some(Self { millis: self.millis, seq }),
+            None => Some(Self { millis: self.millis.checked_sub(1)?, seq: u64::MAX }),
+        }
+    }
+
+    /// Parses an XREAD id, `$` is the last id of the stream and only known once it is read.
+    pub fn to_xread(id: &str) -> Result<Option<Self>, CmdError> {
+        match id {
+            "$" => Ok(None),
+            id => Self::parse(id).map(Some),
+        }
+    }
+
+    /// Parses an XREADGROUP id, `>` asks for entries never delivered to the group.
+    pub fn to_xreadgroup(id: &str) -> Result<Option<Self>, CmdError> {
+        match id {
+            ">" => Ok(None),
+            id => Self::parse(id).map(Some),
+        }
+    }
+
+    /// Parses an XADD id into its millis and seq, `*` generates the whole id and `<millis>-*`
+    /// only its seq, which is `None` until the stream fills it in.
+    pub fn to_xadd(id: &str) -> Result<(u128, Option<u64>), CmdError> {
+        match id.split_once('-') {
+            None if id == "*" => Ok((gen_millis(), None)),
+            Some((millis, "*")) => Ok((parse_millis(millis)?, None)),
+            _ => Self::parse(id).map(|id| (id.millis, Some(id.seq))),
         }
     }
+
+    /// The id as RDB files store it, millis and then seq, both big endian.
+    pub fn to_be_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&(self.millis as u64).to_be_bytes());
+        bytes[8..].copy_from_slice(&self.seq.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_be_bytes(bytes: &[u8; 16]) -> Self {
+        let (millis, seq) = bytes.split_at(8);
+        Self {
+            millis: u64::from_be_bytes(millis.try_into().unwrap()) as u128,
+            seq: u64::from_be_bytes(seq.try_into().unwrap()),
+        }
+    }
+}
+
+// digits alone, `parse` would also take a leading `+`
+fn parse_part<T: std::str::FromStr>(part: &str) -> Result<T, CmdError> {
+    if part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(CmdError::InvalidStreamID);
+    }
+    part.parse::<T>().map_err(|_| CmdError::InvalidStreamID)
+}
+
+// millis are a u64 in redis too, so they fit in RDB files
+fn parse_millis(part: &str) -> Result<u128, CmdError> {
+    parse_part::<u64>(part).map(u128::from)
 }
 
 impl fmt::Display for StreamID {
@@ -78,112 +135,621 @@ impl fmt::Display for StreamID {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrimThreshold {
+    MaxLen(usize),
+    MinId(StreamID),
+}
+
+/// How XADD and XTRIM cut a stream down, always from its oldest entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamTrim {
+    pub threshold: TrimThreshold,
+    // `~`, only trims whole nodes so it may leave a few more entries than asked
+    pub approx: bool,
+    // the most entries a single trim removes, 0 for no limit
+    pub limit: usize,
+}
+
+// Entries live in nodes, listpacks laid out like the ones of redis streams. A node starts with its
+// master entry, which holds the fields of the first entry added to the node:
+//   count | deleted | fields count | field ... | 0
+// Then come the entries, their ids stored as the difference with the master id (the id of that
+// first entry) and their fields left out when they match the master fields (SAMEFIELDS):
+//   flags | millis diff | seq diff | value ... | lp-count
+//   flags | millis diff | seq diff | fields count | field | value ... | lp-count
+// lp-count is the number of elements of the entry, which redis uses to walk a node backwards.
+// Deleted entries are only flagged, the node goes away once all its entries are deleted.
+const ITEM_FLAG_NONE: i64 = 0;
+const ITEM_FLAG_DELETED: i64 = 1;
+const ITEM_FLAG_SAMEFIELDS: i64 = 2;
+
+fn int_at(node: &Listpack, pos: usize) -> i64 {
+    node.get(pos).and_then(|(value, _)| value.as_int()).unwrap_or(0)
+}
+
+fn skip(node: &Listpack, pos: usize, elements: usize) -> Option<usize> {
+    (0..elements).try_fold(pos, |pos, _| node.next(pos))
+}
+
+// live and deleted entries of the node, as counted by its master entry
+fn node_counts(node: &Listpack) -> (i64, i64) {
+    let count_pos = node.first();
+    let deleted = node.next(count_pos).map_or(0, |deleted_pos| int_at(node, deleted_pos));
+    (int_at(node, count_pos), deleted)
+}
+
+fn set_node_counts(node: &mut Listpack, count: i64, deleted: i64) {
+    let count_pos = node.first();
+    node.replace(count_pos, LpValue::Int(count));
+    if let Some(deleted_pos) = node.next(count_pos) {
+        node.replace(deleted_pos, LpValue::Int(deleted));
+    }
+}
+
+// how many master fields there are and where the first one is
+fn master_fields(node: &Listpack) -> (usize, usize) {
+    match skip(node, node.first(), 2) {
+        Some(fields_pos) => (int_at(node, fields_pos) as usize, node.next(fields_pos).unwrap_or(fields_pos)),
+        None => (0, node.first()),
+    }
+}
+
+fn new_node(fields: &BTreeMap<String, String>) -> Listpack {
+    let mut node = Listpack::new();
+    node.push_int(0);
+    node.push_int(0);
+    node.push_int(fields.len() as i64);
+    for field in fields.keys() {
+        node.push_str(field);
+    }
+    node.push_int(0);
+    node
+}
+
+/// An entry of a node, decoded up to its fields.
+struct NodeEntry {
+    id: StreamID,
+    flags: i64,
+    flags_pos: usize,
+    fields_pos: usize,
+}
+
+impl NodeEntry {
+    fn deleted(&self) -> bool {
+        self.flags & ITEM_FLAG_DELETED != 0
+    }
+
+    fn same_fields(&self) -> bool {
+        self.flags & ITEM_FLAG_SAMEFIELDS != 0
+    }
+
+    /// `[id, [field, value, ...]]`
+    fn as_resp(&self, node: &Listpack) -> RespType {
+        use RespType::{Array, BulkString};
+        let fields: Vec<LpValue> = match self.same_fields() {
+            true => {
+                let (count, names_pos) = master_fields(node);
+                node.iter_from(names_pos)
+                    .take(count)
+                    .zip(node.iter_from(self.fields_pos).take(count))
+                    .flat_map(|(field, value)| [field, value])
+                    .collect()
+            }
+            false => {
+                let count = int_at(node, self.fields_pos) as usize;
+                node.iter_from(self.fields_pos).skip(1).take(2 * count).collect()
+            }
+        };
+        let fields = fields.iter().map(|field| BulkString(field.to_string())).collect();
+        Array(vec![BulkString(self.id.to_string()), Array(fields)])
+    }
+}
+
+/// Walks the entries of a node in order, deleted ones included.
+struct NodeIter<'a> {
+    master_id: &'a StreamID,
+    node: &'a Listpack,
+    master_fields: usize,
+    pos: Option<usize>,
+}
+
+impl<'a> NodeIter<'a> {
+    fn new(master_id: &'a StreamID, node: &'a Listpack) -> Self {
+        let (master_fields, names_pos) = master_fields(node);
+        // past the master fields and the 0 ending the master entry
+        let pos = skip(node, names_pos, master_fields + 1);
+        Self {
+            master_id,
+            node,
+            master_fields,
+            pos,
+        }
+    }
+}
+
+impl Iterator for NodeIter<'_> {
+    type Item = NodeEntry;
+
+    fn next(&mut self) -> Option<NodeEntry> {
+        let flags_pos = self.pos?;
+        let (flags, pos) = self.node.get(flags_pos)?;
+        let (millis, pos) = self.node.get(pos)?;
+        let (seq, fields_pos) = self.node.get(pos)?;
+        let entry = NodeEntry {
+            id: StreamID {
+                millis: self.master_id.millis + millis.as_int()? as u128,
+                seq: self.master_id.seq.wrapping_add(seq.as_int()? as u64),
+            },
+            flags: flags.as_int()?,
+            flags_pos,
+            fields_pos,
+        };
+        let fields = match entry.same_fields() {
+            true => self.master_fields,
+            false => 1 + 2 * int_at(self.node, fields_pos) as usize,
+        };
+        // the fields and then lp-count
+        self.pos = skip(self.node, fields_pos, fields + 1);
+        Some(entry)
+    }
+}
+
+/// Like redis, the nodes are indexed by their master id, in a BTreeMap where redis uses a radix
+/// tree keyed by the big endian bytes of the id. Either way a lookup finds the node holding an
+/// id, and the entries are only ever appended to the last node.
 #[derive(Debug)]
 pub struct StreamEntry {
-    // TODO:
-    // - can have just one BTreeMap ?
-    // - can store millis -> to only the last_id_seq and not all of them?
-    // - How to store them as Radix Trees as mentioned in the Redis Streams Docs?
-    stream_ids_order: BTreeMap<u128, VecDeque<u64>>,
-    data: HashMap<StreamID, BTreeMap<String, String>>,
+    nodes: BTreeMap<StreamID, Listpack>,
+    // live entries, deleted ones aren't counted
+    length: usize,
     last_stream_id: StreamID,
+    // the greatest id XDEL or a trim removed, 0-0 if none was
+    max_deleted_entry_id: StreamID,
+    // entries ever added, deleted ones included
+    entries_added: u64,
+    pub groups: BTreeMap<String, ConsumerGroup>,
 }
 
 impl StreamEntry {
     pub fn new() -> Self {
         Self {
-            stream_ids_order: BTreeMap::new(),
-            data: HashMap::new(),
+            nodes: BTreeMap::new(),
+            length: 0,
             last_stream_id: StreamID { millis: u128::MIN, seq: u64::MIN },
+            max_deleted_entry_id: StreamID::MIN,
+            entries_added: 0,
+            groups: BTreeMap::new(),
         }
     }
 
-    pub fn append_stream(&mut self, stream_id: String, data: BTreeMap<String, String>) -> Result<String, String> {
-        let mut stream_id = StreamID::to_xadd(stream_id);
-        self.update_id(&mut stream_id);
-        self.check_id(&stream_id)?;
-        if self.data.get(&stream_id) == None {
-            let result = stream_id.to_string();
-            self.stream_ids_order.entry(stream_id.millis).or_insert_with(VecDeque::new).push_back(stream_id.seq);
-            self.data.insert(stream_id, data);
-            Ok(result)
-        } else {
-            Err("The ID specified in XADD is equal or smaller than the target stream top item".to_owned())
+    /// A stream loaded from an RDB file, its nodes are taken as they are. Older RDB files don't
+    /// have `entries_added`, which is then the number of entries.
+    pub fn from_rdb(
+        nodes: BTreeMap<StreamID, Listpack>,
+        last_stream_id: StreamID,
+        max_deleted_entry_id: StreamID,
+        entries_added: Option<u64>,
+        groups: BTreeMap<String, ConsumerGroup>,
+    ) -> Self {
+        let length = nodes.values().map(|node| node_counts(node).0 as usize).sum();
+        Self {
+            nodes,
+            length,
+            last_stream_id,
+            max_deleted_entry_id,
+            entries_added: entries_added.unwrap_or(length as u64),
+            groups,
         }
     }
 
-    fn read_specific_id_as_resp(&self, stream_id: &StreamID) -> (RespType, bool) {
-        use RespType::*;
-        // TODO: ensure that id format is {}-{}
-        let mut stream_id_array = Vec::new();
-        let mut hash_items = false;
-        if let Some(stream_id_data) = self.data.get(stream_id) {
-            hash_items = true;
-            for (key, value) in stream_id_data.iter() {
-                stream_id_array.push(BulkString(key.clone()));
-                stream_id_array.push(BulkString(value.clone()));
+    /// The stream in the layout of the RDB type `rdb_type`, RDB_TYPE_STREAM_LISTPACKS added
+    /// nothing past the nodes and the groups, _2 added the counters and _3 the consumers'
+    /// active time.
+    pub fn as_rdb(&self, rdb_type: u8) -> Vec<u8> {
+        let len = ValueType::u64_to_length_encoded;
+        let id = |id: &StreamID| [len(id.millis as u64), len(id.seq)].concat();
+        let mut out = len(self.nodes.len() as u64);
+        for (master_id, node) in self.nodes.iter() {
+            out.extend(ValueType::bytes_as_rdb(&master_id.to_be_bytes()));
+            out.extend(ValueType::bytes_as_rdb(node.as_bytes()));
+        }
+        out.extend(len(self.length as u64));
+        out.extend(id(&self.last_stream_id));
+        if rdb_type >= RDB_TYPE_STREAM_LISTPACKS_2 {
+            out.extend(id(&self.first_entry_id().unwrap_or(StreamID::MIN)));
+            out.extend(id(&self.max_deleted_entry_id));
+            out.extend(len(self.entries_added));
+        }
+        out.extend(len(self.groups.len() as u64));
+        for (name, group) in self.groups.iter() {
+            out.extend(ValueType::bytes_as_rdb(name.as_bytes()));
+            out.extend(id(&group.last_delivered_id));
+            if rdb_type >= RDB_TYPE_STREAM_LISTPACKS_2 {
+                // -1 when it isn't known, like redis
+                out.extend(len(group.entries_read.unwrap_or(u64::MAX)));
+            }
+            out.extend(len(group.pending.len() as u64));
+            for (id, entry) in group.pending.iter() {
+                out.extend(id.to_be_bytes());
+                out.extend((entry.delivery_time as u64).to_le_bytes());
+                out.extend(len(entry.delivery_count));
             }
+            out.extend(len(group.consumers.len() as u64));
+            for (name, consumer) in group.consumers.iter() {
+                out.extend(ValueType::bytes_as_rdb(name.as_bytes()));
+                out.extend((consumer.seen_time as u64).to_le_bytes());
+                if rdb_type >= RDB_TYPE_STREAM_LISTPACKS_3 {
+                    let active_time = consumer.active_time.map_or(-1, |active_time| active_time as i64);
+                    out.extend(active_time.to_le_bytes());
+                }
+                // the entries themselves are in the group's PEL
+                out.extend(len(consumer.pending.len() as u64));
+                for id in consumer.pending.iter() {
+                    out.extend(id.to_be_bytes());
+                }
+            }
+        }
+        out
+    }
+
+    pub fn last_id(&self) -> &StreamID {
+        &self.last_stream_id
+    }
+
+    pub fn max_deleted_entry_id(&self) -> &StreamID {
+        &self.max_deleted_entry_id
+    }
+
+    pub fn entries_added(&self) -> u64 {
+        self.entries_added
+    }
+
+    pub fn first_entry_id(&self) -> Option<StreamID> {
+        let (_, entry) = self.range(&StreamID::MIN, &StreamID::MAX, false).next()?;
+        Some(entry.id)
+    }
+
+    pub fn last_entry_id(&self) -> Option<StreamID> {
+        let (_, entry) = self.range(&StreamID::MIN, &StreamID::MAX, true).next()?;
+        Some(entry.id)
+    }
+
+    /// Live entries from `start` to `end` included, walked backwards for `rev`.
+    fn range<'a>(
+        &'a self,
+        start: &StreamID,
+        end: &StreamID,
+        rev: bool,
+    ) -> Box<dyn Iterator<Item = (&'a Listpack, NodeEntry)> + 'a> {
+        if start > end {
+            return Box::new(std::iter::empty());
+        }
+        // the node holding `start` may begin before it
+        let first = self.nodes.range(..=start).next_back().map_or(start, |(master_id, _)| master_id);
+        let nodes = self.nodes.range(first.clone()..=end.clone());
+        let entries = |(master_id, node): (&'a StreamID, &'a Listpack)| {
+            NodeIter::new(master_id, node).map(move |entry| (node, entry))
+        };
+        let (start, end) = (start.clone(), end.clone());
+        let in_range = move |(_, entry): &(&Listpack, NodeEntry)| {
+            !entry.deleted() && start <= entry.id && entry.id <= end
+        };
+        match rev {
+            true => Box::new(
+                nodes
+                    .rev()
+                    .flat_map(move |node| entries(node).collect::<Vec<_>>().into_iter().rev())
+                    .filter(in_range),
+            ),
+            false => Box::new(nodes.flat_map(entries).filter(in_range)),
         }
-       (Array(vec![BulkString(stream_id.to_string()), Array(stream_id_array)]), hash_items)
     }
 
-    pub fn query_xread(&self, id: String) -> (RespType, bool) {
-        use RespType::*;
-        let stream_id = StreamID::to_xread(id, &self.last_stream_id);
-        let stream_upper_bound = StreamID { millis: u128::MAX, seq: u64::MAX };
-        let mut result = Vec::new();
-        let mut hash_items = false;
-        for (&id_millis, &ref all_seq_numbers_per_time) in self.stream_ids_order.range((Included(&stream_id.millis), Included(&stream_upper_bound.millis))) {
-            for seq_number in all_seq_numbers_per_time {
-                if stream_id.millis == id_millis && *seq_number <= stream_id.seq {
-                    continue;
+    /// XSETID, moves the last id and the counters, as long as the entries still fit under them.
+    pub fn set_last_id(
+        &mut self,
+        last_id: StreamID,
+        entries_added: Option<u64>,
+        max_deleted_entry_id: Option<StreamID>,
+    ) -> Result<(), CmdError> {
+        if self.last_entry_id().is_some_and(|top| last_id < top) {
+            return Err(CmdError::XSetIdSmallerThanTop);
+        }
+        if entries_added.is_some_and(|entries_added| entries_added < self.len() as u64) {
+            return Err(CmdError::XSetIdEntriesAddedTooSmall);
+        }
+        if max_deleted_entry_id.as_ref().is_some_and(|max_deleted| last_id < *max_deleted) {
+            return Err(CmdError::XSetIdSmallerThanMaxDeleted);
+        }
+        self.last_stream_id = last_id;
+        if let Some(entries_added) = entries_added {
+            self.entries_added = entries_added;
+        }
+        if let Some(max_deleted_entry_id) = max_deleted_entry_id {
+            self.max_deleted_entry_id = max_deleted_entry_id;
+        }
+        Ok(())
+    }
+
+    /// How many entries the group has yet to read, `None` when deleted entries past its last
+    /// delivered id make it impossible to tell.
+    pub fn group_lag(&self, group: &ConsumerGroup) -> Option<u64> {
+        if group.last_delivered_id >= self.last_stream_id {
+            return Some(0);
+        }
+        // without deletes past the group, the entries after it are exactly the unread ones
+        if self.max_deleted_entry_id < group.last_delivered_id || self.max_deleted_entry_id == StreamID::MIN {
+            return Some(self.ids_after(&group.last_delivered_id, None).len() as u64);
+        }
+        group
+            .entries_read
+            .map(|entries_read| self.entries_added.saturating_sub(entries_read))
+    }
+
+    /// Entries the group read so far, counting the ones deleted since.
+    pub fn group_entries_read(&self, group: &ConsumerGroup) -> Option<u64> {
+        group
+            .entries_read
+            .or_else(|| Some(self.entries_added - self.group_lag(group)?))
+    }
+
+    pub fn contains(&self, id: &StreamID) -> bool {
+        self.range(id, id, false).next().is_some()
+    }
+
+    /// `[id, [field, value, ...]]` of the entry, `None` if there is no such entry.
+    pub fn entry_as_resp(&self, id: &StreamID) -> Option<RespType> {
+        let (node, entry) = self.range(id, id, false).next()?;
+        Some(entry.as_resp(node))
+    }
+
+    /// Ids of the entries after `id`, at most `count` of them.
+    pub fn ids_after(&self, id: &StreamID, count: Option<usize>) -> Vec<StreamID> {
+        let start = match id.next() {
+            Some(start) => start,
+            None => return Vec::new(),
+        };
+        self.range(&start, &StreamID::MAX, false)
+            .take(count.unwrap_or(usize::MAX))
+            .map(|(_, entry)| entry.id)
+            .collect()
+    }
+
+    /// XREADGROUP with `>`, hands the entries the group didn't deliver yet to `consumer`.
+    /// `None` if there is no such group.
+    pub fn read_group_new(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        count: Option<usize>,
+        noack: bool,
+    ) -> Option<Vec<(StreamID, RespType)>> {
+        let last_delivered_id = self.groups.get(group)?.last_delivered_id.clone();
+        let ids = self.ids_after(&last_delivered_id, count);
+        let group = self.groups.get_mut(group)?;
+        group.touch_consumer(consumer);
+        for id in ids.iter() {
+            group.deliver(id, consumer, noack);
+        }
+        Some(
+            ids.into_iter()
+                .filter_map(|id| self.entry_as_resp(&id).map(|entry| (id, entry)))
+                .collect(),
+        )
+    }
+
+    /// XREADGROUP with an id, replies with the entries still pending for `consumer` after `id`.
+    /// Entries deleted since they were delivered come back as `[id, nil]`.
+    pub fn read_group_history(
+        &mut self,
+        group: &str,
+        consumer: &str,
+        id: &StreamID,
+        count: Option<usize>,
+    ) -> Option<Vec<(StreamID, RespType)>> {
+        let group = self.groups.get_mut(group)?;
+        let ids = group
+            .touch_consumer(consumer)
+            .pending
+            .range((Excluded(id), Unbounded))
+            .take(count.unwrap_or(usize::MAX))
+            .cloned()
+            .collect::<Vec<_>>();
+        for id in ids.iter() {
+            group.redeliver(id);
+        }
+        let entries = ids
+            .into_iter()
+            .map(|id| {
+                let entry = self.entry_as_resp(&id).unwrap_or_else(|| {
+                    RespType::Array(vec![RespType::BulkString(id.to_string()), RespType::WildCard("*-1\r\n".into())])
+                });
+                (id, entry)
+            })
+            .collect();
+        Some(entries)
+    }
+
+    pub fn append_stream(&mut self, stream_id: &str, data: BTreeMap<String, String>) -> Result<String, String> {
+        let auto = stream_id == "*";
+        let (mut millis, seq) = StreamID::to_xadd(stream_id).map_err(|err| err.to_string())?;
+        // if the clock went backwards, generated ids keep increasing from the last one
+        if auto && millis < self.last_stream_id.millis {
+            millis = self.last_stream_id.millis;
+        }
+        let seq = seq.unwrap_or_else(|| self.next_seq(millis));
+        let stream_id = StreamID { millis, seq };
+        self.check_id(&stream_id)?;
+        let result = stream_id.to_string();
+        self.entries_added += 1;
+        self.insert_entry(&stream_id, &data);
+        Ok(result)
+    }
+
+    // appends to the last node, or to a new one once it's full
+    fn insert_entry(&mut self, id: &StreamID, data: &BTreeMap<String, String>) {
+        let fits = match self.nodes.last_key_value() {
+            Some((master_id, node)) => {
+                let (count, deleted) = node_counts(node);
+                ((count + deleted) as usize) < STREAM_NODE_MAX_ENTRIES
+                    && node.bytes() < STREAM_NODE_MAX_BYTES
+                    && id.millis - master_id.millis <= i64::MAX as u128
+            }
+            None => false,
+        };
+        if !fits {
+            self.nodes.insert(id.clone(), new_node(data));
+        }
+        let mut last = match self.nodes.last_entry() {
+            Some(last) => last,
+            None => return,
+        };
+        let master_id = last.key().clone();
+        let node = last.get_mut();
+        let (count, deleted) = node_counts(node);
+        set_node_counts(node, count + 1, deleted);
+        let (master_fields, names_pos) = master_fields(node);
+        let same_fields = master_fields == data.len()
+            && node
+                .iter_from(names_pos)
+                .zip(data.keys())
+                .all(|(name, field)| name == LpValue::from_string(field));
+        node.push_int(if same_fields { ITEM_FLAG_SAMEFIELDS } else { ITEM_FLAG_NONE });
+        node.push_int((id.millis - master_id.millis) as i64);
+        node.push_int(id.seq.wrapping_sub(master_id.seq) as i64);
+        let lp_count = match same_fields {
+            true => {
+                for value in data.values() {
+                    node.push_str(value);
+                }
+                data.len()
+            }
+            false => {
+                node.push_int(data.len() as i64);
+                for (field, value) in data.iter() {
+                    node.push_str(field);
+                    node.push_str(value);
                 }
-                let stream_id = StreamID { millis: id_millis, seq: *seq_number };
-                let (data_as_resp, data_has_items) = self.read_specific_id_as_resp(&stream_id);
-                hash_items = hash_items | data_has_items;
-                result.push(data_as_resp);
+                1 + 2 * data.len()
             }
+        };
+        node.push_int(3 + lp_count as i64);
+        self.length += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    fn deleted_up_to(&mut self, id: &StreamID) {
+        self.max_deleted_entry_id = self.max_deleted_entry_id.clone().max(id.clone());
+    }
+
+    /// Removes the entry, the last id stays the same even if it was the last entry.
+    pub fn delete(&mut self, id: &StreamID) -> bool {
+        let found = self.nodes.range(..=id).next_back().and_then(|(master_id, node)| {
+            let entry = NodeIter::new(master_id, node).find(|entry| entry.id == *id && !entry.deleted())?;
+            Some((master_id.clone(), entry))
+        });
+        let (master_id, entry) = match found {
+            Some(found) => found,
+            None => return false,
+        };
+        let node = self.nodes.get_mut(&master_id).unwrap();
+        let (count, deleted) = node_counts(node);
+        if count > 1 {
+            node.replace(entry.flags_pos, LpValue::Int(entry.flags | ITEM_FLAG_DELETED));
+            set_node_counts(node, count - 1, deleted + 1);
+        } else {
+            self.nodes.remove(&master_id);
         }
-        (Array(result), hash_items)
+        self.length -= 1;
+        self.deleted_up_to(id);
+        true
     }
 
-    pub fn query_xrange(&self, start_id: String, end_id: String) -> (RespType, bool) {
-        use RespType::*;
-        // TODO: make sure that start less than end
-        let mut start_id = StreamID::to_xrange(start_id);
-        let mut end_id = StreamID::to_xrange(end_id);
-        let mut result = Vec::new();
-        let mut hash_items = false;
-        for (&id_millis, &ref all_seq_numbers_per_time) in self.stream_ids_order.range((Included(&start_id.millis), Included(&end_id.millis))) {
-            for seq_number in all_seq_numbers_per_time {
-                if start_id.millis == id_millis && *seq_number < start_id.seq {
-                    continue;
-                }
-                if end_id.millis == id_millis && *seq_number > end_id.seq {
-                    break;
+    /// Removes the oldest entries as `trim` asks, returning how many were removed.
+    pub fn trim(&mut self, trim: &StreamTrim) -> usize {
+        let mut excess = match &trim.threshold {
+            TrimThreshold::MaxLen(max_len) => self.len().saturating_sub(*max_len),
+            TrimThreshold::MinId(min_id) => self
+                .range(&StreamID::MIN, min_id, false)
+                .take_while(|(_, entry)| entry.id < *min_id)
+                .count(),
+        };
+        if trim.limit > 0 {
+            excess = excess.min(trim.limit);
+        }
+        let mut removed = 0;
+        while removed < excess {
+            let mut first = match self.nodes.first_entry() {
+                Some(first) => first,
+                None => break,
+            };
+            let master_id = first.key().clone();
+            let (count, deleted) = node_counts(first.get());
+            let live = NodeIter::new(&master_id, first.get()).filter(|entry| !entry.deleted());
+            if removed + count as usize <= excess {
+                let last = live.last();
+                first.remove();
+                // trimming goes from the oldest entries, so each one is the greatest deleted yet
+                if let Some(last) = last {
+                    self.deleted_up_to(&last.id);
                 }
-                let stream_id = StreamID { millis: id_millis, seq: *seq_number };
-                let (data_as_resp, data_has_items) = self.read_specific_id_as_resp(&stream_id);
-                hash_items = hash_items | data_has_items;
-                result.push(data_as_resp);
+                removed += count as usize;
+                continue;
+            }
+            // `~` only removes whole nodes
+            if trim.approx {
+                break;
+            }
+            let entries = live.take(excess - removed).collect::<Vec<_>>();
+            let node = first.get_mut();
+            // flags keep their size, so the positions stay right until the counts are updated
+            for entry in entries.iter() {
+                node.replace(entry.flags_pos, LpValue::Int(entry.flags | ITEM_FLAG_DELETED));
             }
+            set_node_counts(node, count - entries.len() as i64, deleted + entries.len() as i64);
+            if let Some(last) = entries.last() {
+                self.deleted_up_to(&last.id);
+            }
+            removed += entries.len();
         }
-        (Array(result), hash_items)
+        self.length -= removed;
+        removed
     }
 
-    fn update_id(&self, id: &mut StreamID) {
-        if id.seq == INVALID_SEQ {
-            if let Some(last_id_millis) = self.stream_ids_order.get(&id.millis) {
+    pub fn query_xread(&self, stream_id: &StreamID, count: Option<usize>) -> (RespType, bool) {
+        let start = match stream_id.next() {
+            Some(start) => start,
+            None => return (RespType::Array(Vec::new()), false),
+        };
+        let result: Vec<_> = self
+            .range(&start, &StreamID::MAX, false)
+            .take(count.unwrap_or(usize::MAX))
+            .map(|(node, entry)| entry.as_resp(node))
+            .collect();
+        let has_items = !result.is_empty();
+        (RespType::Array(result), has_items)
+    }
 
-                if let Some(last_id_seq) = last_id_millis.back() {
-                    id.seq = last_id_seq + 1;
-                } else {
-                    id.seq = if id.millis == 0 { 1 } else { 0 };
-                }
+    /// Entries from `start_id` to `end_id` included, walked backwards for XREVRANGE. The walk
+    /// stops as soon as `count` entries were found.
+    pub fn query_xrange(&self, start_id: &StreamID, end_id: &StreamID, count: Option<usize>, rev: bool) -> RespType {
+        let entries = self
+            .range(start_id, end_id, rev)
+            .take(count.unwrap_or(usize::MAX))
+            .map(|(node, entry)| entry.as_resp(node))
+            .collect();
+        RespType::Array(entries)
+    }
 
-            } else {
-                id.seq = if id.millis == 0 { 1 } else { 0 };
-            }
+    fn next_seq(&self, millis: u128) -> u64 {
+        // compared to the last id rather than the entries, which may have been deleted
+        match millis == self.last_stream_id.millis {
+            true => self.last_stream_id.seq.saturating_add(1),
+            false => 0,
         }
     }
 
@@ -192,15 +758,101 @@ impl StreamEntry {
         if id.millis == 0 && id.seq == 0 {
             return Err("The ID specified in XADD must be greater than 0-0".to_owned());
         }
-        // inserted id time must be greater than last inserted time
-        // last_id seq in same "time" can't be greater than the next id to insert
-        if let Some((last_id_millis, last_id_seq)) = self.stream_ids_order.last_key_value() {
-            let last_id_seq = last_id_seq.back().unwrap_or(&0);
-            if *last_id_millis > id.millis || (*last_id_millis == id.millis && *last_id_seq > id.seq) {
-                return Err("The ID specified in XADD is equal or smaller than the target stream top item".to_owned());
-            }
+        // ids only ever increase, even after the top item was deleted
+        if *id <= self.last_stream_id {
+            return Err("The ID specified in XADD is equal or smaller than the target stream top item".to_owned());
         }
         self.last_stream_id = id.clone();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(millis: u128, seq: u64) -> StreamID {
+        StreamID { millis, seq }
+    }
+
+    #[test]
+    fn parse_complete_ids() {
+        assert_eq!(StreamID::parse("1526919030474-55").unwrap(), id(1526919030474, 55));
+        assert_eq!(StreamID::parse("5").unwrap(), id(5, 0));
+        assert_eq!(StreamID::parse("0-0").unwrap(), StreamID::MIN);
+        assert_eq!(StreamID::parse(&format!("1-{}", u64::MAX)).unwrap(), id(1, u64::MAX));
+    }
+
+    #[test]
+    fn parse_rejects_garbage() {
+        for bad in ["", "-", "+", "$", ">", "*", "abc", "1-", "-1", "1-a", "a-1", "1-2-3", "+1-2", "1-+2", " 1-2", "1.5-0", "1-*"] {
+            assert!(matches!(StreamID::parse(bad), Err(CmdError::InvalidStreamID)), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_overflow() {
+        let too_big_seq = format!("1-{}", u64::MAX as u128 + 1);
+        let too_big_millis = format!("{}0-0", u128::MAX);
+        for bad in [too_big_seq, too_big_millis] {
+            assert!(matches!(StreamID::parse(&bad), Err(CmdError::InvalidStreamID)), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn xrange_bounds() {
+        assert_eq!(StreamID::to_xrange("-", true).unwrap(), StreamID::MIN);
+        assert_eq!(StreamID::to_xrange("+", false).unwrap(), StreamID::MAX);
+        assert_eq!(StreamID::to_xrange("5", true).unwrap(), id(5, 0));
+        assert_eq!(StreamID::to_xrange("5", false).unwrap(), id(5, u64::MAX));
+        assert_eq!(StreamID::to_xrange("5-3", false).unwrap(), id(5, 3));
+        assert_eq!(StreamID::to_xrange("(5-3", true).unwrap(), id(5, 4));
+        assert_eq!(StreamID::to_xrange("(5-0", false).unwrap(), id(4, u64::MAX));
+        assert!(matches!(StreamID::to_xrange("(-", true), Err(CmdError::InvalidRangeStart)));
+        assert!(matches!(StreamID::to_xrange("(+", false), Err(CmdError::InvalidRangeEnd)));
+        assert!(matches!(StreamID::to_xrange("(0-0", false), Err(CmdError::InvalidRangeEnd)));
+        for bad in ["$", ">", "*", "abc", "5-x", "(", "(abc"] {
+            assert!(matches!(StreamID::to_xrange(bad, true), Err(CmdError::InvalidStreamID)), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn xread_ids() {
+        assert_eq!(StreamID::to_xread("$").unwrap(), None);
+        assert_eq!(StreamID::to_xread("5-1").unwrap(), Some(id(5, 1)));
+        for bad in [">", "*", "+", "-", "abc"] {
+            assert!(matches!(StreamID::to_xread(bad), Err(CmdError::InvalidStreamID)), "{:?}", bad);
+        }
+        assert_eq!(StreamID::to_xreadgroup(">").unwrap(), None);
+        assert_eq!(StreamID::to_xreadgroup("0").unwrap(), Some(StreamID::MIN));
+        assert!(matches!(StreamID::to_xreadgroup("$"), Err(CmdError::InvalidStreamID)));
+    }
+
+    #[test]
+    fn xadd_ids() {
+        let (millis, seq) = StreamID::to_xadd("*").unwrap();
+        assert!(millis > 0);
+        assert_eq!(seq, None);
+        assert_eq!(StreamID::to_xadd("5-*").unwrap(), (5, None));
+        assert_eq!(StreamID::to_xadd("5-2").unwrap(), (5, Some(2)));
+        assert_eq!(StreamID::to_xadd("5").unwrap(), (5, Some(0)));
+        assert_eq!(StreamID::to_xadd(&format!("5-{}", u64::MAX)).unwrap(), (5, Some(u64::MAX)));
+        for bad in ["abc", "*-1", "*-*", "-*", "x-*", "5-**", "5-1-*", "$", ">", "+"] {
+            assert!(matches!(StreamID::to_xadd(bad), Err(CmdError::InvalidStreamID)), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn append_refuses_bad_ids() {
+        let mut stream = StreamEntry::new();
+        let data = BTreeMap::from([("f".to_string(), "v".to_string())]);
+        assert!(stream.append_stream("abc", data.clone()).is_err());
+        assert_eq!(stream.append_stream("5-*", data.clone()).unwrap(), "5-0");
+        assert_eq!(stream.append_stream("5-*", data.clone()).unwrap(), "5-1");
+        assert!(stream.append_stream("5-1", data.clone()).is_err());
+        let max_seq = format!("5-{}", u64::MAX);
+        assert_eq!(stream.append_stream(&max_seq, data.clone()).unwrap(), max_seq);
+        assert!(stream.append_stream("5-*", data).is_err());
+        assert_eq!(stream.len(), 3);
+    }
+}
@@ -1,18 +1,13 @@
 use crate::constants::EOF;
 use crate::rdb::RDBHeader;
 use crate::resp::RespType;
-use crate::redis::{AMRedisDB, db_as_rdb};
+use crate::redis::{Databases, db_as_rdb};
 use crate::cmd::CmdError;
 
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
 use rand::{thread_rng, Rng};
 use tokio::sync::Mutex;
 
-pub fn gen_millis() -> u128 {
-    SystemTime::now().duration_since(UNIX_EPOCH).expect("Time Went Backwards").as_millis()
-}
-
 pub fn random_string(length: usize) -> String {
     thread_rng()
         .sample_iter(&rand::distributions::Alphanumeric)
@@ -31,9 +26,9 @@ pub fn take_upto<'a, const N: usize>(data: &mut &'a [u8]) -> Option<&'a [u8; N]>
     }
 }
 
-pub async fn dump_rdb_file(header: &RDBHeader, redis: AMRedisDB) -> Vec<u8> {
+pub async fn dump_rdb_file(header: &RDBHeader, databases: &Databases) -> Vec<u8> {
     let mut out = header.as_rdb();
-    out.extend_from_slice(&db_as_rdb(redis).await[..]);
+    out.extend_from_slice(&db_as_rdb(databases).await[..]);
     out.push(EOF);
     out
 }
@@ -45,3 +40,226 @@ pub fn unpack_bulk_string(resp: &RespType) -> Result<String, CmdError> {
     }
 }
 
+
+pub fn next_arg<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<String, CmdError> {
+    unpack_bulk_string(args_iter.next().ok_or_else(|| CmdError::MissingArgs)?)
+}
+
+pub fn next_int_arg<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<i64, CmdError> {
+    next_arg(args_iter)?
+        .parse::<i64>()
+        .map_err(|_| CmdError::NotInteger)
+}
+
+/// The next argument if it's a finite float, kept as it was typed so increments can be added
+/// exactly with `add_floats`.
+pub fn next_float_text_arg<'a>(args_iter: &mut impl Iterator<Item = &'a RespType>) -> Result<String, CmdError> {
+    let arg = next_arg(args_iter)?;
+    match arg.parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok(arg),
+        _ => Err(CmdError::NotFloat),
+    }
+}
+
+// significant digits floats are replied with, like redis does
+const FLOAT_DIGITS: usize = 17;
+
+/// Formats floats the way redis replies with them: rounded to 17 significant digits in fixed
+/// notation without trailing zeros i.e `3`, `10.5` or `0.30000000000000004`, never `-0`.
+pub fn format_float(value: f64) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    // `{:e}` rounds the exact binary value to the digits asked for
+    let formatted = format!("{:.*e}", FLOAT_DIGITS - 1, value.abs());
+    let (mantissa, exp) = formatted.split_once('e').unwrap();
+    let digits = mantissa.bytes().filter(u8::is_ascii_digit).map(|digit| digit - b'0').collect();
+    let exp = exp.parse::<i64>().unwrap() - (FLOAT_DIGITS as i64 - 1);
+    Decimal { negative: value < 0.0, digits, exp }.format()
+}
+
+/// Adds two floats given as text, both already known to parse as finite floats. The sum is
+/// exact before being rounded like `format_float`, the way redis' long doubles make `0.1` plus
+/// `0.2` come out as `0.3` in INCRBYFLOAT and HINCRBYFLOAT.
+pub fn add_floats(current: &str, increment: &str) -> String {
+    Decimal::parse(current).add(&Decimal::parse(increment)).format()
+}
+
+// `digits` × 10^`exp`, zero has no digits
+struct Decimal {
+    negative: bool,
+    digits: Vec<u8>,
+    exp: i64,
+}
+
+impl Decimal {
+    const ZERO: Decimal = Decimal { negative: false, digits: Vec::new(), exp: 0 };
+
+    fn parse(text: &str) -> Self {
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (mantissa, exp) = match text.split_once(['e', 'E']) {
+            Some((mantissa, exp)) => match exp.parse::<i64>() {
+                Ok(exp) => (mantissa, exp),
+                // such exponents only parse as finite with a zero mantissa or when tiny
+                Err(_) => return Self::ZERO,
+            },
+            None => (text, 0),
+        };
+        let fraction = mantissa.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+        let digits = mantissa.bytes().filter(u8::is_ascii_digit).map(|digit| digit - b'0').collect();
+        let decimal = Self { negative, digits, exp: exp.saturating_sub(fraction as i64) }.normalized();
+        // far below what a float can hold, it would only take huge alignments
+        match decimal.exp + (decimal.digits.len() as i64) < -400 {
+            true => Self::ZERO,
+            false => decimal,
+        }
+    }
+
+    // drops the leading and trailing zeros
+    fn normalized(mut self) -> Self {
+        let leading = self.digits.iter().take_while(|digit| **digit == 0).count();
+        self.digits.drain(..leading);
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+            self.exp += 1;
+        }
+        match self.digits.is_empty() {
+            true => Self::ZERO,
+            false => self,
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let exp = self.exp.min(other.exp);
+        let len = 1 + [self, other]
+            .iter()
+            .map(|decimal| decimal.digits.len() + (decimal.exp - exp) as usize)
+            .max()
+            .unwrap();
+        // both aligned to `exp` and padded to `len`, so they compare like numbers do
+        let aligned = |decimal: &Self| {
+            let mut digits = vec![0; len - decimal.digits.len() - (decimal.exp - exp) as usize];
+            digits.extend(&decimal.digits);
+            digits.resize(len, 0);
+            digits
+        };
+        let (mut digits, other_digits) = (aligned(self), aligned(other));
+        let mut negative = self.negative;
+        if self.negative == other.negative {
+            let mut carry = 0;
+            for (digit, other_digit) in digits.iter_mut().zip(other_digits.iter()).rev() {
+                let sum = *digit + other_digit + carry;
+                (*digit, carry) = (sum % 10, sum / 10);
+            }
+        } else {
+            let (mut larger, smaller) = match digits >= other_digits {
+                true => (digits, other_digits),
+                false => {
+                    negative = other.negative;
+                    (other_digits, digits)
+                }
+            };
+            let mut borrow = 0;
+            for (digit, smaller_digit) in larger.iter_mut().zip(smaller.iter()).rev() {
+                let subtrahend = smaller_digit + borrow;
+                (*digit, borrow) = match *digit >= subtrahend {
+                    true => (*digit - subtrahend, 0),
+                    false => (*digit + 10 - subtrahend, 1),
+                };
+            }
+            digits = larger;
+        }
+        Self { negative, digits, exp }.normalized()
+    }
+
+    fn format(self) -> String {
+        let Self { negative, mut digits, mut exp } = self.normalized();
+        if digits.len() > FLOAT_DIGITS {
+            exp += (digits.len() - FLOAT_DIGITS) as i64;
+            let round_up = digits[FLOAT_DIGITS] >= 5;
+            digits.truncate(FLOAT_DIGITS);
+            if round_up {
+                match digits.iter().rposition(|digit| *digit != 9) {
+                    Some(at) => {
+                        digits[at] += 1;
+                        digits.truncate(at + 1);
+                        exp += (FLOAT_DIGITS - at - 1) as i64;
+                    }
+                    // all nines carry into a new digit
+                    None => {
+                        digits = vec![1];
+                        exp += FLOAT_DIGITS as i64;
+                    }
+                }
+            }
+        }
+        let Self { digits, exp, .. } = Self { negative, digits, exp }.normalized();
+        if digits.is_empty() {
+            return "0".to_string();
+        }
+        let digits = digits.iter().map(|digit| char::from(b'0' + digit)).collect::<String>();
+        // how many of the digits come before the point
+        let integer = digits.len() as i64 + exp;
+        let formatted = if exp >= 0 {
+            digits + &"0".repeat(exp as usize)
+        } else if integer > 0 {
+            let (integer, fraction) = digits.split_at(integer as usize);
+            format!("{}.{}", integer, fraction)
+        } else {
+            format!("0.{}{}", "0".repeat(-integer as usize), digits)
+        };
+        match negative {
+            true => format!("-{}", formatted),
+            false => formatted,
+        }
+    }
+}
+
+/// Parses memory sizes the way redis config does i.e `1024`, `64k`, `32mb` or `1gb`.
+pub fn parse_memory(value: &str) -> Option<usize> {
+    let value = value.trim().to_lowercase();
+    let digits = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let unit: usize = match &value[digits..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    value[..digits].parse::<usize>().ok()?.checked_mul(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_increments() {
+        assert_eq!(add_floats("0.1", "0.2"), "0.3");
+        assert_eq!(add_floats("10.5", "0.1"), "10.6");
+        assert_eq!(add_floats("5.0e3", "2.0e2"), "5200");
+        assert_eq!(add_floats("1e20", "1"), "100000000000000000000");
+        assert_eq!(add_floats("123456789012345678901", "0"), "123456789012345680000");
+        assert_eq!(add_floats("3", "-3"), "0");
+        assert_eq!(add_floats("-0.5", "0.25"), "-0.25");
+        assert_eq!(add_floats("9.99999999999999999", "0"), "10");
+        assert_eq!(add_floats("1e-400", "1"), "1");
+    }
+
+    #[test]
+    fn float_replies() {
+        assert_eq!(format_float(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(format_float(10.5 + 0.1), "10.6");
+        assert_eq!(format_float(5.0e3 + 2.0e2), "5200");
+        assert_eq!(format_float(1e20), "100000000000000000000");
+        assert_eq!(format_float(-0.0), "0");
+        assert_eq!(format_float(-2.5), "-2.5");
+        assert_eq!(format_float(f64::INFINITY), "inf");
+    }
+}
@@ -0,0 +1,290 @@
+use rand::random;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Boundary of a score range e.g `1.5` (inclusive) or `(1.5` (exclusive).
+#[derive(Debug, Clone, Copy)]
+pub enum ScoreBound {
+    Inclusive(f64),
+    Exclusive(f64),
+}
+
+impl ScoreBound {
+    pub fn parse(bound: &str) -> Option<Self> {
+        let (bound, exclusive) = match bound.strip_prefix('(') {
+            Some(bound) => (bound, true),
+            None => (bound, false),
+        };
+        let score = parse_score(bound)?;
+        match exclusive {
+            true => Some(Self::Exclusive(score)),
+            false => Some(Self::Inclusive(score)),
+        }
+    }
+}
+
+/// Boundary of a lexicographical range i.e `-`, `+`, `[member` or `(member`.
+#[derive(Debug, Clone)]
+pub enum LexBound {
+    Min,
+    Max,
+    Inclusive(String),
+    Exclusive(String),
+}
+
+impl LexBound {
+    pub fn parse(bound: &str) -> Option<Self> {
+        match bound {
+            "-" => Some(Self::Min),
+            "+" => Some(Self::Max),
+            _ if bound.starts_with('[') => Some(Self::Inclusive(bound[1..].to_string())),
+            _ if bound.starts_with('(') => Some(Self::Exclusive(bound[1..].to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a score the way redis does, accepting `inf`, `+inf` and `-inf` but never NaN.
+pub fn parse_score(score: &str) -> Option<f64> {
+    match score.to_lowercase().as_str() {
+        "inf" | "+inf" => Some(f64::INFINITY),
+        "-inf" => Some(f64::NEG_INFINITY),
+        score => score.parse::<f64>().ok().filter(|score| score.is_finite()),
+    }
+}
+
+// (score, member) is the order of the elements in the sorted set
+#[derive(Debug)]
+struct Node {
+    score: f64,
+    member: String,
+    priority: u64,
+    size: usize,
+    left: Option<Box<Node>>,
+    right: Option<Box<Node>>,
+}
+
+fn cmp_keys(score: f64, member: &str, other_score: f64, other_member: &str) -> Ordering {
+    score
+        .partial_cmp(&other_score)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| member.cmp(other_member))
+}
+
+fn size(node: &Option<Box<Node>>) -> usize {
+    node.as_ref().map_or(0, |node| node.size)
+}
+
+impl Node {
+    fn new(score: f64, member: String) -> Box<Self> {
+        Box::new(Self {
+            score,
+            member,
+            priority: random(),
+            size: 1,
+            left: None,
+            right: None,
+        })
+    }
+
+    fn update_size(&mut self) {
+        self.size = 1 + size(&self.left) + size(&self.right);
+    }
+}
+
+// splits the tree into the elements for which `goes_left` holds and the rest, `goes_left` must
+// hold for a prefix of the ordered elements
+fn split(node: Option<Box<Node>>, goes_left: &impl Fn(&Node) -> bool) -> (Option<Box<Node>>, Option<Box<Node>>) {
+    match node {
+        None => (None, None),
+        Some(mut node) => {
+            if goes_left(&node) {
+                let (left, right) = split(node.right.take(), goes_left);
+                node.right = left;
+                node.update_size();
+                (Some(node), right)
+            } else {
+                let (left, right) = split(node.left.take(), goes_left);
+                node.left = right;
+                node.update_size();
+                (left, Some(node))
+            }
+        }
+    }
+}
+
+// every element of `left` must come before every element of `right`
+fn merge(left: Option<Box<Node>>, right: Option<Box<Node>>) -> Option<Box<Node>> {
+    match (left, right) {
+        (None, right) => right,
+        (left, None) => left,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                left.right = merge(left.right.take(), Some(right));
+                left.update_size();
+                Some(left)
+            } else {
+                right.left = merge(Some(left), right.left.take());
+                right.update_size();
+                Some(right)
+            }
+        }
+    }
+}
+
+/// Sorted set made of a member -> score map, plus a treap ordered by (score, member) where every
+/// node knows the size of its subtree, which gives O(log n) rank and range lookups.
+#[derive(Debug, Default)]
+pub struct ZSetEntry {
+    scores: HashMap<String, f64>,
+    root: Option<Box<Node>>,
+}
+
+impl ZSetEntry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    pub fn score(&self, member: &str) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &f64)> {
+        self.scores.iter()
+    }
+
+    /// Inserts `member` or updates its score, returns true if `member` is new.
+    pub fn insert(&mut self, member: String, score: f64) -> bool {
+        // -0 and 0 are the same score
+        let score = if score == 0.0 { 0.0 } else { score };
+        let is_new = match self.scores.get(&member) {
+            Some(&old_score) if old_score == score => return false,
+            Some(&old_score) => {
+                self.remove_node(old_score, &member);
+                false
+            }
+            None => true,
+        };
+        let (left, right) = split(self.root.take(), &|node| {
+            cmp_keys(node.score, &node.member, score, &member) == Ordering::Less
+        });
+        self.root = merge(merge(left, Some(Node::new(score, member.clone()))), right);
+        self.scores.insert(member, score);
+        is_new
+    }
+
+    pub fn remove(&mut self, member: &str) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => {
+                self.remove_node(score, member);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remove_node(&mut self, score: f64, member: &str) {
+        let (left, rest) = split(self.root.take(), &|node| {
+            cmp_keys(node.score, &node.member, score, member) == Ordering::Less
+        });
+        let (_node, right) = split(rest, &|node| {
+            cmp_keys(node.score, &node.member, score, member) != Ordering::Greater
+        });
+        self.root = merge(left, right);
+    }
+
+    // number of elements for which `is_before` holds, it must hold for a prefix of the elements
+    fn count_before(&self, is_before: impl Fn(&Node) -> bool) -> usize {
+        let mut count = 0;
+        let mut current = &self.root;
+        while let Some(node) = current {
+            if is_before(node) {
+                count += size(&node.left) + 1;
+                current = &node.right;
+            } else {
+                current = &node.left;
+            }
+        }
+        count
+    }
+
+    /// 0-based rank of `member` in ascending score order.
+    pub fn rank(&self, member: &str) -> Option<usize> {
+        let score = self.score(member)?;
+        Some(self.count_before(|node| {
+            cmp_keys(node.score, &node.member, score, member) == Ordering::Less
+        }))
+    }
+
+    /// Ranks [start, end) of the elements whose score is in between `min` and `max`.
+    pub fn score_range(&self, min: ScoreBound, max: ScoreBound) -> (usize, usize) {
+        let start = self.count_before(|node| match min {
+            ScoreBound::Inclusive(min) => node.score < min,
+            ScoreBound::Exclusive(min) => node.score <= min,
+        });
+        let end = self.count_before(|node| match max {
+            ScoreBound::Inclusive(max) => node.score <= max,
+            ScoreBound::Exclusive(max) => node.score < max,
+        });
+        (start, end.max(start))
+    }
+
+    /// Ranks [start, end) of the elements whose member is in between `min` and `max`, which only
+    /// makes sense when all elements share the same score.
+    pub fn lex_range(&self, min: &LexBound, max: &LexBound) -> (usize, usize) {
+        let start = self.count_before(|node| match min {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(min) => node.member.as_str() < min.as_str(),
+            LexBound::Exclusive(min) => node.member.as_str() <= min.as_str(),
+        });
+        let end = self.count_before(|node| match max {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(max) => node.member.as_str() <= max.as_str(),
+            LexBound::Exclusive(max) => node.member.as_str() < max.as_str(),
+        });
+        (start, end.max(start))
+    }
+
+    /// Elements with rank in [start, end) in ascending order.
+    pub fn range(&self, start: usize, end: usize) -> Vec<(String, f64)> {
+        fn collect(node: &Option<Box<Node>>, offset: usize, start: usize, end: usize, out: &mut Vec<(String, f64)>) {
+            let node = match node {
+                Some(node) => node,
+                None => return,
+            };
+            let rank = offset + size(&node.left);
+            if start < rank {
+                collect(&node.left, offset, start, end, out);
+            }
+            if start <= rank && rank < end {
+                out.push((node.member.clone(), node.score));
+            }
+            if rank + 1 < end {
+                collect(&node.right, rank + 1, start, end, out);
+            }
+        }
+        let mut out = Vec::with_capacity(end.saturating_sub(start));
+        collect(&self.root, 0, start, end.min(self.len()), &mut out);
+        out
+    }
+}
+
+impl FromIterator<(String, f64)> for ZSetEntry {
+    fn from_iter<I: IntoIterator<Item = (String, f64)>>(iter: I) -> Self {
+        let mut zset = Self::new();
+        for (member, score) in iter {
+            zset.insert(member, score);
+        }
+        zset
+    }
+}